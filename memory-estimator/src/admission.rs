@@ -0,0 +1,122 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use sysinfo::System;
+use crate::job_error::JobError;
+
+/// Memory kept free for the server and the rest of the system, in MB
+pub const RESERVE_ENV: &str = "MEMORY_ESTIMATOR_RESERVE_MB";
+pub const DEFAULT_RESERVE_BYTES: u64 = 256 * 1024 * 1024;
+/// How often a waiting job looks at the available memory again when no job finishes
+const RECHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What the scheduler is holding back and has let through
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdmissionStats {
+    pub reserve_bytes: u64,
+    /// Available memory at the last admission check
+    pub available_bytes: u64,
+    /// Sum of the estimated peaks of the running jobs
+    pub committed_bytes: u64,
+    pub running_jobs: usize,
+    /// Jobs with an estimate waiting for running jobs to finish
+    pub waiting_jobs: usize,
+    pub admitted_jobs: u64,
+    pub rejected_jobs: u64,
+}
+
+/// Whether an estimated peak fits next to the running jobs. Their estimates count in full
+/// on top of what is available now, even though part of them may already be resident.
+pub fn fits(estimated_peak: u64, committed: u64, available: u64, reserve: u64) -> bool {
+    committed.saturating_add(estimated_peak) <= available.saturating_sub(reserve)
+}
+
+/// Admits jobs while the sum of their estimated peaks fits in the available memory minus a reserve
+pub struct AdmissionScheduler {
+    stats: Mutex<AdmissionStats>,
+    released: Condvar,
+    available_memory: Box<dyn Fn() -> u64 + Send + Sync>,
+}
+
+impl AdmissionScheduler {
+    /// Read the available memory from the system
+    pub fn new(reserve_bytes: u64) -> Self {
+        let system = Mutex::new(System::new());
+        Self::with_available_memory(reserve_bytes, move || {
+            let mut system = system.lock().unwrap();
+            system.refresh_memory();
+            system.available_memory()
+        })
+    }
+
+    /// Reserve `MEMORY_ESTIMATOR_RESERVE_MB`, or `DEFAULT_RESERVE_BYTES`
+    pub fn from_env() -> Self {
+        let reserve_bytes = std::env::var(RESERVE_ENV)
+            .ok()
+            .and_then(|reserve| reserve.parse::<u64>().ok())
+            .map(|reserve_mb| reserve_mb * 1024 * 1024)
+            .unwrap_or(DEFAULT_RESERVE_BYTES);
+        Self::new(reserve_bytes)
+    }
+
+    pub fn with_available_memory(reserve_bytes: u64, available_memory: impl Fn() -> u64 + Send + Sync + 'static) -> Self {
+        Self {
+            stats: Mutex::new(AdmissionStats {
+                reserve_bytes,
+                available_bytes: 0,
+                committed_bytes: 0,
+                running_jobs: 0,
+                waiting_jobs: 0,
+                admitted_jobs: 0,
+                rejected_jobs: 0,
+            }),
+            released: Condvar::new(),
+            available_memory: Box::new(available_memory),
+        }
+    }
+
+    /// Wait until `estimated_peak` fits and commit it; call `release` with the same amount once
+    /// the job is done. Rejects the job when it doesn't fit with nothing else running, and gives
+    /// up waiting once `cancelled` says so.
+    pub fn admit(&self, estimated_peak: u64, cancelled: &dyn Fn() -> bool) -> Result<(), JobError> {
+        let mut stats = self.stats.lock().unwrap();
+        stats.waiting_jobs += 1;
+        loop {
+            let available = (self.available_memory)();
+            stats.available_bytes = available;
+            if fits(estimated_peak, stats.committed_bytes, available, stats.reserve_bytes) {
+                stats.waiting_jobs -= 1;
+                stats.committed_bytes += estimated_peak;
+                stats.running_jobs += 1;
+                stats.admitted_jobs += 1;
+                return Ok(());
+            }
+            if stats.running_jobs == 0 {
+                stats.waiting_jobs -= 1;
+                stats.rejected_jobs += 1;
+                return Err(JobError::InsufficientMemory {
+                    estimated_peak,
+                    available,
+                    reserve: stats.reserve_bytes,
+                });
+            }
+            if cancelled() {
+                stats.waiting_jobs -= 1;
+                return Err(JobError::AdmissionCancelled);
+            }
+            stats = self.released.wait_timeout(stats, RECHECK_INTERVAL).unwrap().0;
+        }
+    }
+
+    /// A job admitted with `estimated_peak` finished
+    pub fn release(&self, estimated_peak: u64) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.committed_bytes = stats.committed_bytes.saturating_sub(estimated_peak);
+        stats.running_jobs = stats.running_jobs.saturating_sub(1);
+        self.released.notify_all();
+    }
+
+    pub fn stats(&self) -> AdmissionStats {
+        self.stats.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::estimation_accuracy::AccuracyRecord;
+use crate::estimation_rules::{BufferFormula, Feature, RuleSet};
+use crate::estimator_error::EstimatorError;
+
+/// Share of the measured jobs the fitted buffers must cover
+pub const CALIBRATION_QUANTILE: f64 = 0.95;
+/// Feature the buffers are scaled with when the samples span several values of it
+pub const CALIBRATION_FEATURE: Feature = Feature::BinarySizeBytes;
+
+/// What one measured job says about the buffer its rule should have produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationSample {
+    pub binary_name: String,
+    pub buffer_rule: String,
+    pub features: BTreeMap<Feature, u64>,
+    /// Measured peak minus the estimated minimum, before growth scaling
+    pub needed_buffer_bytes: u64,
+}
+
+impl CalibrationSample {
+    /// `None` for records without a buffer rule or a feature vector (older histories)
+    pub fn from_record(record: &AccuracyRecord, rules: &RuleSet) -> Option<Self> {
+        let buffer_rule = record.buffer_rule.clone()?;
+        if record.features.is_empty() {
+            return None;
+        }
+        let needed = record.measured_peak_bytes().saturating_sub(record.estimated_minimum_memory_bytes);
+        // Undo the growth scaling so the fitted formula gives the right buffer once scaled again
+        let percent = record
+            .growth_behavior
+            .map(|behavior| rules.growth_scaling_percent.percent(behavior))
+            .unwrap_or(100)
+            .max(1);
+        Some(Self {
+            binary_name: record.binary_name.clone(),
+            buffer_rule,
+            features: record.features.clone(),
+            needed_buffer_bytes: needed * 100 / percent,
+        })
+    }
+}
+
+/// Nearest-rank quantile of `values`, 0 when empty
+fn quantile(values: &[f64], q: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+/// Fit `base + per_unit × feature` by least squares, then raise the base so the
+/// formula covers the `quantile` share of the samples. With a single value of the
+/// feature, or a negative slope, the buffer is just the quantile of the samples.
+pub fn fit_buffer(samples: &[CalibrationSample], feature: Feature, quantile_share: f64) -> BufferFormula {
+    let xs: Vec<f64> = samples.iter().map(|sample| sample.features.get(&feature).copied().unwrap_or(0) as f64).collect();
+    let ys: Vec<f64> = samples.iter().map(|sample| sample.needed_buffer_bytes as f64).collect();
+    let n = samples.len().max(1) as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let variance: f64 = xs.iter().map(|x| (x - mean_x).powi(2)).sum();
+    let covariance: f64 = xs.iter().zip(ys.iter()).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+
+    let slope = if variance > 0.0 { (covariance / variance).max(0.0) } else { 0.0 };
+    if slope == 0.0 {
+        return BufferFormula { base_bytes: quantile(&ys, quantile_share).ceil() as u64, per_unit: BTreeMap::new(), max_bytes: None };
+    }
+    let intercept = mean_y - slope * mean_x;
+    let residuals: Vec<f64> = xs.iter().zip(ys.iter()).map(|(x, y)| y - (intercept + slope * x)).collect();
+    BufferFormula {
+        base_bytes: (intercept + quantile(&residuals, quantile_share)).max(0.0).ceil() as u64,
+        per_unit: BTreeMap::from([(feature, slope)]),
+        max_bytes: None,
+    }
+}
+
+/// A buffer rule refitted from measurements
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FittedBuffer {
+    pub rule: String,
+    pub samples: usize,
+    pub binaries: Vec<String>,
+    pub previous: BufferFormula,
+    pub fitted: BufferFormula,
+}
+
+/// Result of a calibration: the new profile and what changed in it
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub rules: RuleSet,
+    pub fitted: Vec<FittedBuffer>,
+    /// Records that could not be used (no buffer rule, no features, unknown rule)
+    pub skipped_records: usize,
+}
+
+/// Refit the buffer of every rule of `rules` that has measurements in `records`.
+/// Classification rules, growth scaling and rules without measurements are kept.
+pub fn calibrate(rules: &RuleSet, records: &[AccuracyRecord], profile: &str) -> Result<Calibration, EstimatorError> {
+    let mut by_rule: BTreeMap<String, Vec<CalibrationSample>> = BTreeMap::new();
+    let mut skipped_records = 0;
+    for record in records.iter() {
+        match CalibrationSample::from_record(record, rules) {
+            Some(sample) if rules.buffers.iter().any(|rule| rule.name == sample.buffer_rule) => {
+                by_rule.entry(sample.buffer_rule.clone()).or_default().push(sample);
+            }
+            _ => skipped_records += 1,
+        }
+    }
+    if by_rule.is_empty() {
+        return Err(EstimatorError::RuleConfig(format!(
+            "no usable measurements to calibrate profile \"{}\" ({} records skipped)",
+            rules.profile, skipped_records
+        )));
+    }
+
+    let mut calibrated = rules.clone();
+    calibrated.profile = profile.to_string();
+    let mut fitted = Vec::new();
+    for rule in calibrated.buffers.iter_mut() {
+        let Some(samples) = by_rule.get(&rule.name) else {
+            continue;
+        };
+        let formula = fit_buffer(samples, CALIBRATION_FEATURE, CALIBRATION_QUANTILE);
+        let mut binaries: Vec<String> = samples.iter().map(|sample| sample.binary_name.clone()).collect();
+        binaries.sort();
+        binaries.dedup();
+        fitted.push(FittedBuffer {
+            rule: rule.name.clone(),
+            samples: samples.len(),
+            binaries,
+            previous: rule.buffer.clone(),
+            fitted: formula.clone(),
+        });
+        rule.buffer = formula;
+    }
+
+    Ok(Calibration { rules: calibrated, fitted, skipped_records })
+}
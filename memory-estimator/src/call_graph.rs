@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+use wasmparser::{FunctionBody, Operator};
+use crate::estimator_error::EstimatorError;
+
+/// Bytes cranelift needs per native frame besides locals (return address,
+/// frame pointer, callee-saved spills)
+const NATIVE_FRAME_OVERHEAD: u64 = 32;
+/// Bytes reserved on the native stack per wasm local or parameter
+const NATIVE_BYTES_PER_LOCAL: u64 = 8;
+
+/// What the stack analysis needs to know about one defined function
+#[derive(Debug, Clone)]
+pub struct FunctionSummary {
+    pub param_count: u32,
+    pub local_count: u32,
+    /// `(global index, bytes)` for each `global.get; i32.const N; i32.sub` sequence;
+    /// resolved against `__stack_pointer` once the globals are known
+    pub stack_adjustments: Vec<(u32, u64)>,
+    pub callees: Vec<u32>,
+    pub calls_indirect: bool,
+    /// Number of `memory.grow` instructions in the body
+    pub memory_grow_count: u32,
+    /// f32/f64 loads and stores at a loop depth of two or more, the shape of a matrix traversal
+    pub nested_loop_float_accesses: u32,
+}
+
+impl FunctionSummary {
+    /// Walk a function body and record its locals, calls and stack pointer adjustments
+    pub fn from_body(body: &FunctionBody<'_>, param_count: u32) -> Result<Self, EstimatorError> {
+        let mut summary = FunctionSummary {
+            param_count,
+            local_count: 0,
+            stack_adjustments: Vec::new(),
+            callees: Vec::new(),
+            calls_indirect: false,
+            memory_grow_count: 0,
+            nested_loop_float_accesses: 0,
+        };
+
+        for local in body.get_locals_reader()? {
+            let (count, _) = local?;
+            summary.local_count += count;
+        }
+
+        // Sliding window over the last two operators to spot the prologue
+        let mut previous_global: Option<u32> = None;
+        let mut previous_const: Option<(u32, u64)> = None;
+        // Open blocks, `true` for loops
+        let mut blocks: Vec<bool> = Vec::new();
+        let mut loop_depth = 0;
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            let operator = reader.read()?;
+            let mut next_global = None;
+            let mut next_const = None;
+            match operator {
+                Operator::GlobalGet { global_index } => next_global = Some(global_index),
+                Operator::I32Const { value } => {
+                    if let Some(global_index) = previous_global {
+                        next_const = Some((global_index, value as u32 as u64));
+                    }
+                }
+                Operator::I32Sub => {
+                    if let Some(adjustment) = previous_const {
+                        summary.stack_adjustments.push(adjustment);
+                    }
+                }
+                Operator::Call { function_index } | Operator::ReturnCall { function_index }
+                    if !summary.callees.contains(&function_index) =>
+                {
+                    summary.callees.push(function_index);
+                }
+                Operator::CallIndirect { .. }
+                | Operator::ReturnCallIndirect { .. }
+                | Operator::CallRef { .. }
+                | Operator::ReturnCallRef { .. } => summary.calls_indirect = true,
+                Operator::MemoryGrow { .. } => summary.memory_grow_count += 1,
+                Operator::Block { .. } | Operator::If { .. } | Operator::Try { .. } | Operator::TryTable { .. } => {
+                    blocks.push(false)
+                }
+                Operator::Loop { .. } => {
+                    blocks.push(true);
+                    loop_depth += 1;
+                }
+                // `delegate` closes a legacy `try` like `end` does
+                Operator::End | Operator::Delegate { .. } => loop_depth -= u32::from(blocks.pop() == Some(true)),
+                Operator::F32Load { .. } | Operator::F64Load { .. } | Operator::F32Store { .. } | Operator::F64Store { .. }
+                    if loop_depth >= 2 =>
+                {
+                    summary.nested_loop_float_accesses += 1;
+                }
+                _ => {}
+            }
+            previous_global = next_global;
+            previous_const = next_const;
+        }
+
+        Ok(summary)
+    }
+
+    fn shadow_frame_bytes(&self, stack_pointer_global: Option<u32>) -> u64 {
+        match stack_pointer_global {
+            Some(global) => self
+                .stack_adjustments
+                .iter()
+                .filter(|(index, _)| *index == global)
+                .map(|(_, bytes)| *bytes)
+                .max()
+                .unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn native_frame_bytes(&self) -> u64 {
+        NATIVE_FRAME_OVERHEAD + (self.param_count + self.local_count) as u64 * NATIVE_BYTES_PER_LOCAL
+    }
+}
+
+/// Worst-case stack usage of a module, computed over its static call graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackAnalysis {
+    /// Deepest shadow stack (in linear memory) along an acyclic call path
+    pub max_shadow_stack_bytes: u64,
+    /// Deepest native stack used by the compiled code along an acyclic call path
+    pub max_native_stack_bytes: u64,
+    /// Function names along the deepest path (by combined usage), starting at the entry point
+    pub deepest_chain: Vec<String>,
+    /// Functions that take part in a call cycle
+    pub recursive_functions: Vec<String>,
+    /// Some function calls through a table; callees are unknown statically
+    pub has_indirect_calls: bool,
+}
+
+impl StackAnalysis {
+    /// Recursion means the depth only holds for a single pass through each cycle
+    pub fn has_unbounded_recursion(&self) -> bool {
+        !self.recursive_functions.is_empty()
+    }
+}
+
+/// Call graph of the defined functions of a core module
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    pub imported_function_count: u32,
+    pub functions: Vec<FunctionSummary>,
+    pub function_names: Vec<(u32, String)>,
+}
+
+#[derive(Clone, Copy)]
+struct Depth {
+    shadow: u64,
+    native: u64,
+    next: Option<u32>,
+}
+
+/// A function whose callees are still being walked
+struct Frame {
+    function_index: u32,
+    next_callee: usize,
+    max_shadow: u64,
+    max_native: u64,
+    deepest_callee: Option<(u32, u64)>,
+}
+
+impl Frame {
+    /// Shadow and native depth are maximized independently; the chain follows
+    /// the callee with the largest combined usage
+    fn record(&mut self, callee: u32, depth: Depth) {
+        self.max_shadow = self.max_shadow.max(depth.shadow);
+        self.max_native = self.max_native.max(depth.native);
+        let combined = depth.shadow + depth.native;
+        if self.deepest_callee.is_none_or(|(_, current)| combined > current) {
+            self.deepest_callee = Some((callee, combined));
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    OnStack,
+    Done,
+}
+
+impl Default for CallGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallGraph {
+    pub fn new() -> Self {
+        Self {
+            imported_function_count: 0,
+            functions: Vec::new(),
+            function_names: Vec::new(),
+        }
+    }
+
+    pub fn function_name(&self, function_index: u32) -> String {
+        self.raw_function_name(function_index)
+            .map(demangle)
+            .unwrap_or_else(|| format!("func[{}]", function_index))
+    }
+
+    /// Symbol from the `name` section, as stored in the binary (not demangled)
+    pub fn raw_function_name(&self, function_index: u32) -> Option<&str> {
+        self.function_names
+            .iter()
+            .find(|(index, _)| *index == function_index)
+            .map(|(_, name)| name.as_str())
+    }
+
+    pub fn summary(&self, function_index: u32) -> Option<&FunctionSummary> {
+        function_index
+            .checked_sub(self.imported_function_count)
+            .and_then(|index| self.functions.get(index as usize))
+    }
+
+    /// Mark every function reachable from `roots` through direct calls, roots included
+    pub fn reachable_from(&self, roots: &[u32]) -> Vec<bool> {
+        let total = self.imported_function_count as usize + self.functions.len();
+        let mut reachable = vec![false; total];
+        let mut pending: Vec<u32> = roots.iter().copied().filter(|&root| (root as usize) < total).collect();
+        while let Some(function_index) = pending.pop() {
+            if reachable[function_index as usize] {
+                continue;
+            }
+            reachable[function_index as usize] = true;
+            if let Some(summary) = self.summary(function_index) {
+                pending.extend(summary.callees.iter().copied().filter(|&callee| (callee as usize) < total));
+            }
+        }
+        reachable
+    }
+
+    /// Compute the deepest stack usage reachable from `roots` (usually the exports).
+    /// Back edges are skipped, so cycles contribute one pass and are reported as recursion.
+    pub fn analyze_stack(&self, stack_pointer_global: Option<u32>, roots: &[u32]) -> StackAnalysis {
+        let total = self.imported_function_count as usize + self.functions.len();
+        let mut state = vec![Visit::New; total];
+        let mut depths: Vec<Option<Depth>> = vec![None; total];
+        let mut recursive: Vec<u32> = Vec::new();
+
+        let roots: Vec<u32> = if roots.is_empty() {
+            (self.imported_function_count..total as u32).collect()
+        } else {
+            roots.to_vec()
+        };
+
+        let mut best: Option<(u32, u64)> = None;
+        let mut max_shadow_stack_bytes = 0;
+        let mut max_native_stack_bytes = 0;
+        for &root in roots.iter() {
+            if (root as usize) >= total {
+                continue;
+            }
+            let depth = self.visit(root, stack_pointer_global, &mut state, &mut depths, &mut recursive);
+            max_shadow_stack_bytes = max_shadow_stack_bytes.max(depth.shadow);
+            max_native_stack_bytes = max_native_stack_bytes.max(depth.native);
+            let combined = depth.shadow + depth.native;
+            if best.is_none_or(|(_, current)| combined > current) {
+                best = Some((root, combined));
+            }
+        }
+
+        let mut deepest_chain = Vec::new();
+        let mut cursor = best.map(|(root, _)| root);
+        while let Some(function_index) = cursor {
+            deepest_chain.push(self.function_name(function_index));
+            cursor = depths[function_index as usize].and_then(|depth| depth.next);
+            if deepest_chain.len() > total {
+                break;
+            }
+        }
+
+        recursive.sort_unstable();
+        recursive.dedup();
+
+        StackAnalysis {
+            max_shadow_stack_bytes,
+            max_native_stack_bytes,
+            deepest_chain,
+            recursive_functions: recursive.iter().map(|&index| self.function_name(index)).collect(),
+            has_indirect_calls: self.functions.iter().any(|function| function.calls_indirect),
+        }
+    }
+
+    /// Depth-first walk from `root` with an explicit stack of frames, so long call
+    /// chains can't overflow the estimator's own stack
+    fn visit(
+        &self,
+        root: u32,
+        stack_pointer_global: Option<u32>,
+        state: &mut [Visit],
+        depths: &mut [Option<Depth>],
+        recursive: &mut Vec<u32>,
+    ) -> Depth {
+        let mut frames: Vec<Frame> = Vec::new();
+        if let Some(depth) = self.enter(root, state, depths, &mut frames) {
+            return depth;
+        }
+
+        while let Some(top) = frames.len().checked_sub(1) {
+            let function_index = frames[top].function_index;
+            // Only functions with a summary are pushed
+            let summary = self.summary(function_index).unwrap();
+
+            if let Some(&callee) = summary.callees.get(frames[top].next_callee) {
+                frames[top].next_callee += 1;
+                let callee_slot = callee as usize;
+                if callee_slot >= state.len() {
+                    continue;
+                }
+                if state[callee_slot] == Visit::OnStack {
+                    let start = frames.iter().position(|frame| frame.function_index == callee).unwrap_or(0);
+                    recursive.extend(frames[start..].iter().map(|frame| frame.function_index));
+                    continue;
+                }
+                if let Some(depth) = self.enter(callee, state, depths, &mut frames) {
+                    frames[top].record(callee, depth);
+                }
+                continue;
+            }
+
+            let frame = frames.pop().unwrap();
+            let depth = Depth {
+                shadow: summary.shadow_frame_bytes(stack_pointer_global) + frame.max_shadow,
+                native: summary.native_frame_bytes() + frame.max_native,
+                next: frame.deepest_callee.map(|(callee, _)| callee),
+            };
+            state[function_index as usize] = Visit::Done;
+            depths[function_index as usize] = Some(depth);
+            if let Some(parent) = frames.last_mut() {
+                parent.record(function_index, depth);
+            }
+        }
+
+        depths[root as usize].unwrap()
+    }
+
+    /// Push a frame for `function_index`, or return its depth when it is already known
+    fn enter(
+        &self,
+        function_index: u32,
+        state: &mut [Visit],
+        depths: &mut [Option<Depth>],
+        frames: &mut Vec<Frame>,
+    ) -> Option<Depth> {
+        let slot = function_index as usize;
+        if state[slot] == Visit::Done {
+            return depths[slot];
+        }
+
+        // Imported functions run on the host and don't use the guest stacks
+        if self.summary(function_index).is_none() {
+            let depth = Depth { shadow: 0, native: 0, next: None };
+            state[slot] = Visit::Done;
+            depths[slot] = Some(depth);
+            return Some(depth);
+        }
+
+        state[slot] = Visit::OnStack;
+        frames.push(Frame {
+            function_index,
+            next_callee: 0,
+            max_shadow: 0,
+            max_native: 0,
+            deepest_callee: None,
+        });
+        None
+    }
+}
+
+/// Best effort demangling of legacy Rust symbols (`_ZN...E`), other names are returned as is
+pub fn demangle(name: &str) -> String {
+    let Some(mut rest) = name.strip_prefix("_ZN") else {
+        return name.to_string();
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    while !rest.starts_with('E') && !rest.is_empty() {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        let Ok(len) = rest[..digits].parse::<usize>() else {
+            return name.to_string();
+        };
+        let Some(segment) = rest.get(digits..digits + len) else {
+            return name.to_string();
+        };
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+
+    // Drop the trailing `h<hash>` segment
+    if let Some(last) = segments.last() {
+        if last.len() == 17 && last.starts_with('h') && last[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+            segments.pop();
+        }
+    }
+
+    segments
+        .iter()
+        .map(|segment| unescape_segment(segment.strip_prefix('_').filter(|rest| rest.starts_with('$')).unwrap_or(segment)))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Decode the `$LT$`, `$u7b$`, `..` escapes used inside legacy Rust symbol segments
+fn unescape_segment(segment: &str) -> String {
+    let mut output = String::new();
+    let mut rest = segment;
+    while !rest.is_empty() {
+        if let Some(stripped) = rest.strip_prefix("..") {
+            output.push_str("::");
+            rest = stripped;
+            continue;
+        }
+        if rest.starts_with('$') {
+            if let Some(end) = rest[1..].find('$') {
+                let escape = &rest[1..end + 1];
+                let decoded = match escape {
+                    "LT" => Some('<'),
+                    "GT" => Some('>'),
+                    "RF" => Some('&'),
+                    "BP" => Some('*'),
+                    "LP" => Some('('),
+                    "RP" => Some(')'),
+                    "C" => Some(','),
+                    "SP" => Some('@'),
+                    _ => escape
+                        .strip_prefix('u')
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .and_then(char::from_u32),
+                };
+                if let Some(c) = decoded {
+                    output.push(c);
+                    rest = &rest[end + 2..];
+                    continue;
+                }
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
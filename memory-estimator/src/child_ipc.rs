@@ -0,0 +1,225 @@
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use wasmtime::component::Val;
+use crate::estimation_accuracy::AccuracyRecord;
+use crate::job_queue::GuestResult;
+use crate::memory_info_monitor::MemoryMonitor;
+
+/// Set on the child to the Unix socket its parent listens on
+pub const IPC_SOCKET_ENV: &str = "MEMORY_ESTIMATOR_IPC_SOCKET";
+/// Largest frame either side accepts; a longer length prefix means the stream is corrupted
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// How far the child got with its job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildStage {
+    /// Read the task and joined its cgroup
+    Started,
+    /// Instantiating the component and calling the guest function
+    Running,
+    Finished,
+}
+
+/// One message from the child to the parent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChildMessage {
+    Progress { stage: ChildStage, elapsed_ms: u64 },
+    /// RSS samples and guest memory growth of the finished job
+    Memory { monitor: MemoryMonitor },
+    Accuracy { record: AccuracyRecord },
+    /// What the guest function returned or why it failed, with its captured output
+    Result { result: GuestResult },
+    /// The child failed outside of the guest (task unreadable, payload undecodable)
+    Error { message: String },
+}
+
+/// Write `message` as a big-endian u32 length followed by that many bytes of JSON
+pub fn write_frame(writer: &mut impl Write, message: &ChildMessage) -> io::Result<()> {
+    let json = serde_json::to_vec(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if json.len() > MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes exceeds the {} byte frame limit", json.len(), MAX_FRAME_BYTES),
+        ));
+    }
+    writer.write_all(&(json.len() as u32).to_be_bytes())?;
+    writer.write_all(&json)?;
+    writer.flush()
+}
+
+/// Read the next frame, `None` once the writer closed the stream between two frames
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<ChildMessage>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the {} byte limit", length, MAX_FRAME_BYTES),
+        ));
+    }
+    let mut json = vec![0u8; length];
+    reader.read_exact(&mut json)?;
+    serde_json::from_slice(&json).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Child end of the channel. Without a parent listening every message is dropped,
+/// so a child started by hand still runs its job.
+pub struct ChildChannel {
+    stream: Option<Mutex<UnixStream>>,
+    started: Instant,
+}
+
+impl ChildChannel {
+    pub fn connect(path: &Path) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        Ok(Self { stream: Some(Mutex::new(stream)), started: Instant::now() })
+    }
+
+    /// Connect to the socket named by `MEMORY_ESTIMATOR_IPC_SOCKET`, if any
+    pub fn from_env() -> Self {
+        match std::env::var(IPC_SOCKET_ENV) {
+            Ok(path) if !path.is_empty() => Self::connect(Path::new(&path)).unwrap_or_else(|e| {
+                eprintln!("Child: failed to connect to the parent at {}: {}", path, e);
+                Self::disconnected()
+            }),
+            _ => Self::disconnected(),
+        }
+    }
+
+    pub fn disconnected() -> Self {
+        Self { stream: None, started: Instant::now() }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn send(&self, message: &ChildMessage) {
+        if let Some(stream) = &self.stream {
+            if let Err(e) = write_frame(&mut *stream.lock().unwrap(), message) {
+                eprintln!("Child: failed to send a message to the parent: {}", e);
+            }
+        }
+    }
+
+    /// Tell the parent the job reached `stage`
+    pub fn progress(&self, stage: ChildStage) {
+        self.send(&ChildMessage::Progress { stage, elapsed_ms: self.started.elapsed().as_millis() as u64 });
+    }
+}
+
+/// Parent end of the channel: a Unix socket one child connects to. The socket file is
+/// removed when the listener is dropped.
+pub struct ChildListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ChildListener {
+    pub fn bind(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        // Left behind by a parent that didn't shut down cleanly
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        Ok(Self { listener, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accept the child and hand every message to `on_message` as it arrives until the child
+    /// closes the connection. Blocks until then; a child that never connects needs `wake`.
+    pub fn receive(&self, mut on_message: impl FnMut(&ChildMessage)) -> Vec<ChildMessage> {
+        let mut messages = Vec::new();
+        let mut stream = match self.listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                println!("Error accepting the child on {}: {}", self.path.display(), e);
+                return messages;
+            }
+        };
+        loop {
+            match read_frame(&mut stream) {
+                Ok(Some(message)) => {
+                    on_message(&message);
+                    messages.push(message);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    println!("Error reading from the child on {}: {}", self.path.display(), e);
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    /// Unblock `receive` after the child exited; harmless when it did connect
+    pub fn wake(&self) {
+        let _ = UnixStream::connect(&self.path);
+    }
+}
+
+impl Drop for ChildListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Render a component model value as JSON: records become objects, variants `{"case": payload}`,
+/// results `{"ok": ..}` / `{"err": ..}`; resources and async handles their debug form
+pub fn val_to_json(val: &Val) -> Value {
+    let boxed = |val: &Option<Box<Val>>| val.as_deref().map_or(Value::Null, val_to_json);
+    match val {
+        Val::Bool(value) => Value::from(*value),
+        Val::S8(value) => Value::from(*value),
+        Val::U8(value) => Value::from(*value),
+        Val::S16(value) => Value::from(*value),
+        Val::U16(value) => Value::from(*value),
+        Val::S32(value) => Value::from(*value),
+        Val::U32(value) => Value::from(*value),
+        Val::S64(value) => Value::from(*value),
+        Val::U64(value) => Value::from(*value),
+        // Non-finite floats have no JSON form and become null
+        Val::Float32(value) => Value::from(*value as f64),
+        Val::Float64(value) => Value::from(*value),
+        Val::Char(value) => Value::from(value.to_string()),
+        Val::String(value) => Value::from(value.as_str()),
+        Val::List(values) | Val::Tuple(values) => Value::Array(values.iter().map(val_to_json).collect()),
+        Val::Record(fields) => {
+            Value::Object(fields.iter().map(|(name, value)| (name.clone(), val_to_json(value))).collect())
+        }
+        Val::Variant(case, payload) => {
+            let mut object = Map::new();
+            object.insert(case.clone(), boxed(payload));
+            Value::Object(object)
+        }
+        Val::Enum(case) => Value::from(case.as_str()),
+        Val::Option(value) => boxed(value),
+        Val::Result(Ok(value)) => {
+            let mut object = Map::new();
+            object.insert("ok".to_string(), boxed(value));
+            Value::Object(object)
+        }
+        Val::Result(Err(value)) => {
+            let mut object = Map::new();
+            object.insert("err".to_string(), boxed(value));
+            Value::Object(object)
+        }
+        Val::Flags(flags) => Value::Array(flags.iter().map(|flag| Value::from(flag.as_str())).collect()),
+        other => Value::from(format!("{:?}", other)),
+    }
+}
@@ -0,0 +1,167 @@
+use object::{Object, ObjectSection};
+use serde::{Deserialize, Serialize};
+use crate::estimator_error::{read_file, EstimatorError};
+use crate::memory_info_estimator::MemoryInfoEstimator;
+
+/// Section holding the serialized engine configuration (target, flags, version)
+const ENGINE_SECTION: &str = ".wasmtime.engine";
+/// Section holding the data segment images used to initialize linear memories
+const RODATA_WASM_SECTION: &str = ".rodata.wasm";
+
+/// What a wasmtime precompiled artifact (`.cwasm`) contains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CwasmInfo {
+    pub file_size_bytes: u64,
+    /// Wasmtime version that produced the artifact
+    pub wasmtime_version: Option<String>,
+    /// Target triple the native code was compiled for
+    pub target_triple: Option<String>,
+    /// Machine code that gets mmapped executable at load time
+    pub text_size_bytes: u64,
+    /// Memory initialization images (`.rodata.wasm`)
+    pub wasm_data_bytes: u64,
+    /// Wasmtime metadata sections (`.wasmtime.*`, unwind info, name maps)
+    pub metadata_bytes: u64,
+    /// Cranelift flags recorded in the engine section, as (name, value)
+    pub compiler_flags: Vec<(String, String)>,
+}
+
+/// Check whether the bytes are a wasmtime precompiled artifact
+pub fn is_cwasm(bytes: &[u8]) -> bool {
+    if !bytes.starts_with(b"\x7fELF") {
+        return false;
+    }
+    match object::File::parse(bytes) {
+        Ok(file) => file.section_by_name(ENGINE_SECTION).is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Analyze a precompiled .cwasm file and record its native code footprint
+pub fn analyze_cwasm(cwasm_path: &str, memory_info: &mut MemoryInfoEstimator) -> Result<(), EstimatorError> {
+    let bytes = read_file(cwasm_path)?;
+    if !is_cwasm(&bytes) {
+        return Err(EstimatorError::MalformedCwasm(format!("{} is not a wasmtime precompiled artifact", cwasm_path)));
+    }
+    let cwasm = parse_cwasm(&bytes)?;
+
+    if let Some(warning) = host_target_mismatch(&cwasm) {
+        memory_info.warnings.push(warning);
+    }
+    memory_info.native_code_bytes = cwasm.text_size_bytes;
+    memory_info.cwasm = Some(cwasm);
+    Ok(())
+}
+
+/// Read the ELF sections of a .cwasm
+pub fn parse_cwasm(bytes: &[u8]) -> Result<CwasmInfo, EstimatorError> {
+    let file = object::File::parse(bytes)?;
+    let mut info = CwasmInfo {
+        file_size_bytes: bytes.len() as u64,
+        wasmtime_version: None,
+        target_triple: None,
+        text_size_bytes: 0,
+        wasm_data_bytes: 0,
+        metadata_bytes: 0,
+        compiler_flags: Vec::new(),
+    };
+
+    for section in file.sections() {
+        let name = section.name()?;
+        match name {
+            ".text" => info.text_size_bytes += section.size(),
+            RODATA_WASM_SECTION => info.wasm_data_bytes += section.size(),
+            ".symtab" | ".strtab" | ".shstrtab" | "" => {}
+            _ => info.metadata_bytes += section.size(),
+        }
+    }
+
+    if let Some(section) = file.section_by_name(ENGINE_SECTION) {
+        parse_engine_section(section.data()?, &mut info);
+    }
+
+    Ok(info)
+}
+
+/// Decode the leading fields of the engine section: a version byte, the
+/// wasmtime version string, then the postcard encoded target triple and
+/// the shared compiler flags. Anything we fail to decode is left empty.
+fn parse_engine_section(data: &[u8], info: &mut CwasmInfo) {
+    let mut reader = SectionReader { data, position: 0 };
+    if reader.byte().is_none() {
+        return;
+    }
+    let Some(version_len) = reader.byte() else { return };
+    info.wasmtime_version = reader.str(version_len as usize);
+    info.target_triple = reader.varint().and_then(|len| reader.str(len as usize));
+
+    let Some(flag_count) = reader.varint() else { return };
+    for _ in 0..flag_count {
+        let Some(name) = reader.varint().and_then(|len| reader.str(len as usize)) else { return };
+        let value = match reader.varint() {
+            Some(0) => reader.varint().and_then(|len| reader.str(len as usize)),
+            Some(1) => reader.byte().map(|value| value.to_string()),
+            Some(2) => reader.byte().map(|value| (value != 0).to_string()),
+            _ => None,
+        };
+        let Some(value) = value else { return };
+        info.compiler_flags.push((name, value));
+    }
+}
+
+struct SectionReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl SectionReader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn str(&mut self, len: usize) -> Option<String> {
+        let end = self.position.checked_add(len)?;
+        let bytes = self.data.get(self.position..end)?;
+        self.position = end;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+/// Compare the artifact's target triple with the architecture and OS of the running host
+pub fn host_target_mismatch(cwasm: &CwasmInfo) -> Option<String> {
+    let triple = cwasm.target_triple.as_deref()?;
+    let arch = triple.split('-').next().unwrap_or_default();
+    let os = if triple.contains("darwin") {
+        "macos"
+    } else if triple.contains("linux") {
+        "linux"
+    } else if triple.contains("windows") {
+        "windows"
+    } else {
+        std::env::consts::OS
+    };
+
+    if arch != std::env::consts::ARCH || os != std::env::consts::OS {
+        return Some(format!(
+            "precompiled artifact targets {} but the host is {}-{}; wasmtime will refuse to load it",
+            triple,
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        ));
+    }
+    None
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::estimation_report::EstimationReport;
+use crate::estimation_rules::Feature;
+use crate::estimator_error::{read_file, EstimatorError};
+use crate::memory_growth::GrowthBehavior;
+use crate::memory_info_monitor::MemoryMonitor;
+
+/// Directory holding one accuracy history file per binary
+pub const ACCURACY_DIR_ENV: &str = "MEMORY_ESTIMATOR_ACCURACY_DIR";
+pub const DEFAULT_ACCURACY_DIR: &str = "accuracy";
+/// Relative error under which an estimate counts as accurate
+pub const ACCURACY_TOLERANCE: f64 = 0.10;
+
+/// Whether the estimated peak was above or below what the job really used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateVerdict {
+    Overestimate,
+    Underestimate,
+    /// Within `ACCURACY_TOLERANCE` of the measurement
+    Accurate,
+}
+
+impl EstimateVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstimateVerdict::Overestimate => "overestimate",
+            EstimateVerdict::Underestimate => "underestimate",
+            EstimateVerdict::Accurate => "accurate",
+        }
+    }
+}
+
+/// Predicted against measured memory for one job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccuracyRecord {
+    pub binary_name: String,
+    pub task_id: usize,
+    pub recorded_at_unix_ms: u64,
+    pub rule_profile: String,
+    pub workload: String,
+    pub buffer_rule: Option<String>,
+    #[serde(default)]
+    pub growth_behavior: Option<GrowthBehavior>,
+    /// Feature vector of the estimate, used to calibrate the buffer rules
+    #[serde(default)]
+    pub features: BTreeMap<Feature, u64>,
+    pub estimated_minimum_memory_bytes: u64,
+    pub estimated_peak_memory_bytes: u64,
+    /// Peak virtual memory of the child (`VmPeak`)
+    pub measured_vm_peak_bytes: Option<u64>,
+    /// RSS high water mark of the child
+    pub measured_rss_peak_bytes: u64,
+    /// `memory.peak` of the job's cgroup; the errors are computed against it when set, else against the RSS peak
+    #[serde(default)]
+    pub measured_cgroup_peak_bytes: Option<u64>,
+    pub guest_linear_memory_peak_bytes: Option<u64>,
+    /// Estimated peak minus measured peak; positive means overestimated
+    pub absolute_error_bytes: i64,
+    /// `absolute_error_bytes` relative to the measured peak
+    pub relative_error: f64,
+    pub verdict: EstimateVerdict,
+}
+
+impl AccuracyRecord {
+    /// Compare the report of a job with what was measured while it ran.
+    /// `None` when no RSS could be measured.
+    pub fn new(binary_name: &str, task_id: usize, report: &EstimationReport, monitor: &MemoryMonitor) -> Option<Self> {
+        let measured_rss_peak_bytes = monitor.resident_peak_bytes()?;
+        let estimated_peak_memory_bytes = report.estimate.peak_bytes;
+        let (absolute_error_bytes, relative_error, verdict) = compare(estimated_peak_memory_bytes, measured_rss_peak_bytes);
+
+        Some(Self {
+            binary_name: binary_name.to_string(),
+            task_id,
+            recorded_at_unix_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0),
+            rule_profile: report.rule_profile.clone(),
+            workload: report.classification.workload.clone(),
+            buffer_rule: report.classification.buffer_rule.clone(),
+            growth_behavior: report.classification.growth_behavior,
+            features: Feature::vector(&report.features),
+            estimated_minimum_memory_bytes: report.estimate.minimum_bytes,
+            estimated_peak_memory_bytes,
+            measured_vm_peak_bytes: monitor.virtual_peak_memory_bytes,
+            measured_rss_peak_bytes,
+            measured_cgroup_peak_bytes: None,
+            guest_linear_memory_peak_bytes: monitor.guest_memory.as_ref().map(|guest| guest.peak_linear_memory_bytes),
+            absolute_error_bytes,
+            relative_error,
+            verdict,
+        })
+    }
+
+    /// Compare against the peak the kernel accounted to the job's cgroup instead of the RSS peak
+    pub fn with_cgroup_peak(mut self, cgroup_peak_bytes: u64) -> Self {
+        (self.absolute_error_bytes, self.relative_error, self.verdict) =
+            compare(self.estimated_peak_memory_bytes, cgroup_peak_bytes);
+        self.measured_cgroup_peak_bytes = Some(cgroup_peak_bytes);
+        self
+    }
+
+    /// The peak the errors were computed against
+    pub fn measured_peak_bytes(&self) -> u64 {
+        self.measured_cgroup_peak_bytes.unwrap_or(self.measured_rss_peak_bytes)
+    }
+}
+
+/// Error of an estimated peak against a measured one, and the verdict it gives
+fn compare(estimated_peak_bytes: u64, measured_peak_bytes: u64) -> (i64, f64, EstimateVerdict) {
+    let absolute_error_bytes = estimated_peak_bytes as i64 - measured_peak_bytes as i64;
+    let relative_error = absolute_error_bytes as f64 / measured_peak_bytes.max(1) as f64;
+    let verdict = if relative_error.abs() <= ACCURACY_TOLERANCE {
+        EstimateVerdict::Accurate
+    } else if absolute_error_bytes > 0 {
+        EstimateVerdict::Overestimate
+    } else {
+        EstimateVerdict::Underestimate
+    };
+    (absolute_error_bytes, relative_error, verdict)
+}
+
+/// Accuracy records accumulated on disk, one JSON-lines file per binary
+pub struct AccuracyHistory {
+    dir: PathBuf,
+}
+
+impl AccuracyHistory {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory named by `MEMORY_ESTIMATOR_ACCURACY_DIR`, or `accuracy`
+    pub fn from_env() -> Self {
+        match std::env::var(ACCURACY_DIR_ENV) {
+            Ok(dir) if !dir.is_empty() => Self::new(dir),
+            _ => Self::new(DEFAULT_ACCURACY_DIR),
+        }
+    }
+
+    fn path(&self, binary_name: &str) -> PathBuf {
+        let file_name: String = binary_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.jsonl", file_name))
+    }
+
+    /// Append a record to the history of its binary. Each record is a single write
+    /// to a file opened in append mode, so concurrent jobs don't interleave lines.
+    pub fn append(&self, record: &AccuracyRecord) -> Result<(), EstimatorError> {
+        let path = self.path(&record.binary_name);
+        let path_str = path.to_string_lossy().to_string();
+        fs::create_dir_all(&self.dir).map_err(|e| EstimatorError::from_io(&self.dir.to_string_lossy(), e))?;
+        let line = serde_json::to_string(record).map_err(|e| EstimatorError::Report(e.to_string()))? + "\n";
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| EstimatorError::from_io(&path_str, e))
+    }
+
+    /// Every record of `binary_name`, oldest first; empty when it never ran
+    pub fn records(&self, binary_name: &str) -> Result<Vec<AccuracyRecord>, EstimatorError> {
+        let path = self.path(binary_name);
+        let path_str = path.to_string_lossy().to_string();
+        let bytes = match read_file(&path_str) {
+            Ok(bytes) => bytes,
+            Err(EstimatorError::MissingFile { .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        String::from_utf8_lossy(&bytes)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| EstimatorError::Report(format!("{} record {}: {}", path_str, index + 1, e)))
+            })
+            .collect()
+    }
+
+    /// Records of every binary, grouped by binary; empty when nothing was recorded yet
+    pub fn all_records(&self) -> Result<Vec<AccuracyRecord>, EstimatorError> {
+        let dir = self.dir.to_string_lossy().to_string();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(EstimatorError::from_io(&dir, e)),
+        };
+        let mut files: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter_map(|name| name.strip_suffix(".jsonl").map(str::to_string))
+            .collect();
+        files.sort();
+        let mut records = Vec::new();
+        for file in files.iter() {
+            records.extend(self.records(file)?);
+        }
+        Ok(records)
+    }
+}
+
+/// How well the estimate did over every recorded job of a binary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccuracySummary {
+    pub binary_name: String,
+    /// Workload class of the latest job
+    pub workload: String,
+    pub jobs: usize,
+    pub overestimates: usize,
+    pub underestimates: usize,
+    pub accurate: usize,
+    pub mean_relative_error: f64,
+    pub mean_absolute_relative_error: f64,
+    /// Largest shortfall of the estimate, 0 if it was never under
+    pub worst_underestimate_bytes: u64,
+}
+
+impl AccuracySummary {
+    /// `None` without records
+    pub fn from_records(records: &[AccuracyRecord]) -> Option<Self> {
+        let latest = records.last()?;
+        let jobs = records.len();
+        let count = |verdict: EstimateVerdict| records.iter().filter(|record| record.verdict == verdict).count();
+        Some(Self {
+            binary_name: latest.binary_name.clone(),
+            workload: latest.workload.clone(),
+            jobs,
+            overestimates: count(EstimateVerdict::Overestimate),
+            underestimates: count(EstimateVerdict::Underestimate),
+            accurate: count(EstimateVerdict::Accurate),
+            mean_relative_error: records.iter().map(|record| record.relative_error).sum::<f64>() / jobs as f64,
+            mean_absolute_relative_error: records.iter().map(|record| record.relative_error.abs()).sum::<f64>() / jobs as f64,
+            worst_underestimate_bytes: records
+                .iter()
+                .map(|record| (-record.absolute_error_bytes).max(0) as u64)
+                .max()
+                .unwrap_or(0),
+        })
+    }
+}
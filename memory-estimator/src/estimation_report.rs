@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use crate::estimation_rules::{Evidence, Workload};
+use crate::estimator_error::EstimatorError;
+use crate::memory_growth::GrowthBehavior;
+use crate::memory_info_estimator::{categorize_binary_size, table_slot_bytes, workload_type_name, MemoryInfoEstimator};
+use crate::peak_interval::PeakInterval;
+
+/// Bumped whenever a field of the report changes meaning or is removed
+pub const REPORT_VERSION: u32 = 4;
+
+/// How the workload was classified and which rule chose the buffer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkloadClassification {
+    /// Human readable name of `class`
+    pub workload: String,
+    pub class: Workload,
+    /// What made the classification rule match
+    pub evidence: Vec<Evidence>,
+    pub size_category: String,
+    pub growth_behavior: Option<GrowthBehavior>,
+    pub buffer_rule: Option<String>,
+}
+
+/// Each term of the estimate; `minimum_bytes` and `peak_bytes` are their sums
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EstimateBreakdown {
+    pub linear_memory_bytes: u64,
+    /// Shadow stack plus native stack; only the native stack adds to the minimum,
+    /// the shadow stack is part of linear memory
+    pub stack_usage_bytes: u64,
+    #[serde(default)]
+    pub native_stack_bytes: u64,
+    /// Data segments outside the initial linear memory: passive ones and active ones past the initial pages
+    pub data_segment_bytes: u64,
+    /// Host memory behind the table slots
+    pub table_bytes: u64,
+    pub native_code_bytes: u64,
+    /// Memory the job's payload adds, 0 when estimated without one
+    #[serde(default)]
+    pub payload_bytes: u64,
+    /// ONNX models, their weights and ONNX Runtime's arena, held by the host rather than the guest
+    #[serde(default)]
+    pub inference_host_bytes: u64,
+    pub minimum_bytes: u64,
+    pub buffer_bytes: u64,
+    pub peak_bytes: u64,
+    /// p50/p95/p99 of the peak
+    #[serde(default)]
+    pub peak_interval: Option<PeakInterval>,
+}
+
+/// Versioned result of an estimation, meant to be consumed programmatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EstimationReport {
+    pub report_version: u32,
+    pub estimator_version: String,
+    pub wasm_file: String,
+    pub cwasm_file: String,
+    pub rule_profile: String,
+    pub classification: WorkloadClassification,
+    pub estimate: EstimateBreakdown,
+    pub warnings: Vec<String>,
+    /// Every feature extracted from the binaries
+    pub features: MemoryInfoEstimator,
+}
+
+impl EstimationReport {
+    /// Build the report from an estimator on which `calculate_aggregated_memory` already ran.
+    /// The warnings are moved out of the estimator into the report.
+    pub fn new(wasm_file: &str, cwasm_file: &str, rule_profile: &str, mut memory_info: MemoryInfoEstimator) -> Self {
+        let table_bytes = table_slot_bytes(&memory_info);
+        let classification = WorkloadClassification {
+            workload: workload_type_name(&memory_info).to_string(),
+            class: memory_info.workload.clone(),
+            evidence: memory_info.workload_evidence.clone(),
+            size_category: categorize_binary_size(memory_info.binary_size_bytes).to_string(),
+            growth_behavior: memory_info.growth_analysis.as_ref().map(|growth| growth.behavior),
+            buffer_rule: memory_info.buffer_rule.clone(),
+        };
+        let estimate = EstimateBreakdown {
+            linear_memory_bytes: memory_info.linear_memory_bytes,
+            stack_usage_bytes: memory_info.stack_usage_bytes,
+            native_stack_bytes: memory_info.native_stack_bytes,
+            data_segment_bytes: memory_info.static_footprint_bytes.saturating_sub(table_bytes),
+            table_bytes,
+            native_code_bytes: memory_info.native_code_bytes,
+            payload_bytes: memory_info.payload.as_ref().map_or(0, |payload| payload.total_bytes),
+            inference_host_bytes: memory_info.inference.as_ref().map_or(0, |inference| inference.total_bytes),
+            minimum_bytes: memory_info.estimated_minimum_memory_bytes,
+            buffer_bytes: memory_info.buffer_bytes,
+            peak_bytes: memory_info.estimated_peak_memory_bytes,
+            peak_interval: memory_info.peak_interval.clone(),
+        };
+
+        Self {
+            report_version: REPORT_VERSION,
+            estimator_version: env!("CARGO_PKG_VERSION").to_string(),
+            wasm_file: wasm_file.to_string(),
+            cwasm_file: cwasm_file.to_string(),
+            rule_profile: rule_profile.to_string(),
+            classification,
+            estimate,
+            warnings: std::mem::take(&mut memory_info.warnings),
+            features: memory_info,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, EstimatorError> {
+        serde_json::to_string_pretty(self).map_err(|e| EstimatorError::Report(e.to_string()))
+    }
+
+    /// Parse a report, rejecting versions newer than this crate understands
+    pub fn from_json(json: &str) -> Result<Self, EstimatorError> {
+        let report: EstimationReport = serde_json::from_str(json).map_err(|e| EstimatorError::Report(e.to_string()))?;
+        if report.report_version > REPORT_VERSION {
+            return Err(EstimatorError::Report(format!(
+                "report version {} is newer than the supported version {}",
+                report.report_version, REPORT_VERSION
+            )));
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,509 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use crate::estimator_error::{read_file, EstimatorError};
+use crate::memory_growth::GrowthBehavior;
+use crate::memory_info_estimator::MemoryInfoEstimator;
+use crate::workload_signals::WorkloadSignals;
+
+/// Environment variable pointing to a rules file that replaces the default profile
+pub const RULES_ENV: &str = "MEMORY_ESTIMATOR_RULES";
+
+/// The profile shipped with the crate
+const DEFAULT_RULES: &str = include_str!("../rules/default.json");
+
+static ACTIVE_RULES: OnceLock<RuleSet> = OnceLock::new();
+
+/// Features extracted by the analyzers that rules can match on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    BinarySizeBytes,
+    FunctionCount,
+    GlobalCount,
+    DataSegmentCount,
+    ElementSegmentCount,
+    TotalFunctionReferences,
+    ImportCount,
+    ExportCount,
+    LinearMemoryBytes,
+    NativeCodeBytes,
+    /// f32/f64 loads and stores inside nested loops of the user's code
+    NestedLoopFloatAccesses,
+    /// Only known once the estimate is computed; zero during classification
+    StaticFootprintBytes,
+    /// Only known once the estimate is computed; zero during classification
+    StackUsageBytes,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 13] = [
+        Feature::BinarySizeBytes,
+        Feature::FunctionCount,
+        Feature::GlobalCount,
+        Feature::DataSegmentCount,
+        Feature::ElementSegmentCount,
+        Feature::TotalFunctionReferences,
+        Feature::ImportCount,
+        Feature::ExportCount,
+        Feature::LinearMemoryBytes,
+        Feature::NativeCodeBytes,
+        Feature::NestedLoopFloatAccesses,
+        Feature::StaticFootprintBytes,
+        Feature::StackUsageBytes,
+    ];
+
+    /// Every feature of an estimate, as stored alongside measurements
+    pub fn vector(memory_info: &MemoryInfoEstimator) -> BTreeMap<Feature, u64> {
+        Feature::ALL.iter().map(|feature| (*feature, feature.value(memory_info))).collect()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::BinarySizeBytes => "binary_size_bytes",
+            Feature::FunctionCount => "function_count",
+            Feature::GlobalCount => "global_count",
+            Feature::DataSegmentCount => "data_segment_count",
+            Feature::ElementSegmentCount => "element_segment_count",
+            Feature::TotalFunctionReferences => "total_function_references",
+            Feature::ImportCount => "import_count",
+            Feature::ExportCount => "export_count",
+            Feature::LinearMemoryBytes => "linear_memory_bytes",
+            Feature::NativeCodeBytes => "native_code_bytes",
+            Feature::NestedLoopFloatAccesses => "nested_loop_float_accesses",
+            Feature::StaticFootprintBytes => "static_footprint_bytes",
+            Feature::StackUsageBytes => "stack_usage_bytes",
+        }
+    }
+
+    pub fn value(&self, memory_info: &MemoryInfoEstimator) -> u64 {
+        match self {
+            Feature::BinarySizeBytes => memory_info.binary_size_bytes,
+            Feature::FunctionCount => memory_info.function_count as u64,
+            Feature::GlobalCount => memory_info.global_count as u64,
+            Feature::DataSegmentCount => memory_info.data_segment_count as u64,
+            Feature::ElementSegmentCount => memory_info.element_segment_count as u64,
+            Feature::TotalFunctionReferences => memory_info.total_function_references as u64,
+            Feature::ImportCount => memory_info.import_count as u64,
+            Feature::ExportCount => memory_info.export_count as u64,
+            Feature::LinearMemoryBytes => memory_info.linear_memory_bytes,
+            Feature::NativeCodeBytes => memory_info.native_code_bytes,
+            Feature::NestedLoopFloatAccesses => memory_info.nested_loop_float_accesses as u64,
+            Feature::StaticFootprintBytes => memory_info.static_footprint_bytes,
+            Feature::StackUsageBytes => memory_info.stack_usage_bytes,
+        }
+    }
+}
+
+/// Workload classes a classification rule can assign. Profiles can name their own
+/// classes: any other name becomes `Custom` and can be matched by buffer and payload rules.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Workload {
+    Ml,
+    Matrix,
+    Simple,
+    /// No classification rule matched
+    Unclassified,
+    Custom(String),
+}
+
+impl Workload {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Workload::Ml => "ml",
+            Workload::Matrix => "matrix",
+            Workload::Simple => "simple",
+            Workload::Unclassified => "unclassified",
+            Workload::Custom(name) => name,
+        }
+    }
+
+    /// Human readable name of the class
+    pub fn display_name(&self) -> &str {
+        match self {
+            Workload::Ml => "ML Inference",
+            Workload::Matrix => "Matrix Operations",
+            Workload::Simple => "Simple Computation",
+            Workload::Unclassified => "Unclassified",
+            Workload::Custom(name) => name,
+        }
+    }
+}
+
+impl From<String> for Workload {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "ml" => Workload::Ml,
+            "matrix" => Workload::Matrix,
+            "simple" => Workload::Simple,
+            "unclassified" => Workload::Unclassified,
+            _ => Workload::Custom(name),
+        }
+    }
+}
+
+impl From<Workload> for String {
+    fn from(workload: Workload) -> Self {
+        workload.as_str().to_string()
+    }
+}
+
+/// Something a binary declares or links. The pattern matches a value of the
+/// corresponding `WorkloadSignals` set as a case-insensitive substring.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    Import(String),
+    Export(String),
+    Producer(String),
+    Library(String),
+    Name(String),
+}
+
+impl Signal {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Signal::Import(_) => "import",
+            Signal::Export(_) => "export",
+            Signal::Producer(_) => "producer",
+            Signal::Library(_) => "library",
+            Signal::Name(_) => "name",
+        }
+    }
+
+    /// Values of `signals` matching this pattern
+    pub fn matches<'a>(&self, signals: &'a WorkloadSignals) -> Vec<&'a String> {
+        let (pattern, values) = match self {
+            Signal::Import(pattern) => (pattern, &signals.imports),
+            Signal::Export(pattern) => (pattern, &signals.exports),
+            Signal::Producer(pattern) => (pattern, &signals.producers),
+            Signal::Library(pattern) => (pattern, &signals.libraries),
+            Signal::Name(pattern) => (pattern, &signals.names),
+        };
+        let pattern = pattern.to_lowercase();
+        values.iter().filter(|value| value.to_lowercase().contains(&pattern)).collect()
+    }
+}
+
+/// Why a binary was given its workload class
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Evidence {
+    /// A signal of the rule matched a value of the binary
+    Signal { signal: Signal, matched: String },
+    /// A feature of the binary fell in the range of the rule
+    Feature { feature: Feature, value: u64, range: Range },
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Evidence::Signal { signal, matched } => write!(f, "{} {}", signal.kind(), matched),
+            Evidence::Feature { feature, value, range } => write!(f, "{} = {} ({})", feature.as_str(), value, range),
+        }
+    }
+}
+
+/// Bounds on a feature; all the given bounds must hold
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gt: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ge: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lt: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub le: Option<u64>,
+}
+
+impl Range {
+    pub fn contains(&self, value: u64) -> bool {
+        // Exclusive bounds are inclusive ones off by one; `> u64::MAX` and `< 0` admit nothing
+        let (Some(low), Some(high)) = (
+            self.gt.map_or(Some(0), |bound| bound.checked_add(1)),
+            self.lt.map_or(Some(u64::MAX), |bound| bound.checked_sub(1)),
+        ) else {
+            return false;
+        };
+        (low.max(self.ge.unwrap_or(0))..=high.min(self.le.unwrap_or(u64::MAX))).contains(&value)
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bounds: Vec<String> = [("> ", self.gt), (">= ", self.ge), ("< ", self.lt), ("<= ", self.le)]
+            .iter()
+            .filter_map(|(operator, bound)| bound.map(|bound| format!("{}{}", operator, bound)))
+            .collect();
+        write!(f, "{}", bounds.join(", "))
+    }
+}
+
+/// Every feature in the map must fall in its range
+pub type Condition = BTreeMap<Feature, Range>;
+
+/// First condition that holds
+fn holding_condition<'a>(conditions: &'a [Condition], memory_info: &MemoryInfoEstimator) -> Option<&'a Condition> {
+    conditions.iter().find(|condition| {
+        condition
+            .iter()
+            .all(|(feature, range)| range.contains(feature.value(memory_info)))
+    })
+}
+
+/// A rule matches when any of its conditions holds; no conditions means always
+fn any_condition(conditions: &[Condition], memory_info: &MemoryInfoEstimator) -> bool {
+    conditions.is_empty() || holding_condition(conditions, memory_info).is_some()
+}
+
+/// Assigns a workload class when any of its signals matches the binary, or failing
+/// that when any of its feature conditions holds. A rule with neither always matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClassificationRule {
+    pub workload: Workload,
+    #[serde(default)]
+    pub signals: Vec<Signal>,
+    #[serde(default)]
+    pub when: Vec<Condition>,
+}
+
+impl ClassificationRule {
+    /// What makes the rule match, `None` when it doesn't
+    pub fn evidence(&self, memory_info: &MemoryInfoEstimator) -> Option<Vec<Evidence>> {
+        let signals: Vec<Evidence> = self
+            .signals
+            .iter()
+            .flat_map(|signal| {
+                signal
+                    .matches(&memory_info.signals)
+                    .into_iter()
+                    .map(|matched| Evidence::Signal { signal: signal.clone(), matched: matched.clone() })
+            })
+            .collect();
+        if !signals.is_empty() {
+            return Some(signals);
+        }
+        if let Some(condition) = holding_condition(&self.when, memory_info) {
+            return Some(
+                condition
+                    .iter()
+                    .map(|(feature, range)| Evidence::Feature {
+                        feature: *feature,
+                        value: feature.value(memory_info),
+                        range: range.clone(),
+                    })
+                    .collect(),
+            );
+        }
+        (self.signals.is_empty() && self.when.is_empty()).then(Vec::new)
+    }
+}
+
+/// `base_bytes + Σ feature × bytes_per_unit`, optionally capped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BufferFormula {
+    pub base_bytes: u64,
+    #[serde(default)]
+    pub per_unit: BTreeMap<Feature, f64>,
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+impl BufferFormula {
+    pub fn evaluate(&self, memory_info: &MemoryInfoEstimator) -> u64 {
+        let variable: f64 = self
+            .per_unit
+            .iter()
+            .map(|(feature, bytes_per_unit)| feature.value(memory_info) as f64 * bytes_per_unit)
+            .sum();
+        let bytes = (self.base_bytes as f64 + variable).max(0.0) as u64;
+        match self.max_bytes {
+            Some(max_bytes) => bytes.min(max_bytes),
+            None => bytes,
+        }
+    }
+}
+
+/// Buffer added on top of the minimum estimate; the first matching rule wins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BufferRule {
+    pub name: String,
+    /// Workload class that must be set, if any
+    #[serde(default)]
+    pub workload: Option<Workload>,
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    pub buffer: BufferFormula,
+}
+
+impl BufferRule {
+    pub fn matches(&self, memory_info: &MemoryInfoEstimator) -> bool {
+        self.workload.as_ref().is_none_or(|workload| *workload == memory_info.workload)
+            && any_condition(&self.when, memory_info)
+    }
+}
+
+fn one_copy() -> u64 {
+    100
+}
+
+/// Memory a job's payload adds to the minimum; the first matching rule wins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PayloadRule {
+    pub name: String,
+    /// Workload class that must be set, if any
+    #[serde(default)]
+    pub workload: Option<Workload>,
+    /// Copies of the payload held in guest memory, in percent of its size;
+    /// lowering the string through the canonical ABI makes one
+    #[serde(default = "one_copy")]
+    pub copy_percent: u64,
+    /// Bytes per number once the payload is parsed into native structures
+    #[serde(default)]
+    pub bytes_per_number: u64,
+    /// Bytes per element of the product of two compatible matrices of the payload
+    #[serde(default)]
+    pub bytes_per_product_element: u64,
+    /// Fixed bytes per binary item (base64 blob or referenced file), e.g. one input tensor
+    #[serde(default)]
+    pub bytes_per_blob: u64,
+    /// Memory per byte of binary item once decoded, in percent
+    #[serde(default)]
+    pub blob_percent: u64,
+}
+
+impl PayloadRule {
+    pub fn matches(&self, memory_info: &MemoryInfoEstimator) -> bool {
+        self.workload.as_ref().is_none_or(|workload| *workload == memory_info.workload)
+    }
+}
+
+/// Percentage applied to the buffer depending on how the guest grows its memory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrowthScaling {
+    pub allocator_driven: u64,
+    pub explicit: u64,
+    pub absent: u64,
+}
+
+impl GrowthScaling {
+    pub fn percent(&self, behavior: GrowthBehavior) -> u64 {
+        match behavior {
+            GrowthBehavior::AllocatorDriven => self.allocator_driven,
+            GrowthBehavior::Explicit => self.explicit,
+            GrowthBehavior::Absent => self.absent,
+        }
+    }
+}
+
+fn no_growth_scaling() -> GrowthScaling {
+    GrowthScaling { allocator_driven: 100, explicit: 100, absent: 100 }
+}
+
+/// A profile of classification and buffer rules, loaded from JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    pub profile: String,
+    #[serde(default)]
+    pub classification: Vec<ClassificationRule>,
+    pub buffers: Vec<BufferRule>,
+    #[serde(default = "no_growth_scaling")]
+    pub growth_scaling_percent: GrowthScaling,
+    /// Without payload rules the payload is not accounted for
+    #[serde(default)]
+    pub payload: Vec<PayloadRule>,
+}
+
+impl RuleSet {
+    /// The rules shipped in `rules/default.json`
+    pub fn default_profile() -> Self {
+        Self::from_json(DEFAULT_RULES).expect("bundled default rules are valid")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, EstimatorError> {
+        let rules: RuleSet = serde_json::from_str(json).map_err(|e| EstimatorError::RuleConfig(e.to_string()))?;
+        if rules.buffers.is_empty() {
+            return Err(EstimatorError::RuleConfig(format!("rule profile \"{}\" has no buffer rules", rules.profile)));
+        }
+        Ok(rules)
+    }
+
+    pub fn to_json(&self) -> Result<String, EstimatorError> {
+        serde_json::to_string_pretty(self).map_err(|e| EstimatorError::RuleConfig(e.to_string()))
+    }
+
+    /// Write the profile so it can be loaded with `MEMORY_ESTIMATOR_RULES`
+    pub fn save(&self, path: &str) -> Result<(), EstimatorError> {
+        std::fs::write(path, self.to_json()? + "\n").map_err(|e| EstimatorError::from_io(path, e))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, EstimatorError> {
+        let bytes = read_file(path)?;
+        let json = String::from_utf8(bytes).map_err(|e| EstimatorError::RuleConfig(format!("{}: {}", path, e)))?;
+        Self::from_json(&json).map_err(|e| match e {
+            EstimatorError::RuleConfig(reason) => EstimatorError::RuleConfig(format!("{}: {}", path, reason)),
+            other => other,
+        })
+    }
+
+    /// Set the workload class from the first matching classification rule, with its evidence
+    pub fn classify(&self, memory_info: &mut MemoryInfoEstimator) {
+        let (workload, evidence) = self
+            .classification
+            .iter()
+            .find_map(|rule| rule.evidence(memory_info).map(|evidence| (rule.workload.clone(), evidence)))
+            .unwrap_or((Workload::Unclassified, Vec::new()));
+        memory_info.workload = workload;
+        memory_info.workload_evidence = evidence;
+    }
+
+    /// First payload rule matching the workload
+    pub fn payload_rule(&self, memory_info: &MemoryInfoEstimator) -> Option<&PayloadRule> {
+        self.payload.iter().find(|rule| rule.matches(memory_info))
+    }
+
+    /// Buffer from the first matching rule, scaled by the growth behavior
+    pub fn buffer_bytes(&self, memory_info: &MemoryInfoEstimator) -> (Option<&BufferRule>, u64) {
+        let Some(rule) = self.buffers.iter().find(|rule| rule.matches(memory_info)) else {
+            return (None, 0);
+        };
+        let bytes = rule.buffer.evaluate(memory_info);
+        let bytes = match memory_info.growth_analysis.as_ref() {
+            Some(growth) => bytes.saturating_mul(self.growth_scaling_percent.percent(growth.behavior)) / 100,
+            None => bytes,
+        };
+        (Some(rule), bytes)
+    }
+}
+
+/// Rules in effect for this process; the default profile unless others were loaded
+pub fn active_rules() -> &'static RuleSet {
+    ACTIVE_RULES.get_or_init(RuleSet::default_profile)
+}
+
+/// Load a rules file as the active profile. Must run before the first estimate.
+pub fn load_rules(path: &str) -> Result<&'static RuleSet, EstimatorError> {
+    let rules = RuleSet::from_file(path)?;
+    ACTIVE_RULES
+        .set(rules)
+        .map_err(|_| EstimatorError::RuleConfig("estimation rules are already loaded".to_string()))?;
+    Ok(active_rules())
+}
+
+/// Load the file named by `MEMORY_ESTIMATOR_RULES`, or keep the default profile
+pub fn load_rules_from_env() -> Result<&'static RuleSet, EstimatorError> {
+    match std::env::var(RULES_ENV) {
+        Ok(path) if !path.is_empty() => load_rules(&path),
+        _ => Ok(active_rules()),
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// Everything that can make an estimate unusable. The estimator never falls back
+/// to zeroed fields: callers get one of these and can reject the job instead.
+#[derive(Debug)]
+pub enum EstimatorError {
+    /// An input file (wasm, cwasm, rules) does not exist
+    MissingFile { path: String },
+    /// An input file exists but could not be read
+    Io { path: String, source: io::Error },
+    /// A core module failed to parse or validate
+    MalformedWasm { module_index: Option<u32>, source: wasmparser::BinaryReaderError },
+    /// The component wrapping the core modules failed to parse
+    ComponentParse(wasmparser::BinaryReaderError),
+    /// The module uses something the estimate can't account for (memory64, multi-memory)
+    UnsupportedFeature { module_index: u32, feature: String },
+    /// The precompiled artifact is not a wasmtime ELF image or is corrupted
+    MalformedCwasm(String),
+    /// An ONNX model the job loads through wasi-nn could not be parsed
+    MalformedModel(String),
+    /// The estimation rules file is invalid or could not be applied
+    RuleConfig(String),
+    /// A serialized estimation report could not be read back
+    Report(String),
+}
+
+impl EstimatorError {
+    /// Map an I/O error on `path`, telling a missing file apart from other failures
+    pub fn from_io(path: &str, error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::NotFound {
+            EstimatorError::MissingFile { path: path.to_string() }
+        } else {
+            EstimatorError::Io { path: path.to_string(), source: error }
+        }
+    }
+
+    /// Attribute a parse error to the core module it occurred in
+    pub(crate) fn in_module(self, index: u32) -> Self {
+        match self {
+            EstimatorError::MalformedWasm { module_index: None, source } => {
+                EstimatorError::MalformedWasm { module_index: Some(index), source }
+            }
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for EstimatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EstimatorError::MissingFile { path } => write!(f, "file not found: {}", path),
+            EstimatorError::Io { path, source } => write!(f, "failed to read {}: {}", path, source),
+            EstimatorError::MalformedWasm { module_index: Some(index), source } => {
+                write!(f, "malformed core module {}: {}", index, source)
+            }
+            EstimatorError::MalformedWasm { module_index: None, source } => write!(f, "malformed wasm: {}", source),
+            EstimatorError::ComponentParse(source) => write!(f, "failed to parse component: {}", source),
+            EstimatorError::UnsupportedFeature { module_index, feature } => {
+                write!(f, "unsupported feature in core module {}: {}", module_index, feature)
+            }
+            EstimatorError::MalformedCwasm(reason) => write!(f, "malformed precompiled artifact: {}", reason),
+            EstimatorError::MalformedModel(reason) => write!(f, "malformed ONNX model: {}", reason),
+            EstimatorError::RuleConfig(reason) => write!(f, "invalid estimation rules: {}", reason),
+            EstimatorError::Report(reason) => write!(f, "invalid estimation report: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EstimatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EstimatorError::Io { source, .. } => Some(source),
+            EstimatorError::MalformedWasm { source, .. } => Some(source),
+            EstimatorError::ComponentParse(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<wasmparser::BinaryReaderError> for EstimatorError {
+    fn from(source: wasmparser::BinaryReaderError) -> Self {
+        EstimatorError::MalformedWasm { module_index: None, source }
+    }
+}
+
+impl From<object::read::Error> for EstimatorError {
+    fn from(error: object::read::Error) -> Self {
+        EstimatorError::MalformedCwasm(error.to_string())
+    }
+}
+
+/// Read a whole input file
+pub(crate) fn read_file(path: &str) -> Result<Vec<u8>, EstimatorError> {
+    fs::read(path).map_err(|e| EstimatorError::from_io(path, e))
+}
@@ -0,0 +1,267 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::estimation_report::{EstimateBreakdown, EstimationReport};
+use crate::job_error::JobError;
+
+/// Headroom applied to the estimate when it is enforced
+pub const DEFAULT_SAFETY_FACTOR: f64 = 1.5;
+
+/// What a growth request applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrowthTarget {
+    /// A linear memory; sizes are in bytes
+    Memory,
+    /// A table; sizes are in elements
+    Table,
+}
+
+/// One `memory_growing` / `table_growing` call made by wasmtime.
+/// Instantiating a memory or table also shows up here, growing from 0.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrowthRequest {
+    pub target: GrowthTarget,
+    pub current: u64,
+    pub desired: u64,
+    pub maximum: Option<u64>,
+    /// Allowed by the tracker and not failed afterwards in the runtime
+    pub granted: bool,
+    /// Time since the tracker was created
+    pub elapsed_us: u64,
+}
+
+/// Caps derived from an estimate, enforced on the store running the job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryLimit {
+    pub safety_factor: f64,
+    /// Linear memory the estimate expects: the initial pages, the payload and the buffer.
+    /// The rest of the peak (native code and stack, host tables, inference) is outside the guest.
+    pub estimated_linear_memory_bytes: u64,
+    /// Table elements found in the binary
+    pub estimated_table_elements: u64,
+    /// Total of all linear memories
+    pub linear_memory_bytes: u64,
+    /// Total of all table elements
+    pub table_elements: u64,
+    pub instances: usize,
+    pub tables: usize,
+    pub memories: usize,
+}
+
+impl MemoryLimit {
+    /// Scale the estimated linear memory and the counts found in the binary by `safety_factor`.
+    /// Factors below 1 would reject jobs the estimate allows, so they are raised to 1.
+    pub fn from_report(report: &EstimationReport, safety_factor: f64) -> Self {
+        let safety_factor = safety_factor.max(1.0);
+        let features = &report.features;
+        let scale = |value: u64| (value as f64 * safety_factor).ceil() as u64;
+        let estimate = &report.estimate;
+        let estimated_linear_memory_bytes = estimate.linear_memory_bytes + estimate.payload_bytes + estimate.buffer_bytes;
+        let estimated_table_elements = features.total_function_references as u64;
+        Self {
+            safety_factor,
+            estimated_linear_memory_bytes,
+            estimated_table_elements,
+            linear_memory_bytes: scale(estimated_linear_memory_bytes),
+            table_elements: scale(estimated_table_elements).max(1),
+            instances: scale(features.core_instance_count.max(1) as u64) as usize,
+            tables: scale(features.function_tables.len().max(1) as u64) as usize,
+            memories: scale(features.memories.len().max(1) as u64) as usize,
+        }
+    }
+}
+
+/// Records every growth of the guest's memories and tables. Wasmtime only reports
+/// sizes, not which memory grew, so the running total adds up the deltas.
+#[derive(Debug, Clone)]
+pub struct GuestMemoryTracker {
+    started: Instant,
+    requests: Vec<GrowthRequest>,
+    memory_bytes: u64,
+    table_elements: u64,
+    limit: Option<MemoryLimit>,
+}
+
+impl Default for GuestMemoryTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuestMemoryTracker {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: Vec::new(),
+            memory_bytes: 0,
+            table_elements: 0,
+            limit: None,
+        }
+    }
+
+    /// Refuse any growth taking the totals past `limit`
+    pub fn with_limit(limit: MemoryLimit) -> Self {
+        Self { limit: Some(limit), ..Self::new() }
+    }
+
+    pub fn limit(&self) -> Option<&MemoryLimit> {
+        self.limit.as_ref()
+    }
+
+    /// Record a linear memory growth; fails with `EstimateExceeded` past the limit
+    pub fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> Result<bool, JobError> {
+        let requested = self.memory_bytes.saturating_add(desired.saturating_sub(current) as u64);
+        if let Some(limit) = self.limit.as_ref().filter(|limit| requested > limit.linear_memory_bytes) {
+            let (estimated, limit) = (limit.estimated_linear_memory_bytes, limit.linear_memory_bytes);
+            self.deny(GrowthTarget::Memory, current, desired, maximum);
+            return Err(JobError::EstimateExceeded { target: GrowthTarget::Memory, requested, estimated, limit });
+        }
+        self.memory_bytes = requested;
+        Ok(self.record(GrowthTarget::Memory, current, desired, maximum))
+    }
+
+    /// The runtime failed a growth that was allowed (e.g. past the memory's maximum)
+    pub fn memory_grow_failed(&mut self) {
+        if let Some(request) = self.undo_last(GrowthTarget::Memory) {
+            self.memory_bytes = self.memory_bytes.saturating_sub(request.desired.saturating_sub(request.current));
+        }
+    }
+
+    /// Record a table growth; fails with `EstimateExceeded` past the limit
+    pub fn table_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> Result<bool, JobError> {
+        let requested = self.table_elements.saturating_add(desired.saturating_sub(current) as u64);
+        if let Some(limit) = self.limit.as_ref().filter(|limit| requested > limit.table_elements) {
+            let (estimated, limit) = (limit.estimated_table_elements, limit.table_elements);
+            self.deny(GrowthTarget::Table, current, desired, maximum);
+            return Err(JobError::EstimateExceeded { target: GrowthTarget::Table, requested, estimated, limit });
+        }
+        self.table_elements = requested;
+        Ok(self.record(GrowthTarget::Table, current, desired, maximum))
+    }
+
+    pub fn table_grow_failed(&mut self) {
+        if let Some(request) = self.undo_last(GrowthTarget::Table) {
+            self.table_elements = self.table_elements.saturating_sub(request.desired.saturating_sub(request.current));
+        }
+    }
+
+    fn record(&mut self, target: GrowthTarget, current: usize, desired: usize, maximum: Option<usize>) -> bool {
+        self.requests.push(GrowthRequest {
+            target,
+            current: current as u64,
+            desired: desired as u64,
+            maximum: maximum.map(|maximum| maximum as u64),
+            granted: true,
+            elapsed_us: self.started.elapsed().as_micros() as u64,
+        });
+        true
+    }
+
+    fn deny(&mut self, target: GrowthTarget, current: usize, desired: usize, maximum: Option<usize>) {
+        self.record(target, current, desired, maximum);
+        if let Some(request) = self.requests.last_mut() {
+            request.granted = false;
+        }
+    }
+
+    /// Mark the last granted request on `target` as denied
+    fn undo_last(&mut self, target: GrowthTarget) -> Option<GrowthRequest> {
+        let request = self.requests.iter_mut().rev().find(|request| request.target == target && request.granted)?;
+        request.granted = false;
+        Some(request.clone())
+    }
+
+    /// Current total of the guest's linear memories
+    pub fn linear_memory_bytes(&self) -> u64 {
+        self.memory_bytes
+    }
+
+    /// Peaks so far, replayed from the granted requests so failed growths don't count
+    pub fn peak(&self) -> GuestMemoryPeak {
+        let mut peak = GuestMemoryPeak { requests: self.requests.clone(), ..GuestMemoryPeak::new() };
+        let (mut memory_bytes, mut table_elements) = (0u64, 0u64);
+        for request in self.requests.iter() {
+            match request.target {
+                GrowthTarget::Memory => peak.memory_grow_requests += 1,
+                GrowthTarget::Table => peak.table_grow_requests += 1,
+            }
+            if !request.granted {
+                peak.denied_requests += 1;
+                continue;
+            }
+            let delta = request.desired.saturating_sub(request.current);
+            match request.target {
+                GrowthTarget::Memory => {
+                    memory_bytes += delta;
+                    peak.peak_linear_memory_bytes = peak.peak_linear_memory_bytes.max(memory_bytes);
+                    peak.largest_memory_bytes = peak.largest_memory_bytes.max(request.desired);
+                }
+                GrowthTarget::Table => {
+                    table_elements += delta;
+                    peak.peak_table_elements = peak.peak_table_elements.max(table_elements);
+                }
+            }
+        }
+        peak.final_linear_memory_bytes = memory_bytes;
+        peak
+    }
+}
+
+/// Exact guest memory usage of a job, as seen by the resource limiter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestMemoryPeak {
+    /// Highest total size of all linear memories at once
+    pub peak_linear_memory_bytes: u64,
+    pub final_linear_memory_bytes: u64,
+    /// Largest single linear memory
+    pub largest_memory_bytes: u64,
+    pub peak_table_elements: u64,
+    pub memory_grow_requests: u32,
+    pub table_grow_requests: u32,
+    pub denied_requests: u32,
+    pub requests: Vec<GrowthRequest>,
+}
+
+impl Default for GuestMemoryPeak {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuestMemoryPeak {
+    pub fn new() -> Self {
+        Self {
+            peak_linear_memory_bytes: 0,
+            final_linear_memory_bytes: 0,
+            largest_memory_bytes: 0,
+            peak_table_elements: 0,
+            memory_grow_requests: 0,
+            table_grow_requests: 0,
+            denied_requests: 0,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Compare the measured peak with the static estimate of the same binary
+    pub fn compare(&self, estimate: &EstimateBreakdown) -> GuestMemoryComparison {
+        GuestMemoryComparison {
+            measured_linear_memory_bytes: self.peak_linear_memory_bytes,
+            estimated_linear_memory_bytes: estimate.linear_memory_bytes,
+            estimated_peak_bytes: estimate.peak_bytes,
+            growth_beyond_estimate_bytes: self.peak_linear_memory_bytes as i64 - estimate.linear_memory_bytes as i64,
+            within_estimated_peak: self.peak_linear_memory_bytes <= estimate.peak_bytes,
+        }
+    }
+}
+
+/// Measured guest linear memory against the estimate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestMemoryComparison {
+    pub measured_linear_memory_bytes: u64,
+    /// Initial linear memory declared by the binary
+    pub estimated_linear_memory_bytes: u64,
+    pub estimated_peak_bytes: u64,
+    /// How far the guest grew past its declared linear memory; negative if it never reached it
+    pub growth_beyond_estimate_bytes: i64,
+    pub within_estimated_peak: bool,
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+
+/// Cgroup under which every job gets its own child cgroup
+pub const CGROUP_ENV: &str = "MEMORY_ESTIMATOR_CGROUP";
+pub const DEFAULT_CGROUP: &str = "/sys/fs/cgroup/memory-estimator";
+/// Memory a child uses besides its guest's linear memory, in MB; when unset it is measured from the children
+pub const CHILD_BASELINE_ENV: &str = "MEMORY_ESTIMATOR_CHILD_BASELINE_MB";
+/// Child baseline until the first child reported one
+pub const DEFAULT_CHILD_BASELINE_BYTES: u64 = 128 * 1024 * 1024;
+
+/// What the kernel accounted to the cgroup of a job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CgroupUsage {
+    pub path: String,
+    /// `memory.max` the job ran under, `None` when unlimited
+    pub memory_max_bytes: Option<u64>,
+    /// `memory.peak`: highest usage of anonymous, file and kernel memory
+    pub peak_bytes: Option<u64>,
+    /// `memory.stat` at the end of the job
+    pub stat: BTreeMap<String, u64>,
+    /// Times usage hit `memory.max` and reclaim failed
+    pub oom_events: u64,
+    /// Processes of the job killed by the OOM killer
+    pub oom_kill_events: u64,
+}
+
+impl CgroupUsage {
+    pub fn oom_killed(&self) -> bool {
+        self.oom_kill_events > 0
+    }
+}
+
+/// A cgroup v2 created for one job, removed with `finish` once the job exited
+#[derive(Debug)]
+pub struct JobCgroup {
+    path: PathBuf,
+    memory_max_bytes: Option<u64>,
+}
+
+impl JobCgroup {
+    /// Create `<base>/<name>` with `memory.max` set, enabling the memory controller on the way.
+    /// Fails when cgroup v2 isn't mounted there or isn't writable by this process.
+    pub fn create(base: &Path, name: &str, memory_max_bytes: Option<u64>) -> io::Result<Self> {
+        enable_memory_controller(base)?;
+        let path = base.join(name);
+        match fs::create_dir(&path) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+            _ => {}
+        }
+        let cgroup = Self { path, memory_max_bytes };
+        if let Some(memory_max) = memory_max_bytes {
+            if let Err(e) = fs::write(cgroup.path.join("memory.max"), memory_max.to_string()) {
+                let _ = fs::remove_dir(&cgroup.path);
+                return Err(e);
+            }
+        }
+        Ok(cgroup)
+    }
+
+    /// Create the cgroup under the base named by `MEMORY_ESTIMATOR_CGROUP`, or `DEFAULT_CGROUP`
+    pub fn from_env(name: &str, memory_max_bytes: Option<u64>) -> io::Result<Self> {
+        let base = match std::env::var(CGROUP_ENV) {
+            Ok(base) if !base.is_empty() => PathBuf::from(base),
+            _ => PathBuf::from(DEFAULT_CGROUP),
+        };
+        Self::create(&base, name, memory_max_bytes)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the process `command` spawns into this cgroup between fork and exec, so all of its
+    /// memory is accounted here from its first allocation. `spawn` fails when it can't move.
+    pub fn join_on_spawn(&self, command: &mut Command) -> io::Result<()> {
+        let procs = CString::new(self.path.join("cgroup.procs").as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // Safety: the hook only makes async-signal-safe calls and allocates nothing
+        unsafe {
+            command.pre_exec(move || {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // "0" is the writing process itself
+                let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+                let error = io::Error::last_os_error();
+                libc::close(fd);
+                if written != 1 {
+                    return Err(error);
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Read what the job used so far
+    pub fn usage(&self) -> CgroupUsage {
+        let read = |file: &str| fs::read_to_string(self.path.join(file)).unwrap_or_default();
+        let events = parse_flat_keyed(&read("memory.events"));
+        CgroupUsage {
+            path: self.path.to_string_lossy().to_string(),
+            memory_max_bytes: self.memory_max_bytes,
+            peak_bytes: read("memory.peak").trim().parse().ok(),
+            stat: parse_flat_keyed(&read("memory.stat")),
+            oom_events: events.get("oom").copied().unwrap_or(0),
+            oom_kill_events: events.get("oom_kill").copied().unwrap_or(0),
+        }
+    }
+
+    /// Read the final usage and remove the cgroup; every process in it must have exited
+    pub fn finish(self) -> CgroupUsage {
+        let usage = self.usage();
+        if let Err(e) = fs::remove_dir(&self.path) {
+            println!("Error removing cgroup {}: {}", self.path.display(), e);
+        }
+        usage
+    }
+}
+
+/// `memory.max` of a job: its estimated peak with `safety_factor` headroom, plus the child
+/// baseline, which the estimate of the guest leaves out
+pub fn job_memory_max(estimated_peak: u64, safety_factor: f64, child_baseline: u64) -> u64 {
+    ((estimated_peak as f64 * safety_factor.max(1.0)).ceil() as u64).saturating_add(child_baseline)
+}
+
+/// What a child process uses besides its guest's linear memory: the binary, wasmtime compiling the
+/// component, the rules and the task. Measured as the lowest over the children, so a job with large
+/// host-side buffers doesn't raise it for every other job.
+#[derive(Debug)]
+pub struct ChildBaseline {
+    fixed_bytes: Option<u64>,
+    measured_bytes: AtomicU64,
+}
+
+impl ChildBaseline {
+    /// `DEFAULT_CHILD_BASELINE_BYTES` until `observe` measures one
+    pub fn measured() -> Self {
+        Self { fixed_bytes: None, measured_bytes: AtomicU64::new(u64::MAX) }
+    }
+
+    /// Always `bytes`, whatever the children measure
+    pub fn fixed(bytes: u64) -> Self {
+        Self { fixed_bytes: Some(bytes), measured_bytes: AtomicU64::new(u64::MAX) }
+    }
+
+    /// Fixed to `MEMORY_ESTIMATOR_CHILD_BASELINE_MB` when set, else measured
+    pub fn from_env() -> Self {
+        match std::env::var(CHILD_BASELINE_ENV).ok().and_then(|baseline| baseline.parse::<u64>().ok()) {
+            Some(baseline_mb) => Self::fixed(baseline_mb * 1024 * 1024),
+            None => Self::measured(),
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.fixed_bytes.unwrap_or_else(|| match self.measured_bytes.load(Ordering::Relaxed) {
+            u64::MAX => DEFAULT_CHILD_BASELINE_BYTES,
+            measured => measured,
+        })
+    }
+
+    /// Take what a finished child used besides its guest into account; 0 means it wasn't measured
+    pub fn observe(&self, runtime_bytes: u64) {
+        if runtime_bytes > 0 {
+            self.measured_bytes.fetch_min(runtime_bytes, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Let `base` hand the memory controller to its children. cgroup v2 only allows this on a
+/// cgroup without processes of its own, which `base` is as long as only jobs live below it.
+fn enable_memory_controller(base: &Path) -> io::Result<()> {
+    let parent = base
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the cgroup base has no parent"))?;
+    if !parent.join("cgroup.controllers").exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not a cgroup v2 hierarchy", parent.display()),
+        ));
+    }
+    fs::create_dir_all(base)?;
+    if !has_controller(&base.join("cgroup.controllers"), "memory") {
+        fs::write(parent.join("cgroup.subtree_control"), "+memory")?;
+    }
+    if !has_controller(&base.join("cgroup.subtree_control"), "memory") {
+        fs::write(base.join("cgroup.subtree_control"), "+memory")?;
+    }
+    Ok(())
+}
+
+fn has_controller(file: &Path, controller: &str) -> bool {
+    fs::read_to_string(file).is_ok_and(|controllers| controllers.split_whitespace().any(|name| name == controller))
+}
+
+/// Parse the `key value` lines of `memory.stat` and `memory.events`
+pub fn parse_flat_keyed(contents: &str) -> BTreeMap<String, u64> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+use crate::guest_memory::GrowthTarget;
+
+/// Why a job was stopped or turned away after its estimate was made: admission
+/// control and the enforced limits, as opposed to an unusable estimate (`EstimatorError`)
+#[derive(Debug)]
+pub enum JobError {
+    /// A guest grew past the enforced estimate; `requested` is the total it asked for
+    /// (bytes of linear memory or table elements), `limit` is `estimated` times the safety factor
+    EstimateExceeded { target: GrowthTarget, requested: u64, estimated: u64, limit: u64 },
+    /// The estimated peak doesn't fit in the memory left above the reserve, even with no other job running
+    InsufficientMemory { estimated_peak: u64, available: u64, reserve: u64 },
+    /// The job was cancelled while it waited for memory to be admitted
+    AdmissionCancelled,
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::EstimateExceeded { target: GrowthTarget::Memory, requested, estimated, limit } => write!(
+                f,
+                "estimate exceeded: guest requested {} bytes of linear memory, the limit is {} bytes \
+                 ({} bytes of estimated linear memory: initial pages, payload and buffer)",
+                requested, limit, estimated
+            ),
+            JobError::EstimateExceeded { target: GrowthTarget::Table, requested, estimated, limit } => write!(
+                f,
+                "estimate exceeded: guest requested {} table elements, the limit is {} ({} estimated table elements)",
+                requested, limit, estimated
+            ),
+            JobError::InsufficientMemory { estimated_peak, available, reserve } => write!(
+                f,
+                "not enough memory: the estimated peak is {} bytes, {} bytes are available of which {} are reserved",
+                estimated_peak, available, reserve
+            ),
+            JobError::AdmissionCancelled => write!(f, "cancelled while waiting for memory"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
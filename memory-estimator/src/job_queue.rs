@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::child_ipc::ChildStage;
+use crate::estimation_report::EstimateBreakdown;
+use crate::memory_info_monitor::MemoryMonitor;
+
+/// How many finished jobs keep their record; older ones are dropped first
+pub const RETAINED_JOBS_ENV: &str = "MEMORY_ESTIMATOR_RETAINED_JOBS";
+pub const DEFAULT_RETAINED_JOBS: usize = 1000;
+
+/// Where a submitted job is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Estimating,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Estimating => "estimating",
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+/// Why a job was stopped before it finished on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    TimedOut,
+    Cancelled,
+    /// Killed by the kernel at the `memory.max` of its cgroup
+    OomKilled,
+}
+
+impl Termination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Termination::TimedOut => "timed_out",
+            Termination::Cancelled => "cancelled",
+            Termination::OomKilled => "oom_killed",
+        }
+    }
+}
+
+/// What a cancellation did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cancellation {
+    /// The job was still queued and won't run
+    Cancelled,
+    /// The job is being estimated or running; whoever runs it stops it
+    Requested,
+    /// Too late, the job already ended in this state
+    AlreadyFinished(JobState),
+}
+
+/// What the guest function returned, or why it didn't, as reported by the child
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestResult {
+    pub success: bool,
+    /// The returned values as JSON, empty when the call failed
+    pub values: Vec<serde_json::Value>,
+    pub error: Option<String>,
+    /// The call was interrupted at the job's deadline
+    #[serde(default)]
+    pub timed_out: bool,
+    /// What the guest wrote to its stdout and stderr, apart from the child's own output
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Everything known about a submitted job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: u64,
+    pub task_id: usize,
+    pub binary_name: String,
+    pub func_name: String,
+    pub state: JobState,
+    /// Last progress the child reported while running
+    pub stage: Option<ChildStage>,
+    pub submitted_at_unix_ms: u64,
+    pub started_at_unix_ms: Option<u64>,
+    pub finished_at_unix_ms: Option<u64>,
+    pub estimate: Option<EstimateBreakdown>,
+    /// Warnings the estimate raised when the job was admitted
+    pub warnings: Vec<String>,
+    pub measured: Option<MemoryMonitor>,
+    pub result: Option<GuestResult>,
+    /// Why the job failed outside of the guest (estimate rejected, child crashed)
+    pub error: Option<String>,
+    pub termination: Option<Termination>,
+    /// `DELETE /jobs/{id}` asked for the job to stop
+    pub cancel_requested: bool,
+}
+
+/// A job record without its estimate and measurements, for listings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobSummary {
+    pub id: u64,
+    pub task_id: usize,
+    pub binary_name: String,
+    pub state: JobState,
+    pub termination: Option<Termination>,
+    pub submitted_at_unix_ms: u64,
+    pub finished_at_unix_ms: Option<u64>,
+}
+
+impl JobSummary {
+    fn new(record: &JobRecord) -> Self {
+        Self {
+            id: record.id,
+            task_id: record.task_id,
+            binary_name: record.binary_name.clone(),
+            state: record.state,
+            termination: record.termination,
+            submitted_at_unix_ms: record.submitted_at_unix_ms,
+            finished_at_unix_ms: record.finished_at_unix_ms,
+        }
+    }
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0)
+}
+
+struct QueueState<T> {
+    next_id: u64,
+    pending: VecDeque<(u64, T)>,
+    jobs: BTreeMap<u64, JobRecord>,
+    /// Finished jobs in the order they finished, for eviction
+    finished: VecDeque<u64>,
+}
+
+impl<T> QueueState<T> {
+    /// Remember that `id` finished and drop the oldest finished records past `retained_jobs`
+    fn retire(&mut self, id: u64, retained_jobs: usize) {
+        self.finished.push_back(id);
+        while self.finished.len() > retained_jobs {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.jobs.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Jobs waiting for a worker, the records of every unfinished job and of the
+/// last `retained_jobs` finished ones. Submitting never blocks; workers take jobs in submission order.
+pub struct JobQueue<T> {
+    state: Mutex<QueueState<T>>,
+    available: Condvar,
+    retained_jobs: usize,
+}
+
+impl<T> JobQueue<T> {
+    pub fn new() -> Self {
+        Self::with_retention(DEFAULT_RETAINED_JOBS)
+    }
+
+    /// Keep the records of the last `retained_jobs` finished jobs
+    pub fn with_retention(retained_jobs: usize) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                next_id: 1,
+                pending: VecDeque::new(),
+                jobs: BTreeMap::new(),
+                finished: VecDeque::new(),
+            }),
+            available: Condvar::new(),
+            retained_jobs,
+        }
+    }
+
+    /// Retain `MEMORY_ESTIMATOR_RETAINED_JOBS` finished jobs, or `DEFAULT_RETAINED_JOBS`
+    pub fn from_env() -> Self {
+        let retained_jobs = std::env::var(RETAINED_JOBS_ENV)
+            .ok()
+            .and_then(|retained| retained.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_RETAINED_JOBS);
+        Self::with_retention(retained_jobs)
+    }
+
+    /// Queue `task` and return its job ID
+    pub fn submit(&self, task_id: usize, binary_name: &str, func_name: &str, task: T) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            JobRecord {
+                id,
+                task_id,
+                binary_name: binary_name.to_string(),
+                func_name: func_name.to_string(),
+                state: JobState::Queued,
+                stage: None,
+                submitted_at_unix_ms: now_unix_ms(),
+                started_at_unix_ms: None,
+                finished_at_unix_ms: None,
+                estimate: None,
+                warnings: Vec::new(),
+                measured: None,
+                result: None,
+                error: None,
+                termination: None,
+                cancel_requested: false,
+            },
+        );
+        state.pending.push_back((id, task));
+        self.available.notify_one();
+        id
+    }
+
+    /// Wait for the next queued job and mark it as being estimated
+    pub fn next(&self) -> (u64, T) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some((id, task)) = state.pending.pop_front() {
+                if let Some(record) = state.jobs.get_mut(&id) {
+                    record.state = JobState::Estimating;
+                    record.started_at_unix_ms = Some(now_unix_ms());
+                }
+                return (id, task);
+            }
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// Jobs waiting for a worker
+    pub fn depth(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut JobRecord)) {
+        if let Some(record) = self.state.lock().unwrap().jobs.get_mut(&id) {
+            change(record);
+        }
+    }
+
+    /// The estimate is known; the job waits for memory to be admitted
+    pub fn estimated(&self, id: u64, estimate: EstimateBreakdown) {
+        self.update(id, |record| record.estimate = Some(estimate));
+    }
+
+    /// The job was admitted and its child is about to start
+    pub fn start(&self, id: u64) {
+        self.update(id, |record| record.state = JobState::Running);
+    }
+
+    /// The child reached `stage`
+    pub fn progress(&self, id: u64, stage: ChildStage) {
+        self.update(id, |record| record.stage = Some(stage));
+    }
+
+    /// The estimate of the job raised `warnings` (precompiled artifact mismatch, unbounded recursion)
+    pub fn warn(&self, id: u64, warnings: &[String]) {
+        self.update(id, |record| record.warnings.extend_from_slice(warnings));
+    }
+
+    /// The child exited; the job succeeded only if the guest function did and nothing stopped it
+    pub fn finish(
+        &self,
+        id: u64,
+        measured: Option<MemoryMonitor>,
+        result: Option<GuestResult>,
+        error: Option<String>,
+        termination: Option<Termination>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let Some(record) = state.jobs.get_mut(&id) else {
+            return;
+        };
+        let newly_finished = !record.state.is_finished();
+        record.state = match (&termination, &result, &error) {
+            (Some(Termination::Cancelled), _, _) => JobState::Cancelled,
+            (None, Some(result), None) if result.success => JobState::Succeeded,
+            _ => JobState::Failed,
+        };
+        record.error = match (&result, error, termination) {
+            (_, Some(error), _) => Some(error),
+            (_, None, Some(termination)) => Some(format!("the job was stopped: {}", termination.as_str())),
+            (None, None, None) => Some("the job reported no result".to_string()),
+            (Some(_), None, None) => None,
+        };
+        record.measured = measured;
+        record.result = result;
+        record.termination = termination;
+        record.finished_at_unix_ms = Some(now_unix_ms());
+        if newly_finished {
+            state.retire(id, self.retained_jobs);
+        }
+    }
+
+    /// The job failed before its guest could run
+    pub fn fail(&self, id: u64, error: String) {
+        self.finish(id, None, None, Some(error), None);
+    }
+
+    /// Stop a job: a queued one is dropped right away, for a started one a cancellation is
+    /// requested and acted on by whoever runs it. `None` for an unknown job.
+    pub fn cancel(&self, id: u64) -> Option<Cancellation> {
+        let mut state = self.state.lock().unwrap();
+        let record = state.jobs.get_mut(&id)?;
+        match record.state {
+            JobState::Queued => {
+                record.state = JobState::Cancelled;
+                record.termination = Some(Termination::Cancelled);
+                record.cancel_requested = true;
+                record.finished_at_unix_ms = Some(now_unix_ms());
+                state.pending.retain(|(pending_id, _)| *pending_id != id);
+                state.retire(id, self.retained_jobs);
+                Some(Cancellation::Cancelled)
+            }
+            JobState::Estimating | JobState::Running => {
+                record.cancel_requested = true;
+                Some(Cancellation::Requested)
+            }
+            finished => Some(Cancellation::AlreadyFinished(finished)),
+        }
+    }
+
+    pub fn cancel_requested(&self, id: u64) -> bool {
+        self.state.lock().unwrap().jobs.get(&id).is_some_and(|record| record.cancel_requested)
+    }
+
+    pub fn get(&self, id: u64) -> Option<JobRecord> {
+        self.state.lock().unwrap().jobs.get(&id).cloned()
+    }
+
+    /// Every job, oldest first
+    pub fn list(&self) -> Vec<JobSummary> {
+        self.state.lock().unwrap().jobs.values().map(JobSummary::new).collect()
+    }
+}
+
+impl<T> Default for JobQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
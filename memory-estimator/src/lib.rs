@@ -1,2 +1,23 @@
+pub mod admission;
+pub mod calibration;
+pub mod call_graph;
+pub mod child_ipc;
+pub mod cwasm_analyzer;
+pub mod estimation_accuracy;
+pub mod estimation_report;
+pub mod estimation_rules;
+pub mod estimator_error;
+pub mod guest_memory;
+pub mod job_cgroup;
+pub mod job_error;
+pub mod job_queue;
+pub mod memory_growth;
 pub mod memory_info_estimator;
-pub mod wasm_loaders;
\ No newline at end of file
+pub mod memory_info_monitor;
+pub mod memory_layout;
+pub mod onnx_model;
+pub mod payload_analyzer;
+pub mod peak_interval;
+pub mod wasm_binary_analyzer;
+pub mod wasm_loaders;
+pub mod workload_signals;
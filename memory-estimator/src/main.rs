@@ -1,9 +1,24 @@
 
 use std::env;
 use std::io::{Read, Write};
-use std::process::{Command, Stdio};
-use memory_estimator::memory_info_estimator::{build_memory_info, convert_wasm_to_wat, print_memory_analysis_simple, MemoryInfoEstimator};
-use memory_estimator::wasm_loaders::run_wasm_job_component;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use memory_estimator::admission::AdmissionScheduler;
+use memory_estimator::calibration::calibrate;
+use memory_estimator::child_ipc::{val_to_json, ChildChannel, ChildListener, ChildMessage, ChildStage, IPC_SOCKET_ENV};
+use memory_estimator::estimation_accuracy::{AccuracyHistory, AccuracyRecord, AccuracySummary};
+use memory_estimator::estimation_report::EstimationReport;
+use memory_estimator::estimation_rules::{active_rules, load_rules_from_env};
+use memory_estimator::guest_memory::{GuestMemoryPeak, MemoryLimit, DEFAULT_SAFETY_FACTOR};
+use memory_estimator::job_cgroup::{job_memory_max, ChildBaseline, JobCgroup};
+use memory_estimator::job_error::JobError;
+use memory_estimator::job_queue::{Cancellation, GuestResult, JobQueue, Termination};
+use memory_estimator::memory_info_estimator::{build_memory_info, build_memory_info_with_payload, print_estimation_report};
+use memory_estimator::memory_info_monitor::{MemoryMonitor, MemorySampler, DEFAULT_SAMPLE_INTERVAL_MS};
+use memory_estimator::wasm_loaders::{check_precompiled_compatibility, estimate_exceeded, run_wasm_job_component, timed_out};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use base64::{Engine as _, engine::general_purpose};
@@ -11,6 +26,29 @@ use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 
 
 
+/// Set to "json" to print estimation reports as JSON instead of the text summary
+const OUTPUT_ENV: &str = "MEMORY_ESTIMATOR_OUTPUT";
+/// Per-binary job settings used by `calibrate`, keyed by binary name
+const CALIBRATION_JOBS_FILE: &str = "wasm-modules/calibration_jobs.json";
+const DEFAULT_CALIBRATION_RUNS: usize = 3;
+const DEFAULT_CALIBRATION_OUTPUT: &str = "rules/calibrated.json";
+/// Server-wide default of `WasmJobRequest::timeout_ms`
+const JOB_TIMEOUT_ENV: &str = "MEMORY_ESTIMATOR_JOB_TIMEOUT_MS";
+const DEFAULT_JOB_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+/// Time the child gets past its timeout to trap the guest and report before it is killed
+const CHILD_KILL_GRACE: Duration = Duration::from_secs(2);
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Keeps the task files and cgroups of children running at the same time apart, task IDs may repeat
+static CHILD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// How `calibrate` invokes a module; missing fields fall back to `run`, an empty payload and `models`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CalibrationJob {
+    func_name: Option<String>,
+    payload: Option<String>,
+    model_folder_name: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WasmJobRequest{
     binary_name: String,
@@ -20,115 +58,661 @@ pub struct WasmJobRequest{
     task_id: usize,
     model_folder_name: String,
     cwasm_file: String,
-    wat_file: String,
+    /// RSS sampling interval while the job runs, defaults to DEFAULT_SAMPLE_INTERVAL_MS
+    #[serde(default)]
+    sample_interval_ms: Option<u64>,
+    /// Fail the job once the guest grows its linear memory (or tables) past the estimate times `safety_factor`
+    #[serde(default)]
+    enforce_estimate: bool,
+    /// Headroom over the estimate when enforced, defaults to DEFAULT_SAFETY_FACTOR
+    #[serde(default)]
+    safety_factor: Option<f64>,
+    /// Wall-clock limit of the job, defaults to MEMORY_ESTIMATOR_JOB_TIMEOUT_MS or DEFAULT_JOB_TIMEOUT_MS.
+    /// Only an explicit one traps the guest; the default kills the child.
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+/// The task file handed to a child: the job and the estimate it was admitted with.
+/// The child never estimates itself, that would count against its own cgroup and peaks.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct ChildTask {
+    task: WasmJobRequest,
+    report: EstimationReport,
+}
+
+/// What the parent learned from a finished child
+struct ChildOutcome {
+    monitor: Option<MemoryMonitor>,
+    result: Option<GuestResult>,
+    /// Set when the child could not be run or exited unsuccessfully
+    error: Option<String>,
+    termination: Option<Termination>,
+}
+
+fn default_job_timeout_ms() -> u64 {
+    env::var(JOB_TIMEOUT_ENV).ok().and_then(|timeout| timeout.parse().ok()).unwrap_or(DEFAULT_JOB_TIMEOUT_MS)
+}
+
+/// Kill the child and everything it started; it leads its own process group
+fn kill_process_group(child: &mut Child) {
+    // Safety: killpg only sends a signal; the group is the child's, created with process_group(0)
+    if unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) } == 0 {
+        return;
+    }
+    println!("Failed to kill process group {}: {}", child.id(), std::io::Error::last_os_error());
+    // At least the child itself goes
+    if let Err(e) = child.kill() {
+        println!("Failed to kill child {}: {}", child.id(), e);
+    }
 }
 
-fn spawn_child_process(task: WasmJobRequest) {
+/// Everything left in a pipe of the child
+fn read_pipe(pipe: Option<impl Read>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut buffer);
+    }
+    buffer
+}
+
+/// Wait for the child while collecting its output, killing it once `timeout` has passed
+/// or `cancelled` says so
+fn wait_for_child(mut child: Child, timeout: Duration, cancelled: &dyn Fn() -> bool) -> std::io::Result<(Output, Option<Termination>)> {
+    let started = Instant::now();
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    std::thread::scope(|scope| -> std::io::Result<(Output, Option<Termination>)> {
+        let stdout = scope.spawn(|| read_pipe(stdout));
+        let stderr = scope.spawn(|| read_pipe(stderr));
+        let mut termination = None;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if termination.is_none() {
+                if started.elapsed() >= timeout {
+                    termination = Some(Termination::TimedOut);
+                } else if cancelled() {
+                    termination = Some(Termination::Cancelled);
+                }
+                if termination.is_some() {
+                    kill_process_group(&mut child);
+                }
+            }
+            std::thread::sleep(CHILD_POLL_INTERVAL);
+        };
+        let output = Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        };
+        Ok((output, termination))
+    })
+}
+
+/// Estimate the task in the parent, flagging a precompiled artifact this engine can't load
+fn estimate_task(task: &WasmJobRequest) -> Result<EstimationReport, String> {
+    let cwasm_file: String = "wasm-modules/".to_string() + &task.cwasm_file;
+    let wasm_file: String = "wasm-modules/".to_string() + &task.binary_name;
+    // The payload is part of the estimate: a large matrix or an image batch needs far more
+    let payload = decode_payload(task)?;
+    let mut report = build_memory_info_with_payload(&cwasm_file, &wasm_file, &payload, Some(&task.model_folder_name))
+        .map_err(|e| format!("rejected, no estimate: {}", e))?;
+    // Only a job with its own timeout runs with epoch interruption, which the artifact must match
+    if let Err(e) = check_precompiled_compatibility(&cwasm_file, task.timeout_ms.is_some()) {
+        report.warnings.push(format!("{} is incompatible with this engine: {}", cwasm_file, e));
+    }
+    Ok(report)
+}
+
+/// Run the task in a child process, inside a cgroup of its own limited to `memory_max_bytes` when
+/// the host lets us create one. The child runs on `report` and doesn't estimate again. The child reports over a Unix socket; every message is handed
+/// to `on_message` as it arrives. The child is killed past its timeout or once `cancelled` says so.
+fn spawn_child_process(
+    task: WasmJobRequest,
+    report: EstimationReport,
+    memory_max_bytes: Option<u64>,
+    on_message: &(dyn Fn(&ChildMessage) + Sync),
+    cancelled: &dyn Fn() -> bool,
+) -> ChildOutcome {
     let current_pid = std::process::id() as usize;
     println!("Parent pid {}: spawning child process for task {}", current_pid, task.task_id);
 
-    let task_file = format!("/tmp/wasm_task_{}.json", task.task_id);
-    let task_json = serde_json::to_string(&task).unwrap();
-    std::fs::write(&task_file, task_json).expect("Failed to write task");
-    
-    let mut child = Command::new(std::env::current_exe().unwrap())
-        .arg("child")
-        .arg(&task_file) // Only pass the task file path
-        .env("OMP_NUM_THREADS", "1")
-        .env("MKL_NUM_THREADS", "1")
-        .env("NUMEXPR_NUM_THREADS", "1")
-        .env("OPENBLAS_NUM_THREADS", "1")
-        .env("BLIS_NUM_THREADS", "1")
-        .env("VECLIB_MAXIMUM_THREADS", "1")
-        .env("NUMBA_NUM_THREADS", "1")
-        .env("ORT_DISABLE_PARALLELISM", "1")
-        .env("ORT_NUM_THREADS", "1")
-        .env("ORT_EXECUTION_PROVIDER", "CPUExecutionProvider")
-        .env("MKL_DYNAMIC", "FALSE")
-        .env("OMP_DYNAMIC", "FALSE")
-        .env("OPENBLAS_DYNAMIC", "FALSE")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .expect("Failed to spawn child");
-    
-    let output = child.wait_with_output().expect("Failed to wait for child");
+    // With `timeout_ms` the child traps the guest at the timeout and the parent only kills it if that
+    // doesn't end it; the server default is enforced by the kill alone, so the engine runs without
+    // epoch interruption and stays compatible with artifacts compiled without it
+    let timeout_ms = task.timeout_ms.unwrap_or_else(default_job_timeout_ms);
+    let failed = |error: String| ChildOutcome { monitor: None, result: None, error: Some(error), termination: None };
+    let sequence = CHILD_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let task_file = format!("/tmp/wasm_task_{}_{}_{}.json", current_pid, task.task_id, sequence);
+    let child_task = ChildTask { task, report };
+    let written = serde_json::to_string(&child_task)
+        .map_err(|e| e.to_string())
+        .and_then(|task_json| std::fs::write(&task_file, task_json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        return failed(format!("failed to write {}: {}", task_file, e));
+    }
+    let task = child_task.task;
+    let listener = match ChildListener::bind(format!("/tmp/wasm_job_{}_{}.sock", current_pid, sequence)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = std::fs::remove_file(&task_file);
+            return failed(format!("failed to listen for the child: {}", e));
+        }
+    };
+
+    // Without a cgroup the job still runs, measured by the child's own RSS sampler only
+    let cgroup = match JobCgroup::from_env(&format!("job-{}-{}", current_pid, sequence), memory_max_bytes) {
+        Ok(cgroup) => Some(cgroup),
+        Err(e) => {
+            println!("Task {}: running without a cgroup: {}", task.task_id, e);
+            None
+        }
+    };
+
+    let child_command = || {
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command
+            .arg("child")
+            .arg(&task_file) // Only pass the task file path
+            .env(IPC_SOCKET_ENV, listener.path())
+            .env("OMP_NUM_THREADS", "1")
+            .env("MKL_NUM_THREADS", "1")
+            .env("NUMEXPR_NUM_THREADS", "1")
+            .env("OPENBLAS_NUM_THREADS", "1")
+            .env("BLIS_NUM_THREADS", "1")
+            .env("VECLIB_MAXIMUM_THREADS", "1")
+            .env("NUMBA_NUM_THREADS", "1")
+            .env("ORT_DISABLE_PARALLELISM", "1")
+            .env("ORT_NUM_THREADS", "1")
+            .env("ORT_EXECUTION_PROVIDER", "CPUExecutionProvider")
+            .env("MKL_DYNAMIC", "FALSE")
+            .env("OMP_DYNAMIC", "FALSE")
+            .env("OPENBLAS_DYNAMIC", "FALSE")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        command
+    };
+    // The child joins its cgroup before exec, so the parent knows whether it did; if it can't, it runs without one
+    let mut joined_cgroup = false;
+    // Messages are read while the child runs; its stdout and stderr are only logs
+    let (messages, output) = std::thread::scope(|scope| {
+        let receiver = scope.spawn(|| listener.receive(on_message));
+        let spawned = match &cgroup {
+            Some(cgroup) => {
+                let mut command = child_command();
+                match cgroup.join_on_spawn(&mut command).and_then(|()| command.spawn()) {
+                    Ok(child) => {
+                        println!("Task {}: child {} joined cgroup {}", task.task_id, child.id(), cgroup.path().display());
+                        joined_cgroup = true;
+                        Ok(child)
+                    }
+                    Err(e) => {
+                        println!("Task {}: child couldn't join cgroup {}, running without it: {}", task.task_id, cgroup.path().display(), e);
+                        child_command().spawn()
+                    }
+                }
+            }
+            None => child_command().spawn(),
+        };
+        let output = spawned.and_then(|child| wait_for_child(child, Duration::from_millis(timeout_ms) + CHILD_KILL_GRACE, cancelled));
+        listener.wake();
+        (receiver.join().unwrap_or_default(), output)
+    });
     
     // Clean up temp file
     let _ = std::fs::remove_file(&task_file);
-    
-    if output.status.success() {
-        println!("Child output: {}", String::from_utf8_lossy(&output.stdout));
+    // A cgroup the child never joined accounted none of its memory
+    let cgroup_usage = cgroup.map(JobCgroup::finish).filter(|_| joined_cgroup);
+
+    let (output, killed) = match output {
+        Ok(output) => output,
+        Err(e) => return failed(format!("failed to run the child process: {}", e)),
+    };
+    let (mut monitor, mut result, mut accuracy, mut child_error) = (None, None, None, None);
+    for message in messages {
+        match message {
+            ChildMessage::Memory { monitor: measured } => monitor = Some(measured),
+            ChildMessage::Accuracy { record } => accuracy = Some(record),
+            ChildMessage::Result { result: guest_result } => result = Some(guest_result),
+            ChildMessage::Error { message } => child_error = Some(message),
+            ChildMessage::Progress { .. } => {}
+        }
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    println!("Child output: {}", stdout);
+    if !stderr.trim().is_empty() {
+        println!("Child stderr: {}", stderr);
+    }
+    let oom_killed = cgroup_usage.as_ref().is_some_and(|usage| usage.oom_killed());
+    let termination = if oom_killed {
+        Some(Termination::OomKilled)
+    } else if killed.is_some() {
+        killed
+    } else if result.as_ref().is_some_and(|result: &GuestResult| result.timed_out) {
+        Some(Termination::TimedOut)
     } else {
-        println!("Child error: {}", String::from_utf8_lossy(&output.stderr));
-        println!("Child stdout: {}", String::from_utf8_lossy(&output.stdout));
+        None
+    };
+    let error = match (&cgroup_usage, termination) {
+        (Some(usage), Some(Termination::OomKilled)) => Some(format!(
+            "child was OOM-killed at memory.max {} bytes ({} oom kills)",
+            usage.memory_max_bytes.map_or("max".to_string(), |bytes| bytes.to_string()),
+            usage.oom_kill_events
+        )),
+        (_, Some(Termination::TimedOut)) => Some(format!("timed out after {} ms", timeout_ms)),
+        (_, Some(Termination::Cancelled)) => Some("cancelled while running".to_string()),
+        _ if child_error.is_some() => child_error,
+        _ if !output.status.success() => Some(format!("child exited with {}: {}", output.status, stderr.trim())),
+        _ => None,
+    };
+
+    if let Some(usage) = &cgroup_usage {
+        println!(
+            "📦 Task {} cgroup: peak {}, memory.max {}, {} oom events, {} oom kills",
+            task.task_id,
+            usage
+                .peak_bytes
+                .map(|bytes| format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0)))
+                .unwrap_or_else(|| "n/a".to_string()),
+            usage
+                .memory_max_bytes
+                .map(|bytes| format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0)))
+                .unwrap_or_else(|| "max".to_string()),
+            usage.oom_events,
+            usage.oom_kill_events
+        );
+        if let Some(monitor) = monitor.as_mut() {
+            monitor.cgroup = Some(usage.clone());
+        }
+    }
+    match &monitor {
+        Some(monitor) => println!(
+            "📈 Task {} measured memory: initial {:.2} MB, peak {:.2} MB (kernel high water mark {}), final {:.2} MB, {} samples over {} ms",
+            task.task_id,
+            monitor.initial_memory_bytes as f64 / (1024.0 * 1024.0),
+            monitor.peak_memory_bytes as f64 / (1024.0 * 1024.0),
+            monitor
+                .kernel_peak_memory_bytes
+                .map(|bytes| format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0)))
+                .unwrap_or_else(|| "n/a".to_string()),
+            monitor.final_memory_bytes as f64 / (1024.0 * 1024.0),
+            monitor.samples.len(),
+            monitor.execution_time_ms
+        ),
+        None => println!("Task {}: child returned no memory measurements", task.task_id),
     }
+
+    if let Some(mut record) = accuracy {
+        // A peak of 0 means the kernel accounted nothing, not that the job used no memory
+        if let Some(peak) = cgroup_usage.as_ref().and_then(|usage| usage.peak_bytes).filter(|&peak| peak > 0) {
+            record = record.with_cgroup_peak(peak);
+        }
+        record_accuracy(&record);
+    }
+    ChildOutcome { monitor, result, error, termination }
 }
 
-async fn run_child(task: WasmJobRequest) {
-    println!("Child: running wasm job component...");
+/// Add the record to the history of its binary and print how the estimate does so far
+fn record_accuracy(record: &AccuracyRecord) {
+    println!(
+        "🎯 Task {} estimate: {:.2} MB predicted vs {:.2} MB measured ({:+.1}%, {})",
+        record.task_id,
+        record.estimated_peak_memory_bytes as f64 / (1024.0 * 1024.0),
+        record.measured_peak_bytes() as f64 / (1024.0 * 1024.0),
+        record.relative_error * 100.0,
+        record.verdict.as_str()
+    );
+    let history = AccuracyHistory::from_env();
+    if let Err(e) = history.append(record) {
+        println!("Error recording estimation accuracy: {}", e);
+        return;
+    }
+    match history.records(&record.binary_name).map(|records| AccuracySummary::from_records(&records)) {
+        Ok(Some(summary)) => println!(
+            "🎯 {} ({}): {} jobs, mean error {:+.1}% (|{:.1}|%), {} over, {} under, {} accurate",
+            summary.binary_name,
+            summary.workload,
+            summary.jobs,
+            summary.mean_relative_error * 100.0,
+            summary.mean_absolute_relative_error * 100.0,
+            summary.overestimates,
+            summary.underestimates,
+            summary.accurate
+        ),
+        Ok(None) => {}
+        Err(e) => println!("Error reading estimation accuracy history: {}", e),
+    }
+}
 
+fn output_report(report: &EstimationReport, json: bool) {
+    if json {
+        match report.to_json() {
+            Ok(json) => println!("{}", json),
+            Err(e) => println!("Error serializing estimation report: {}", e),
+        }
+    } else {
+        print_estimation_report(report);
+    }
+}
+
+fn print_guest_memory(guest_memory: &GuestMemoryPeak, report: &EstimationReport) {
+    let comparison = guest_memory.compare(&report.estimate);
+    println!(
+        "🧮 Guest linear memory: peak {:.2} MB ({} memory.grow requests, {} denied) vs declared {:.2} MB, estimated peak {:.2} MB{}",
+        comparison.measured_linear_memory_bytes as f64 / (1024.0 * 1024.0),
+        guest_memory.memory_grow_requests,
+        guest_memory.denied_requests,
+        comparison.estimated_linear_memory_bytes as f64 / (1024.0 * 1024.0),
+        comparison.estimated_peak_bytes as f64 / (1024.0 * 1024.0),
+        if comparison.within_estimated_peak { "" } else { " ⚠️ above the estimated peak" }
+    );
+}
+
+/// The payload as the guest will receive it
+fn decode_payload(task: &WasmJobRequest) -> Result<String, String> {
     // Handle compressed payload
-    let payload = if task.payload_compressed {
+    if task.payload_compressed {
         // Decompress the payload
-        let compressed_bytes = general_purpose::STANDARD.decode(&task.payload).expect("Failed to decode base64");
+        let compressed_bytes = general_purpose::STANDARD
+            .decode(&task.payload)
+            .map_err(|e| format!("failed to decode the base64 payload: {}", e))?;
         let mut decoder = flate2::read::GzDecoder::new(&compressed_bytes[..]);
         let mut decompressed = String::new();
-        std::io::Read::read_to_string(&mut decoder, &mut decompressed).expect("Failed to decompress");
-        decompressed
+        decoder.read_to_string(&mut decompressed).map_err(|e| format!("failed to decompress the payload: {}", e))?;
+        Ok(decompressed)
     } else {
         // Payload is already uncompressed
-        task.payload
-    };
+        Ok(task.payload.clone())
+    }
+}
+
+/// Tell the parent why the child gives up on its task, then exit
+fn reject_child_task(channel: &ChildChannel, message: String) -> ! {
+    eprintln!("Child error: {}", message);
+    channel.send(&ChildMessage::Error { message });
+    std::process::exit(1);
+}
+
+async fn run_child(task: WasmJobRequest, payload: String, memory_limit: Option<MemoryLimit>, channel: &ChildChannel) -> MemoryMonitor {
+    println!("Child: running wasm job component...");
+    let sample_interval = Duration::from_millis(task.sample_interval_ms.unwrap_or(DEFAULT_SAMPLE_INTERVAL_MS).max(1));
+
+    // Sample RSS in the background for as long as the job runs
+    let sampler = MemorySampler::start(sample_interval);
+    channel.progress(ChildStage::Running);
 
     // Run WASM component with error handling
-    match run_wasm_job_component(
+    let (result, guest_memory, guest_output) = run_wasm_job_component(
         task.task_id,
         "wasm-modules/".to_string() + &task.binary_name,
         task.func_name,
         payload,
         task.model_folder_name,
-    ).await {
-        Ok(result) => println!("Child result: {:?}", result),
-        Err(e) => println!("Child error: {:?}", e),
-    }
+        memory_limit,
+        task.timeout_ms.map(Duration::from_millis),
+    ).await;
+    let mut monitor = sampler.stop();
+    monitor.guest_memory = Some(guest_memory);
 
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(statm_content) = std::fs::read_to_string("/proc/self/statm") {
-            let parts: Vec<&str> = statm_content.trim().split_whitespace().collect();
-            if parts.len() >= 2 {
-                let rss_pages: u64 = parts[1].parse().unwrap_or(0);
-                let rss_kb = rss_pages * 4; // Each page is 4KB on Linux
-                println!("Child: /proc/self/statm RSS = {} KB ({} pages)", rss_kb, rss_pages);
+    let guest_result = match result {
+        Ok(result) => {
+            println!("Child result: {:?}", result);
+            GuestResult {
+                success: true,
+                values: result.iter().map(val_to_json).collect(),
+                error: None,
+                timed_out: false,
+                stdout: guest_output.stdout,
+                stderr: guest_output.stderr,
             }
         }
-    }
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(status_content) = std::fs::read_to_string("/proc/self/status") {
-            for line in status_content.lines() {
-                if line.starts_with("VmRSS:") || line.starts_with("VmSize:") || line.starts_with("VmPeak:") {
-                    println!("Child: {}", line.trim());
+        Err(e) => {
+            match estimate_exceeded(&e) {
+                Some(exceeded) => println!("Child error: task {} stopped: {}", task.task_id, exceeded),
+                None => println!("Child error: {:?}", e),
+            }
+            GuestResult {
+                success: false,
+                values: Vec::new(),
+                error: Some(format!("{:#}", e)),
+                timed_out: timed_out(&e),
+                stdout: guest_output.stdout,
+                stderr: guest_output.stderr,
+            }
+        }
+    };
+    channel.send(&ChildMessage::Result { result: guest_result });
+    println!("Child: peak RSS {} bytes over {} samples", monitor.peak_memory_bytes, monitor.samples.len());
+
+    monitor
+}
+
+/// Run every component of wasm-modules/ that has a precompiled artifact `runs` times, then
+/// fit the buffer rules of the active profile to the measured peaks and write the profile
+fn run_calibration(runs: usize, output: &str) {
+    let jobs: std::collections::HashMap<String, CalibrationJob> = match std::fs::read_to_string(CALIBRATION_JOBS_FILE) {
+        Ok(json) => match serde_json::from_str(&json) {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                eprintln!("Error parsing {}: {}", CALIBRATION_JOBS_FILE, e);
+                std::process::exit(1);
+            }
+        },
+        Err(_) => std::collections::HashMap::new(),
+    };
+    let mut binaries: Vec<String> = std::fs::read_dir("wasm-modules")
+        .expect("Failed to read wasm-modules")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".wasm"))
+        .filter(|name| std::path::Path::new("wasm-modules").join(name.replace(".wasm", ".cwasm")).exists())
+        .collect();
+    binaries.sort();
+
+    println!("🔧 Calibrating profile \"{}\" with {} modules × {} runs", active_rules().profile, binaries.len(), runs);
+    let mut task_id = 0;
+    for binary_name in binaries.iter() {
+        let job = jobs.get(binary_name).cloned().unwrap_or_default();
+        for _ in 0..runs {
+            task_id += 1;
+            let task = WasmJobRequest {
+                binary_name: binary_name.clone(),
+                func_name: job.func_name.clone().unwrap_or_else(|| "run".to_string()),
+                payload: job.payload.clone().unwrap_or_default(),
+                payload_compressed: false,
+                task_id,
+                model_folder_name: job.model_folder_name.clone().unwrap_or_else(|| "models".to_string()),
+                cwasm_file: binary_name.replace(".wasm", ".cwasm"),
+                sample_interval_ms: None,
+                enforce_estimate: false,
+                safety_factor: None,
+                timeout_ms: None,
+            };
+            match estimate_task(&task) {
+                // The child only instantiates components
+                Ok(report) if !report.features.is_component => {
+                    println!("Task {} ({}): not a component, skipped", task_id, binary_name);
+                    break;
+                }
+                Ok(report) => {
+                    spawn_child_process(task, report, None, &|_| {}, &|| false);
                 }
+                Err(e) => println!("Task {} ({}): {}", task_id, binary_name, e),
             }
         }
     }
 
+    // Every recorded job of these binaries counts, not only the runs above
+    let history = AccuracyHistory::from_env();
+    let mut records = Vec::new();
+    for binary_name in binaries.iter() {
+        match history.records(binary_name) {
+            Ok(binary_records) => records.extend(binary_records),
+            Err(e) => println!("Error reading estimation accuracy history: {}", e),
+        }
+    }
+    let device = std::fs::read_to_string("/etc/hostname")
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_else(|_| std::env::consts::ARCH.to_string());
+    let calibration = match calibrate(active_rules(), &records, &format!("calibrated-{}", device)) {
+        Ok(calibration) => calibration,
+        Err(e) => {
+            eprintln!("Error calibrating: {}", e);
+            std::process::exit(1);
+        }
+    };
+    for fitted in calibration.fitted.iter() {
+        println!(
+            "   • {}: {} samples from {} → base {:.2} MB{}",
+            fitted.rule,
+            fitted.samples,
+            fitted.binaries.join(", "),
+            fitted.fitted.base_bytes as f64 / (1024.0 * 1024.0),
+            fitted
+                .fitted
+                .per_unit
+                .iter()
+                .map(|(feature, bytes_per_unit)| format!(" + {:.3} × {:?}", bytes_per_unit, feature))
+                .collect::<String>()
+        );
+    }
+    if calibration.skipped_records > 0 {
+        println!("   • {} records without a usable buffer rule or feature vector were skipped", calibration.skipped_records);
+    }
+    if let Err(e) = calibration.rules.save(output) {
+        eprintln!("Error writing calibrated profile: {}", e);
+        std::process::exit(1);
+    }
+    println!("✅ Wrote profile \"{}\" to {}; use it with MEMORY_ESTIMATOR_RULES={}", calibration.rules.profile, output, output);
 }
 
-async fn run_task(task: WasmJobRequest){
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 && args[1] == "child" {
-        run_child(task).await;
-    } else {
-        spawn_child_process(task);
+/// Take jobs off the queue in order, estimate each in the parent and start its child once the
+/// scheduler admits the estimated peak; several children run at once while their estimates fit
+fn run_job_worker(queue: Arc<JobQueue<WasmJobRequest>>, scheduler: Arc<AdmissionScheduler>) {
+    let baseline = Arc::new(ChildBaseline::from_env());
+    loop {
+        let (job_id, task) = queue.next();
+        println!("📥 Job {}: estimating task {} ({} waiting)", job_id, task.task_id, queue.depth());
+
+        let report = match estimate_task(&task) {
+            Ok(report) => report,
+            Err(e) => {
+                println!("Job {}: {}", job_id, e);
+                queue.fail(job_id, e);
+                continue;
+            }
+        };
+        let estimated_peak = report.estimate.peak_bytes;
+        queue.estimated(job_id, report.estimate.clone());
+        queue.warn(job_id, &report.warnings);
+        if queue.cancel_requested(job_id) {
+            queue.finish(job_id, None, None, None, Some(Termination::Cancelled));
+            continue;
+        }
+
+        // Blocks the queue until the job fits: later jobs don't overtake it
+        match scheduler.admit(estimated_peak, &|| queue.cancel_requested(job_id)) {
+            Ok(()) => {}
+            Err(JobError::AdmissionCancelled) => {
+                queue.finish(job_id, None, None, None, Some(Termination::Cancelled));
+                continue;
+            }
+            Err(e) => {
+                println!("Job {}: rejected, {}", job_id, e);
+                queue.fail(job_id, format!("rejected, {}", e));
+                continue;
+            }
+        }
+        // Cancelled just as it was admitted
+        if queue.cancel_requested(job_id) {
+            scheduler.release(estimated_peak);
+            queue.finish(job_id, None, None, None, Some(Termination::Cancelled));
+            continue;
+        }
+        let stats = scheduler.stats();
+        println!(
+            "🚦 Job {}: admitted {:.2} MB, {} running with {:.2} MB committed of {:.2} MB available",
+            job_id,
+            estimated_peak as f64 / (1024.0 * 1024.0),
+            stats.running_jobs,
+            stats.committed_bytes as f64 / (1024.0 * 1024.0),
+            stats.available_bytes as f64 / (1024.0 * 1024.0)
+        );
+        queue.start(job_id);
+
+        // The cgroup leaves the same headroom over the estimate as an enforced store limit, and room for the child itself
+        let memory_max = job_memory_max(estimated_peak, task.safety_factor.unwrap_or(DEFAULT_SAFETY_FACTOR), baseline.bytes());
+        let (job_queue, job_scheduler, job_baseline) = (queue.clone(), scheduler.clone(), baseline.clone());
+        std::thread::spawn(move || {
+            let on_message = |message: &ChildMessage| match message {
+                ChildMessage::Progress { stage, .. } => job_queue.progress(job_id, *stage),
+                _ => {}
+            };
+            let cancelled = || job_queue.cancel_requested(job_id);
+            let outcome = spawn_child_process(task, report, Some(memory_max), &on_message, &cancelled);
+            // What the child resided in besides the linear memory of its guest
+            if let Some(monitor) = &outcome.monitor {
+                if let (Some(resident_peak), Some(guest_memory)) = (monitor.resident_peak_bytes(), &monitor.guest_memory) {
+                    job_baseline.observe(resident_peak.saturating_sub(guest_memory.peak_linear_memory_bytes));
+                }
+            }
+            job_scheduler.release(estimated_peak);
+            job_queue.finish(job_id, outcome.monitor, outcome.result, outcome.error, outcome.termination);
+            if let Some(job) = job_queue.get(job_id) {
+                println!("📤 Job {}: {}", job_id, job.state.as_str());
+            }
+        });
+    }
+}
+
+/// Queue the job and answer with its ID right away; poll GET /jobs/{id} for the outcome
+async fn handle_submit_task(task: web::Json<WasmJobRequest>, queue: web::Data<JobQueue<WasmJobRequest>>)->impl Responder{
+    let task = task.into_inner();
+    let job_id = queue.submit(task.task_id, &task.binary_name, &task.func_name, task);
+    HttpResponse::Accepted().json(serde_json::json!({
+        "job_id": job_id,
+        "state": "queued",
+        "status_url": format!("/jobs/{}", job_id),
+    }))
+}
+
+async fn handle_get_job(job_id: web::Path<u64>, queue: web::Data<JobQueue<WasmJobRequest>>)->impl Responder{
+    match queue.get(job_id.into_inner()) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().body("No such job"),
+    }
+}
+
+/// Cancel a queued job, or kill the child of a running one
+async fn handle_cancel_job(job_id: web::Path<u64>, queue: web::Data<JobQueue<WasmJobRequest>>)->impl Responder{
+    let job_id = job_id.into_inner();
+    match queue.cancel(job_id) {
+        Some(Cancellation::Cancelled) => HttpResponse::Ok().json(serde_json::json!({ "job_id": job_id, "state": "cancelled" })),
+        Some(Cancellation::Requested) => {
+            HttpResponse::Accepted().json(serde_json::json!({ "job_id": job_id, "state": "cancelling" }))
+        }
+        Some(Cancellation::AlreadyFinished(state)) => HttpResponse::Conflict().json(serde_json::json!({
+            "job_id": job_id,
+            "state": state.as_str(),
+            "error": "the job already finished",
+        })),
+        None => HttpResponse::NotFound().body("No such job"),
     }
+}
 
+async fn handle_list_jobs(queue: web::Data<JobQueue<WasmJobRequest>>)->impl Responder{
+    HttpResponse::Ok().json(queue.list())
 }
-async fn handle_submit_task(task: web::Json<WasmJobRequest>)->impl Responder{
-    run_task(task.into_inner()).await;
-    HttpResponse::Ok().body("Task done")
+
+async fn handle_scheduler(queue: web::Data<JobQueue<WasmJobRequest>>, scheduler: web::Data<AdmissionScheduler>)->impl Responder{
+    HttpResponse::Ok().json(serde_json::json!({
+        "queue_depth": queue.depth(),
+        "admission": scheduler.stats(),
+    }))
 }
 
 async fn handle_plot_memory()->impl Responder{
@@ -151,47 +735,141 @@ async fn main() {
     std::env::set_var("MKL_DYNAMIC", "FALSE");
     std::env::set_var("OMP_DYNAMIC", "FALSE");
     std::env::set_var("OPENBLAS_DYNAMIC", "FALSE");
-    
+
     let args: Vec<String> = env::args().collect();
+    // JSON output keeps stdout machine readable: the report is the only thing printed before the job runs
+    let json_output = args.iter().any(|arg| arg == "--json")
+        || env::var(OUTPUT_ENV).is_ok_and(|output| output == "json");
+
+    // Estimation rules are read once per process; the child inherits MEMORY_ESTIMATOR_RULES.
+    // A broken rules file would make every estimate wrong, so refuse to start.
+    match load_rules_from_env() {
+        Ok(rules) if !json_output => println!("📐 Estimation rules: profile \"{}\"", rules.profile),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Error loading estimation rules: {}", e);
+            std::process::exit(1);
+        }
+    }
+    
     // If this is a child process, run the WASM task and exit
     if args.len() > 1 && args[1] == "child" {
         // Parse command line arguments for child process
         if args.len() >= 3 {
+            let started = Instant::now();
+            let channel = ChildChannel::from_env();
             let task_file = &args[2];
-            let task_json = std::fs::read_to_string(task_file).expect("Failed to read task file");
-            let task: WasmJobRequest = serde_json::from_str(&task_json).expect("Failed to parse task JSON");
-            
-            // Construct full file paths
-            let cwasm_file: String = "wasm-modules/".to_string() + &task.cwasm_file;
-            let wat_file: String = "wasm-modules/".to_string() + &task.wat_file;
-            let wasm_file: String = "wasm-modules/".to_string() + &task.binary_name;
-            // Convert WASM to WAT only if .wat file doesn't exist
-            if !std::path::Path::new(&wat_file).exists() {
-                match convert_wasm_to_wat(&wasm_file, &wat_file) {
-                    Ok(_) => println!("Successfully converted {} to {}", cwasm_file, wat_file),
-                    Err(e) => println!("Error converting file: {}", e),
-                }
-            }
+            // The parent estimated the task already; the child only runs it
+            let ChildTask { mut task, report } = match std::fs::read_to_string(task_file)
+                .map_err(|e| format!("failed to read task file {}: {}", task_file, e))
+                .and_then(|task_json| serde_json::from_str(&task_json).map_err(|e| format!("failed to parse task JSON: {}", e)))
+            {
+                Ok(child_task) => child_task,
+                Err(e) => reject_child_task(&channel, e),
+            };
+            channel.progress(ChildStage::Started);
 
-            let memory_info: MemoryInfoEstimator = build_memory_info(&cwasm_file, &wat_file);
-            println!("Estimated memory info: {}", memory_info);
-            print_memory_analysis_simple(&memory_info);
+            let payload = match decode_payload(&task) {
+                Ok(payload) => payload,
+                Err(e) => reject_child_task(&channel, format!("rejecting task {}: {}", task.task_id, e)),
+            };
+            output_report(&report, json_output);
+
+            let memory_limit = task
+                .enforce_estimate
+                .then(|| MemoryLimit::from_report(&report, task.safety_factor.unwrap_or(DEFAULT_SAFETY_FACTOR)));
+            if let Some(limit) = &memory_limit {
+                println!(
+                    "🔒 Enforcing the estimate: {:.2} MB of linear memory ({:.2} MB estimated linear memory × {})",
+                    limit.linear_memory_bytes as f64 / (1024.0 * 1024.0),
+                    limit.estimated_linear_memory_bytes as f64 / (1024.0 * 1024.0),
+                    limit.safety_factor
+                );
+            }
             
-            run_child(task).await;
+            // The timeout counts from the parent's spawn, loading the task included, so the guest traps
+            // before the parent loses patience and kills the child
+            task.timeout_ms = task.timeout_ms.map(|timeout_ms| timeout_ms.saturating_sub(started.elapsed().as_millis() as u64).max(1));
+            let (binary_name, task_id) = (task.binary_name.clone(), task.task_id);
+            let monitor = run_child(task, payload, memory_limit, &channel).await;
+            if let Some(guest_memory) = &monitor.guest_memory {
+                print_guest_memory(guest_memory, &report);
+            }
+            if let Some(record) = AccuracyRecord::new(&binary_name, task_id, &report, &monitor) {
+                channel.send(&ChildMessage::Accuracy { record });
+            }
+            channel.send(&ChildMessage::Memory { monitor });
+            channel.progress(ChildStage::Finished);
         } else {
             println!("Error: Not enough arguments for child process");
         }
         return; // Exit child process - don't start HTTP server
     }
+    // Estimate only: memory-estimator estimate <wasm file> <cwasm file> [--payload <file>] [--json]
+    if args.len() > 1 && args[1] == "estimate" {
+        if args.len() >= 4 {
+            let payload_file = args.iter().position(|arg| arg == "--payload").and_then(|index| args.get(index + 1));
+            let report = match payload_file {
+                Some(payload_file) => match std::fs::read_to_string(payload_file) {
+                    Ok(payload) => build_memory_info_with_payload(&args[3], &args[2], &payload, None),
+                    Err(e) => {
+                        eprintln!("Error reading payload {}: {}", payload_file, e);
+                        std::process::exit(1);
+                    }
+                },
+                None => build_memory_info(&args[3], &args[2]),
+            };
+            match report {
+                Ok(report) => output_report(&report, json_output),
+                Err(e) => {
+                    eprintln!("Error estimating {}: {}", args[2], e);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            println!("Usage: {} estimate <wasm file> <cwasm file> [--payload <file>] [--json]", args[0]);
+        }
+        return;
+    }
+    // Fit the buffer rules to this device: memory-estimator calibrate [runs] [output file]
+    if args.len() > 1 && args[1] == "calibrate" {
+        let runs = match args.get(2).map(|runs| runs.parse::<usize>()) {
+            Some(Ok(runs)) => runs,
+            Some(Err(_)) => {
+                println!("Usage: {} calibrate [runs] [output file]", args[0]);
+                return;
+            }
+            None => DEFAULT_CALIBRATION_RUNS,
+        };
+        run_calibration(runs, args.get(3).map(String::as_str).unwrap_or(DEFAULT_CALIBRATION_OUTPUT));
+        return;
+    }
     // Only start HTTP server if this is the parent process
     println!("🚀 HTTP Server starting on http://[::]:8082");
     println!("📡 Available endpoints:");
-    println!("   POST /submit_task - Submit a WASM task");
+    println!("   POST /submit_task - Queue a WASM task, returns its job ID");
+    println!("   GET  /jobs        - List submitted jobs");
+    println!("   GET  /jobs/{{id}}   - State, estimate, measured memory and result of a job");
+    println!("   DELETE /jobs/{{id}} - Cancel a queued or running job");
+    println!("   GET  /scheduler   - Queue depth, committed memory and rejected jobs");
     println!("   GET  /plot_memory - Get memory plots");
 
+    let queue = web::Data::new(JobQueue::<WasmJobRequest>::from_env());
+    let scheduler = web::Data::new(AdmissionScheduler::from_env());
+    println!(
+        "🚦 Admitting jobs while their estimated peaks fit, keeping {:.2} MB in reserve",
+        scheduler.stats().reserve_bytes as f64 / (1024.0 * 1024.0)
+    );
+    let (worker_queue, worker_scheduler) = (queue.clone().into_inner(), scheduler.clone().into_inner());
+    std::thread::spawn(move || run_job_worker(worker_queue, worker_scheduler));
+
     let server = HttpServer::new(move || {
-        let mut app = App::new() ;
+        let mut app = App::new().app_data(queue.clone()).app_data(scheduler.clone());
         app = app.route("/submit_task", web::post().to(handle_submit_task));
+        app = app.route("/jobs", web::get().to(handle_list_jobs));
+        app = app.route("/jobs/{id}", web::get().to(handle_get_job));
+        app = app.route("/jobs/{id}", web::delete().to(handle_cancel_job));
+        app = app.route("/scheduler", web::get().to(handle_scheduler));
         app = app.route("/plot_memory", web::get().to(handle_plot_memory));
         app
     })
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use crate::call_graph::CallGraph;
+
+/// Allocators recognized from function names and exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Allocator {
+    /// dlmalloc, from wasi-libc or the Rust `dlmalloc` crate
+    Dlmalloc,
+    WeeAlloc,
+    /// Rust's global allocator shims (`__rust_alloc`, `__rdl_alloc`, `__rg_alloc`)
+    RustDefault,
+    /// Emscripten's compact allocator
+    Emmalloc,
+}
+
+impl Allocator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Allocator::Dlmalloc => "dlmalloc",
+            Allocator::WeeAlloc => "wee_alloc",
+            Allocator::RustDefault => "Rust global allocator",
+            Allocator::Emmalloc => "emmalloc",
+        }
+    }
+
+    /// Match a raw (possibly mangled) symbol against the allocator name patterns
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        let symbol = symbol.to_ascii_lowercase();
+        if symbol.contains("wee_alloc") {
+            Some(Allocator::WeeAlloc)
+        } else if symbol.contains("emmalloc") {
+            Some(Allocator::Emmalloc)
+        } else if ["dlmalloc", "dlcalloc", "dlrealloc", "dlfree", "dlmemalign"].iter().any(|name| symbol.contains(name)) {
+            Some(Allocator::Dlmalloc)
+        } else if ["__rust_alloc", "__rust_realloc", "__rdl_alloc", "__rdl_realloc", "__rg_alloc", "__rg_realloc"]
+            .iter()
+            .any(|name| symbol.contains(name))
+            && !symbol.contains("alloc_error")
+        {
+            Some(Allocator::RustDefault)
+        } else {
+            None
+        }
+    }
+}
+
+/// Exported entry points that allocate on behalf of the host, whatever allocator backs them
+const ALLOCATOR_EXPORTS: &[&str] = &["malloc", "calloc", "realloc", "aligned_alloc", "cabi_realloc"];
+
+/// How a module's linear memory grows at run time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrowthBehavior {
+    /// Every `memory.grow` is reached from an allocator: growth follows heap usage
+    AllocatorDriven,
+    /// Some `memory.grow` is issued outside an allocator (or by one we don't recognize)
+    Explicit,
+    /// No `memory.grow` at all: the memory stays at its initial size
+    Absent,
+}
+
+impl GrowthBehavior {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrowthBehavior::AllocatorDriven => "allocator-driven",
+            GrowthBehavior::Explicit => "explicit",
+            GrowthBehavior::Absent => "absent",
+        }
+    }
+}
+
+/// A function containing `memory.grow`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryGrowSite {
+    pub function_index: u32,
+    pub function_name: String,
+    /// `memory.grow` instructions in this function
+    pub count: u32,
+    /// The function is an allocator or reachable from one
+    pub allocator_driven: bool,
+}
+
+/// Dynamic growth behavior of a core module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthAnalysis {
+    pub behavior: GrowthBehavior,
+    pub grow_sites: Vec<MemoryGrowSite>,
+    pub allocators: Vec<Allocator>,
+    /// Allocator functions and exports the grow sites were traced from
+    pub allocator_entry_points: Vec<String>,
+}
+
+impl GrowthAnalysis {
+    pub fn grow_instruction_count(&self) -> u32 {
+        self.grow_sites.iter().map(|site| site.count).sum()
+    }
+}
+
+/// Find the `memory.grow` sites of a module and attribute them to its allocator.
+/// `exports` are the exported functions as (name, function index).
+pub fn analyze_growth(call_graph: &CallGraph, exports: &[(String, u32)]) -> GrowthAnalysis {
+    let mut allocators: Vec<Allocator> = Vec::new();
+    let mut entry_points: Vec<u32> = Vec::new();
+
+    for (function_index, name) in call_graph.function_names.iter() {
+        if let Some(allocator) = Allocator::from_symbol(name) {
+            if !allocators.contains(&allocator) {
+                allocators.push(allocator);
+            }
+            entry_points.push(*function_index);
+        }
+    }
+    for (name, function_index) in exports.iter() {
+        let allocator = Allocator::from_symbol(name);
+        if let Some(allocator) = allocator {
+            if !allocators.contains(&allocator) {
+                allocators.push(allocator);
+            }
+        }
+        if allocator.is_some() || ALLOCATOR_EXPORTS.contains(&name.as_str()) {
+            entry_points.push(*function_index);
+        }
+    }
+    entry_points.sort_unstable();
+    entry_points.dedup();
+
+    let reachable = call_graph.reachable_from(&entry_points);
+    let grow_sites: Vec<MemoryGrowSite> = call_graph
+        .functions
+        .iter()
+        .enumerate()
+        .filter(|(_, summary)| summary.memory_grow_count > 0)
+        .map(|(defined_index, summary)| {
+            let function_index = call_graph.imported_function_count + defined_index as u32;
+            MemoryGrowSite {
+                function_index,
+                function_name: call_graph.function_name(function_index),
+                count: summary.memory_grow_count,
+                allocator_driven: reachable.get(function_index as usize).copied().unwrap_or(false),
+            }
+        })
+        .collect();
+
+    let behavior = if grow_sites.is_empty() {
+        GrowthBehavior::Absent
+    } else if grow_sites.iter().all(|site| site.allocator_driven) {
+        GrowthBehavior::AllocatorDriven
+    } else {
+        GrowthBehavior::Explicit
+    };
+
+    GrowthAnalysis {
+        behavior,
+        grow_sites,
+        allocators,
+        allocator_entry_points: entry_points.iter().map(|&index| call_graph.function_name(index)).collect(),
+    }
+}
@@ -1,22 +1,88 @@
 use std::fs;
-use wasmprinter;
-use regex::Regex;
 use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::call_graph::StackAnalysis;
+use crate::cwasm_analyzer::{analyze_cwasm, CwasmInfo};
+use crate::estimation_accuracy::AccuracyHistory;
+use crate::estimation_report::EstimationReport;
+use crate::estimation_rules::{active_rules, Evidence, Workload};
+use crate::estimator_error::EstimatorError;
+use crate::memory_growth::GrowthAnalysis;
+use crate::memory_layout::MemoryLayout;
+use crate::onnx_model::{estimate_inference_memory, InferenceMemory};
+use crate::payload_analyzer::{analyze_payload, PayloadEstimate, PayloadFeatures};
+use crate::peak_interval::{peak_interval, PeakInterval};
+use crate::workload_signals::WorkloadSignals;
+use crate::wasm_binary_analyzer::{analyze_wasm_binary, CoreModuleInfo, CoreModuleRole, MemoryDescriptor, TableElementStats};
 
-#[derive(Debug, Clone)]
+/// Host memory per table slot (one pointer per funcref)
+const TABLE_SLOT_BYTES: u64 = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryInfoEstimator {
-    pub linear_memory_pages: u32,
+    pub linear_memory_pages: u64,
     pub linear_memory_bytes: u64,
-    pub stack_pointer_offset: u64,
+    pub maximum_memory_pages: Option<u64>,
+    pub maximum_memory_bytes: Option<u64>,
+    pub memories: Vec<MemoryDescriptor>,
+    pub stack_size_bytes: u64,
+    pub static_data_bytes: u64,
+    pub heap_start_bytes: u64,
+    pub memory_layout: MemoryLayout,
+    pub stack_analysis: Option<StackAnalysis>,
+    pub stack_usage_bytes: u64,
+    /// Part of `stack_usage_bytes` outside linear memory, the native stack of the compiled code
+    #[serde(default)]
+    pub native_stack_bytes: u64,
+    pub growth_analysis: Option<GrowthAnalysis>,
     pub function_tables: Vec<u32>,
     pub total_function_references: u32,
+    pub function_count: u32,
+    pub global_count: u32,
+    pub data_segment_count: u32,
+    pub element_segment_count: u32,
+    pub element_segments_per_table: Vec<(u32, TableElementStats)>,
+    pub active_data_bytes: u64,
+    pub passive_data_bytes: u64,
+    pub highest_data_offset: u64,
+    pub static_footprint_bytes: u64,
+    pub import_count: u32,
+    pub imported_memory_count: u32,
+    pub imported_table_count: u32,
+    pub export_count: u32,
+    pub exported_functions: Vec<String>,
+    /// f32/f64 memory accesses inside nested loops of the user's modules
+    #[serde(default)]
+    pub nested_loop_float_accesses: u32,
+    pub is_component: bool,
+    pub core_instance_count: u32,
+    pub core_modules: Vec<CoreModuleInfo>,
+    /// Memory the job's payload adds, when estimated with one
+    #[serde(default)]
+    pub payload: Option<PayloadEstimate>,
+    /// Host memory of the ONNX models the job loads through wasi-nn, outside linear memory
+    #[serde(default)]
+    pub inference: Option<InferenceMemory>,
     pub estimated_minimum_memory_bytes: u64,
     pub estimated_peak_memory_bytes: u64,
-    pub is_ml_workload: bool,
-    pub is_matrix_workload: bool,
-    pub is_simple_workload: bool,
+    /// p50/p95/p99 of the peak, from the measurements of similar modules
+    #[serde(default)]
+    pub peak_interval: Option<PeakInterval>,
+    pub buffer_bytes: u64,
+    /// Name of the buffer rule that produced the peak estimate
+    pub buffer_rule: Option<String>,
+    /// Imports, exports, producers, libraries and names the workload is classified from
+    pub signals: WorkloadSignals,
+    pub workload: Workload,
+    /// What made the classification rule match
+    pub workload_evidence: Vec<Evidence>,
     pub binary_size_bytes: u64,
     pub binary_size_mb: f64,
+    pub native_code_bytes: u64,
+    pub cwasm: Option<CwasmInfo>,
+    /// Moved to the top level of the `EstimationReport`
+    #[serde(skip)]
+    pub warnings: Vec<String>,
 }
 impl MemoryInfoEstimator {
 
@@ -24,18 +90,71 @@ impl MemoryInfoEstimator {
         Self {
             linear_memory_pages: 0,
             linear_memory_bytes: 0,
-            stack_pointer_offset: 0,
+            maximum_memory_pages: None,
+            maximum_memory_bytes: None,
+            memories: Vec::new(),
+            stack_size_bytes: 0,
+            static_data_bytes: 0,
+            heap_start_bytes: 0,
+            memory_layout: MemoryLayout::new(),
+            stack_analysis: None,
+            stack_usage_bytes: 0,
+            native_stack_bytes: 0,
+            growth_analysis: None,
             function_tables: Vec::new(),
             total_function_references: 0,
+            function_count: 0,
+            global_count: 0,
+            data_segment_count: 0,
+            element_segment_count: 0,
+            element_segments_per_table: Vec::new(),
+            active_data_bytes: 0,
+            passive_data_bytes: 0,
+            highest_data_offset: 0,
+            static_footprint_bytes: 0,
+            import_count: 0,
+            imported_memory_count: 0,
+            imported_table_count: 0,
+            export_count: 0,
+            exported_functions: Vec::new(),
+            nested_loop_float_accesses: 0,
+            is_component: false,
+            core_instance_count: 0,
+            core_modules: Vec::new(),
+            payload: None,
+            inference: None,
             estimated_minimum_memory_bytes: 0,
             estimated_peak_memory_bytes: 0,
-            is_ml_workload: false,
-            is_matrix_workload: false,
-            is_simple_workload: false,
+            peak_interval: None,
+            buffer_bytes: 0,
+            buffer_rule: None,
+            signals: WorkloadSignals::new(),
+            workload: Workload::Unclassified,
+            workload_evidence: Vec::new(),
             binary_size_bytes: 0,
             binary_size_mb: 0.0,
+            native_code_bytes: 0,
+            cwasm: None,
+            warnings: Vec::new(),
         }
     }
+
+    /// Core modules written by the user (main module and linked libraries)
+    pub fn user_modules(&self) -> impl Iterator<Item = &CoreModuleInfo> {
+        self.core_modules
+            .iter()
+            .filter(|module| matches!(module.role, CoreModuleRole::Main | CoreModuleRole::Library))
+    }
+
+    /// Memories defined (not imported) without a declared maximum
+    pub fn unbounded_memories(&self) -> impl Iterator<Item = &MemoryDescriptor> {
+        self.memories.iter().filter(|memory| !memory.imported && memory.is_unbounded())
+    }
+
+    /// The wasi-preview1 adapter module, if the component embeds one
+    pub fn adapter_module(&self) -> Option<&CoreModuleInfo> {
+        self.core_modules.iter().find(|module| module.role == CoreModuleRole::Adapter)
+    }
     
 }
 
@@ -47,32 +166,50 @@ impl fmt::Display for MemoryInfoEstimator {
             "MemoryInfo:\n\
              - linear_memory_pages: {}\n\
              - linear_memory_bytes: {}\n\
-             - stack_pointer_offset: {}\n\
+             - maximum_memory_bytes: {}\n\
+             - stack_size_bytes: {}\n\
+             - static_data_bytes: {}\n\
+             - heap_start_bytes: {}\n\
              - function_tables: {:?}\n\
              - total_function_references: {}\n\
+             - function_count: {}\n\
+             - data_segment_count: {}\n\
+             - imported_memory_count: {}\n\
+             - core_modules: {}\n\
+             - core_instances: {}\n\
              - estimated_minimum_memory: {}\n\
              - estimated_peak_memory: {}\n\
-             - is_ml_workload: {}\n\
+             - workload: {}\n\
              - binary_size_bytes: {}\n\
-             - binary_size_mb: {:.2}",
+             - binary_size_mb: {:.2}\n\
+             - native_code_bytes: {}",
             self.linear_memory_pages,
             self.linear_memory_bytes,
-            self.stack_pointer_offset,
+            self.maximum_memory_bytes.map(|bytes| bytes.to_string()).unwrap_or_else(|| "unbounded".to_string()),
+            self.stack_size_bytes,
+            self.static_data_bytes,
+            self.heap_start_bytes,
             self.function_tables,
             self.total_function_references,
+            self.function_count,
+            self.data_segment_count,
+            self.imported_memory_count,
+            self.core_modules.len(),
+            self.core_instance_count,
             self.estimated_minimum_memory_bytes,
             self.estimated_peak_memory_bytes,
-            self.is_ml_workload,
+            self.workload.as_str(),
             self.binary_size_bytes,
             self.binary_size_mb,
+            self.native_code_bytes,
         )
     }
 }
 
 
 /// Analyze binary size of WASM file
-pub fn analyze_binary_size(wasm_path: &str, memory_info: &mut MemoryInfoEstimator) -> Result<(), Box<dyn std::error::Error>> {
-    let metadata = fs::metadata(wasm_path)?;
+pub fn analyze_binary_size(wasm_path: &str, memory_info: &mut MemoryInfoEstimator) -> Result<(), EstimatorError> {
+    let metadata = fs::metadata(wasm_path).map_err(|e| EstimatorError::from_io(wasm_path, e))?;
     let binary_size_bytes = metadata.len();
     let binary_size_mb = binary_size_bytes as f64 / (1024.0 * 1024.0);
     memory_info.binary_size_bytes = binary_size_bytes;
@@ -93,179 +230,266 @@ pub fn categorize_binary_size(size_bytes: u64) -> &'static str {
 }
 
 
-/// Convert a .wasm file to .wat format using wasmprinter crate
-pub fn convert_wasm_to_wat(wasm_path: &str, wat_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let wasm_bytes = fs::read(wasm_path)?;
-    let wat_string = wasmprinter::print_bytes(&wasm_bytes)?;
-    fs::write(wat_path, wat_string)?;
-    Ok(())
+/// Classify the workload from the section statistics collected by the binary analyzer,
+/// using the classification rules of the active profile
+pub fn classify_workload(memory_info: &mut MemoryInfoEstimator) {
+    active_rules().classify(memory_info);
 }
 
-/// Analyze memory requirements from a .wat file (ENHANCED)
-pub fn analyze_wat_memory_simple(wat_path: &str, memory_info: &mut MemoryInfoEstimator) -> Result<(), Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(wat_path)?;
-    
-    // Enhanced regex patterns for comprehensive analysis
-    let memory_regex = Regex::new(r"\(memory.*?(\d+)\)")?;
-    let stack_regex = Regex::new(r"stack_pointer.*?i32\.const\s+(\d+)")?;
-    let table_regex = Regex::new(r"\(table.*?(\d+).*?(\d+).*?funcref\)")?;
-    let func_regex = Regex::new(r"^\s*\(func ")?;
-    let data_regex = Regex::new(r"^\s*\(data ")?;
-    let global_regex = Regex::new(r"^\s*\(global ")?;
-    
-    // Find memory pages
-    if let Some(caps) = memory_regex.captures(&content) {
-        memory_info.linear_memory_pages = caps[1].parse()?;
-        memory_info.linear_memory_bytes = memory_info.linear_memory_pages as u64 * 65536;
-    }
-    
-    // Find stack pointer offset (more precise pattern)
-    if let Some(caps) = stack_regex.captures(&content) {
-        memory_info.stack_pointer_offset = caps[1].parse()?;
-    }
-    
-    // Find function tables
-    for caps in table_regex.captures_iter(&content) {
-        let table_size = caps[1].parse::<u32>()?;
-        memory_info.function_tables.push(table_size);
+/// Human readable name of the detected workload class
+pub fn workload_type_name(memory_info: &MemoryInfoEstimator) -> &str {
+    memory_info.workload.display_name()
+}
+
+/// Stack actually needed by the workload. When the call graph gives a bound (no
+/// recursion, no indirect calls) that bound is used for the shadow stack, otherwise
+/// the whole reserved stack is assumed to be used. The native stack of the compiled
+/// code is added on top.
+pub fn estimate_stack_usage(memory_info: &MemoryInfoEstimator) -> u64 {
+    match &memory_info.stack_analysis {
+        Some(analysis) => {
+            let bounded = !analysis.has_unbounded_recursion() && !analysis.has_indirect_calls;
+            let shadow = if bounded {
+                analysis.max_shadow_stack_bytes.min(memory_info.stack_size_bytes)
+            } else {
+                memory_info.stack_size_bytes
+            };
+            shadow + analysis.max_native_stack_bytes
+        }
+        None => memory_info.stack_size_bytes,
     }
-    
-    memory_info.total_function_references = memory_info.function_tables.iter().sum();
-    
-    // Count functions for complexity analysis (simpler approach)
-    let function_count = content.lines()
-        .filter(|line| line.trim().starts_with("(func "))
-        .count();
-    
-    // Count data sections for static data analysis
-    let data_section_count = content.lines()
-        .filter(|line| line.trim().starts_with("(data "))
-        .count();
-    
-    // Count globals for state analysis
-    let global_count = content.lines()
-        .filter(|line| line.trim().starts_with("(global "))
-        .count();
-    
-    // Enhanced workload classification based on multiple factors
-    // ML inference workloads are specifically neural network models (ONNX, etc.)
-    // These typically have moderate size but high function count and data sections
-    let is_ml_inference = memory_info.binary_size_bytes > 600_000 || 
-                         (function_count > 500 && data_section_count > 2) ||
-                         (memory_info.binary_size_bytes > 300_000 && function_count > 400) ||
-                         (memory_info.binary_size_bytes > 200_000 && function_count > 500);
-    
-    // Matrix operations are computational workloads with moderate complexity
-    let is_matrix_workload = memory_info.binary_size_bytes > 150_000 && 
-                            memory_info.binary_size_bytes <= 600_000 &&
-                            function_count > 200 && function_count <= 500 &&
-                            !is_ml_inference;
-    
-    // Simple computational workloads (Fibonacci, basic algorithms)
-    let is_simple_workload = memory_info.binary_size_bytes <= 150_000 || 
-                            function_count <= 200;
-    
-    // Update workload classification flags
-    memory_info.is_ml_workload = is_ml_inference;
-    memory_info.is_matrix_workload = is_matrix_workload;
-    memory_info.is_simple_workload = is_simple_workload;
-    
-    println!("📊 Enhanced WAT Analysis:");
-    println!("   • Functions: {}", function_count);
-    println!("   • Data sections: {}", data_section_count);
-    println!("   • Globals: {}", global_count);
-    println!("   • Stack pointer: {} bytes ({:.2} MB)", 
-             memory_info.stack_pointer_offset,
-             memory_info.stack_pointer_offset as f64 / (1024.0 * 1024.0));
-    
-    // Print workload classification
-    let workload_type = if memory_info.is_ml_workload {
-        "ML Inference"
-    } else if memory_info.is_matrix_workload {
-        "Matrix Operations"
-    } else if memory_info.is_simple_workload {
-        "Simple Computation"
-    } else {
-        "Unclassified"
-    };
-    println!("   • Workload type: {}", workload_type);
-    
-    Ok(())
+}
+
+/// The part of the stack usage outside linear memory. The shadow stack lives in the
+/// initial pages and is already counted with them; without a call graph the native
+/// stack isn't known and is left to the buffer.
+pub fn estimate_native_stack_usage(memory_info: &MemoryInfoEstimator) -> u64 {
+    memory_info.stack_analysis.as_ref().map_or(0, |analysis| analysis.max_native_stack_bytes)
+}
+
+/// Host memory behind the table slots
+pub fn table_slot_bytes(memory_info: &MemoryInfoEstimator) -> u64 {
+    memory_info.total_function_references as u64 * TABLE_SLOT_BYTES
+}
+
+/// Active data written past the initial pages of its memory; the rest lands in pages
+/// already counted as linear memory
+pub fn active_data_beyond_initial_bytes(memory_info: &MemoryInfoEstimator) -> u64 {
+    memory_info
+        .memories
+        .iter()
+        .map(|memory| memory.highest_data_offset.saturating_sub(memory.initial_bytes()).min(memory.active_data_bytes))
+        .sum()
 }
 
 pub fn calculate_aggregated_memory(memory_info: &mut MemoryInfoEstimator) -> () {
-    // Enhanced memory calculation using stack pointer and linear memory
-    // Base memory = linear memory + stack space
-    let base_memory = memory_info.linear_memory_bytes + memory_info.stack_pointer_offset;
+    // Base memory = linear memory + the native stack; the shadow stack is part of linear memory
+    memory_info.stack_usage_bytes = estimate_stack_usage(memory_info);
+    memory_info.native_stack_bytes = estimate_native_stack_usage(memory_info);
+    let base_memory = memory_info.linear_memory_bytes + memory_info.native_stack_bytes;
     
-    // Add binary overhead (typically 10-20% of binary size for runtime overhead)
-    let binary_overhead = (memory_info.binary_size_bytes as f64 * 0.15) as u64;
+    // Static footprint: what the module image adds outside the initial linear memory. Active
+    // data is copied into those pages, so only passive segments and the host-side table slots count.
+    memory_info.static_footprint_bytes = memory_info.passive_data_bytes
+        + active_data_beyond_initial_bytes(memory_info)
+        + table_slot_bytes(memory_info);
     
-    // Calculate minimum memory requirement
-    memory_info.estimated_minimum_memory_bytes = base_memory + binary_overhead;
-
-    // Dynamic buffer calculation based on workload complexity
-    let buffer_size = if memory_info.is_ml_workload {
-        // ML inference workloads (SqueezeNet, ResNet) - large buffer for model operations
-        if memory_info.binary_size_bytes > 500_000 {
-            15 * 1024 * 1024 // 15MB for large ML models
-        } else {
-            12 * 1024 * 1024 // 12MB for medium ML models
+    // The payload is priced by the first payload rule matching the workload
+    let payload_bytes = match memory_info.payload.take() {
+        Some(payload) => {
+            let payload = PayloadEstimate::new(payload.features, active_rules().payload_rule(memory_info));
+            let total_bytes = payload.total_bytes;
+            memory_info.payload = Some(payload);
+            total_bytes
         }
-    } else if memory_info.is_matrix_workload {
-        // Matrix operations (multiplication, transpose) - medium buffer
-        if memory_info.binary_size_bytes > 200_000 {
-            8 * 1024 * 1024  // 8MB for large matrix operations
-        } else {
-            6 * 1024 * 1024  // 6MB for medium matrix operations
+        None => 0,
+    };
+
+    // Calculate minimum memory requirement; the native code of a precompiled
+    // artifact is mmapped as a whole when the module is loaded, and wasi-nn
+    // models live in the host next to the guest
+    memory_info.estimated_minimum_memory_bytes = base_memory
+        + memory_info.static_footprint_bytes
+        + memory_info.native_code_bytes
+        + payload_bytes
+        + memory_info.inference.as_ref().map_or(0, |inference| inference.total_bytes);
+
+    // Dynamic buffer from the first matching rule of the active profile,
+    // scaled by how the guest can grow its linear memory
+    let (buffer_rule, buffer_size) = active_rules().buffer_bytes(memory_info);
+    memory_info.buffer_rule = buffer_rule.map(|rule| rule.name.clone());
+    memory_info.buffer_bytes = buffer_size;
+
+    memory_info.estimated_peak_memory_bytes = memory_info.estimated_minimum_memory_bytes + buffer_size;
+}
+
+
+/// Inspect the ONNX models in `model_folder`, one inference per binary item of the payload.
+/// A model that can't be parsed leaves the inference memory out of the estimate, with a warning.
+pub fn estimate_inference(memory_info: &mut MemoryInfoEstimator, model_folder: &str) {
+    let batch_size = memory_info.payload.as_ref().map_or(1, |payload| payload.features.blobs.len() as u64).max(1);
+    match estimate_inference_memory(model_folder, batch_size) {
+        Ok(inference) => {
+            for model in inference.iter().flat_map(|inference| inference.models.iter()) {
+                if !model.unknown_ops.is_empty() {
+                    memory_info.warnings.push(format!(
+                        "{}: output shapes of {} guessed from their largest input",
+                        model.path,
+                        model.unknown_ops.join(", ")
+                    ));
+                }
+            }
+            memory_info.inference = inference;
         }
-    } else if memory_info.is_simple_workload {
-        // Simple computational workloads (Fibonacci) - small buffer
-        if memory_info.total_function_references > 50 {
-            3 * 1024 * 1024  // 3MB for complex simple workloads
-        } else {
-            2 * 1024 * 1024  // 2MB for basic simple workloads
+        Err(e) => memory_info.warnings.push(format!("inference host memory not estimated: {}", e)),
+    }
+}
+
+/// Attach the peak interval, from the accuracy history in `MEMORY_ESTIMATOR_ACCURACY_DIR`.
+/// An unreadable history only costs the precision of the interval, so it is a warning.
+pub fn estimate_peak_interval(memory_info: &mut MemoryInfoEstimator) {
+    let history = match AccuracyHistory::from_env().all_records() {
+        Ok(history) => history,
+        Err(e) => {
+            memory_info.warnings.push(format!("accuracy history unavailable, using the fallback peak interval: {}", e));
+            Vec::new()
         }
-    } else {
-        // Fallback for unclassified workloads
-        5 * 1024 * 1024      // 5MB default buffer
     };
+    memory_info.peak_interval = Some(peak_interval(memory_info, &history));
+}
 
-    memory_info.estimated_peak_memory_bytes = memory_info.estimated_minimum_memory_bytes + buffer_size;
-    
-    println!("🧮 Memory Calculation:");
-    println!("   • Base memory: {:.2} MB (linear: {:.2} MB + stack: {:.2} MB)", 
-             base_memory as f64 / (1024.0 * 1024.0),
-             memory_info.linear_memory_bytes as f64 / (1024.0 * 1024.0),
-             memory_info.stack_pointer_offset as f64 / (1024.0 * 1024.0));
-    println!("   • Binary overhead: {:.2} MB", binary_overhead as f64 / (1024.0 * 1024.0));
-    println!("   • Buffer size: {:.2} MB", buffer_size as f64 / (1024.0 * 1024.0));
+/// Run every analyzer on the wasm binary and its precompiled artifact and compute the
+/// estimate. Nothing is printed; any analyzer failure fails the whole estimate.
+pub fn build_memory_info(cwasm_file: &str, wasm_file: &str) -> Result<EstimationReport, EstimatorError> {
+    build(cwasm_file, wasm_file, None, None)
 }
 
+/// Same as `build_memory_info`, plus what the decompressed `payload` of the job adds.
+/// Files it references are looked up in `base_dir`, the folder mounted into the guest,
+/// and the ONNX models in it are counted as inference host memory.
+pub fn build_memory_info_with_payload(cwasm_file: &str, wasm_file: &str, payload: &str, base_dir: Option<&str>) -> Result<EstimationReport, EstimatorError> {
+    build(cwasm_file, wasm_file, Some(analyze_payload(payload, base_dir)), base_dir)
+}
 
-pub fn build_memory_info(wasm_file: &str, wat_file: &str) -> MemoryInfoEstimator {
+fn build(cwasm_file: &str, wasm_file: &str, payload: Option<PayloadFeatures>, model_folder: Option<&str>) -> Result<EstimationReport, EstimatorError> {
     let mut memory_info = MemoryInfoEstimator::new();
+    memory_info.payload = payload.map(|features| PayloadEstimate::new(features, None));
     
-    // Analyze binary size first (fast and informative)
-    match analyze_binary_size(wasm_file, &mut memory_info) {
-        Ok(_) => {
-            let size_category = categorize_binary_size(memory_info.binary_size_bytes);
-            println!("📦 Binary Analysis:");
-            println!("   • File size: {:.2} MB", memory_info.binary_size_mb);
-            println!("   • Size category: {}", size_category);
-        },
-        Err(e) => println!("Error analyzing binary: {}", e),
-    }
-    println!("memory_info: {}", memory_info);
-    // Analyze memory requirements (simplified)
-    match analyze_wat_memory_simple(wat_file, &mut memory_info) {
-        Ok(()) => {},
-        Err(e) => println!("Error analyzing memory: {}", e),
+    // The size of the original wasm is what the workload heuristics were designed around
+    analyze_binary_size(wasm_file, &mut memory_info)?;
+
+    // Analyze memory requirements from the wasm sections; aggregation resets the
+    // warnings, so it runs before anything else can add one
+    analyze_wasm_binary(wasm_file, &mut memory_info)?;
+
+    // The precompiled artifact tells how much native code gets mapped
+    analyze_cwasm(cwasm_file, &mut memory_info)?;
+
+    if let Some(model_folder) = model_folder {
+        estimate_inference(&mut memory_info, model_folder);
     }
 
     calculate_aggregated_memory(&mut memory_info);
+    estimate_peak_interval(&mut memory_info);
 
-    memory_info
+    Ok(EstimationReport::new(wasm_file, cwasm_file, &active_rules().profile, memory_info))
+}
+
+/// Human readable rendering of a report: the analysis steps, the estimate and its warnings
+pub fn print_estimation_report(report: &EstimationReport) {
+    let memory_info = &report.features;
+
+    println!("📦 Binary Analysis:");
+    println!("   • File size: {:.2} MB", memory_info.binary_size_mb);
+    println!("   • Size category: {}", report.classification.size_category);
+
+    if let Some(cwasm) = &memory_info.cwasm {
+        println!("⚙️  Precompiled Artifact:");
+        println!("   • File size: {:.2} MB", cwasm.file_size_bytes as f64 / (1024.0 * 1024.0));
+        println!("   • Native code (.text): {:.2} MB", cwasm.text_size_bytes as f64 / (1024.0 * 1024.0));
+        println!("   • Memory images (.rodata.wasm): {:.2} KB", cwasm.wasm_data_bytes as f64 / 1024.0);
+        println!("   • Metadata: {:.2} KB", cwasm.metadata_bytes as f64 / 1024.0);
+        println!("   • Target: {} (wasmtime {})",
+                 cwasm.target_triple.as_deref().unwrap_or("unknown"),
+                 cwasm.wasmtime_version.as_deref().unwrap_or("unknown"));
+    }
+
+    println!("📊 Binary Section Analysis:");
+    println!("   • Functions: {}", memory_info.function_count);
+    println!("   • Data segments: {}", memory_info.data_segment_count);
+    println!("   • Element segments: {}", memory_info.element_segment_count);
+    println!("   • Globals: {}", memory_info.global_count);
+    println!("   • Imports: {} ({} memories, {} tables)",
+             memory_info.import_count,
+             memory_info.imported_memory_count,
+             memory_info.imported_table_count);
+    println!("   • Exports: {}", memory_info.export_count);
+    println!("   • Toolchain: {}", memory_info.memory_layout.toolchain.as_str());
+    println!("   • Stack size: {} bytes ({:.2} MB)", 
+             memory_info.stack_size_bytes,
+             memory_info.stack_size_bytes as f64 / (1024.0 * 1024.0));
+    println!("   • Static data: {} bytes", memory_info.static_data_bytes);
+    println!("   • Heap start: {:#x}", memory_info.heap_start_bytes);
+    println!("   • Workload type: {}", report.classification.workload);
+    if memory_info.is_component {
+        println!("   • Component: {} core modules, {} core instances",
+                 memory_info.core_modules.len(),
+                 memory_info.core_instance_count);
+    }
+
+    let estimate = &report.estimate;
+    println!("🧮 Memory Calculation:");
+    println!("   • Base memory: {:.2} MB (linear: {:.2} MB + native stack: {:.2} MB; {:.2} MB of stack in all)", 
+             (estimate.linear_memory_bytes + estimate.native_stack_bytes) as f64 / (1024.0 * 1024.0),
+             estimate.linear_memory_bytes as f64 / (1024.0 * 1024.0),
+             estimate.native_stack_bytes as f64 / (1024.0 * 1024.0),
+             estimate.stack_usage_bytes as f64 / (1024.0 * 1024.0));
+    println!("   • Static footprint: {:.2} MB (data: {:.2} MB passive + {:.2} MB active past the initial pages, {} table slots)",
+             memory_info.static_footprint_bytes as f64 / (1024.0 * 1024.0),
+             memory_info.passive_data_bytes as f64 / (1024.0 * 1024.0),
+             active_data_beyond_initial_bytes(memory_info) as f64 / (1024.0 * 1024.0),
+             memory_info.total_function_references);
+    println!("   • Native code: {:.2} MB", estimate.native_code_bytes as f64 / (1024.0 * 1024.0));
+    if let Some(payload) = &memory_info.payload {
+        println!("   • Payload: {:.2} MB (rule: {}; copy {:.2} MB, parsed {:.2} MB, product {:.2} MB, {} binary item(s) {:.2} MB)",
+                 payload.total_bytes as f64 / (1024.0 * 1024.0),
+                 payload.rule.as_deref().unwrap_or("none"),
+                 payload.copy_bytes as f64 / (1024.0 * 1024.0),
+                 payload.parsed_bytes as f64 / (1024.0 * 1024.0),
+                 payload.product_bytes as f64 / (1024.0 * 1024.0),
+                 payload.features.blobs.len(),
+                 payload.blob_bytes as f64 / (1024.0 * 1024.0));
+    }
+    if let Some(inference) = &memory_info.inference {
+        println!("   • Inference host memory: {:.2} MB ({} ONNX model(s) in {}, batch of {})",
+                 inference.total_bytes as f64 / (1024.0 * 1024.0),
+                 inference.models.len(),
+                 inference.model_folder,
+                 inference.batch_size);
+        for model in inference.models.iter() {
+            println!("     - {}: file {:.2} MB, weights {:.2} MB ({} initializers), activations {:.2} MB in a {:.2} MB arena",
+                     model.path,
+                     model.file_bytes as f64 / (1024.0 * 1024.0),
+                     model.weight_bytes as f64 / (1024.0 * 1024.0),
+                     model.initializer_count,
+                     model.activation_peak_bytes as f64 / (1024.0 * 1024.0),
+                     model.arena_bytes as f64 / (1024.0 * 1024.0));
+        }
+    }
+    println!("   • Buffer size: {:.2} MB (rule: {} from profile \"{}\", memory growth: {})",
+             estimate.buffer_bytes as f64 / (1024.0 * 1024.0),
+             report.classification.buffer_rule.as_deref().unwrap_or("none"),
+             report.rule_profile,
+             report.classification.growth_behavior.map(|behavior| behavior.as_str()).unwrap_or("unknown"));
+
+    print_memory_analysis_simple(memory_info);
+
+    if !report.warnings.is_empty() {
+        println!("\n⚠️  Warnings:");
+        for warning in report.warnings.iter() {
+            println!("   • {}", warning);
+        }
+    }
 }
 
 /// Print simplified memory analysis
@@ -273,32 +497,141 @@ pub fn print_memory_analysis_simple(memory_info: &MemoryInfoEstimator) {
     println!("📊 Linear Memory:");
     println!("   • Pages: {} (64KB each)", memory_info.linear_memory_pages);
     println!("   • Total: {:.2} MB", memory_info.linear_memory_bytes as f64 / (1024.0 * 1024.0));
+    for memory in memory_info.memories.iter().filter(|memory| !memory.imported) {
+        let maximum = match memory.maximum_pages {
+            Some(pages) => format!("{} pages ({:.2} MB)", pages, memory.maximum_bytes().unwrap_or(0) as f64 / (1024.0 * 1024.0)),
+            None => "none".to_string(),
+        };
+        println!("   • Module {} memory {}: initial {} pages, maximum {}{}",
+                 memory.module_index,
+                 memory.memory_index,
+                 memory.initial_pages,
+                 maximum,
+                 if memory.shared { ", shared" } else { "" });
+        println!("     data: {} bytes active, {} bytes passive, highest offset {:#x}",
+                 memory.active_data_bytes,
+                 memory.passive_data_bytes,
+                 memory.highest_data_offset);
+    }
+    if memory_info.unbounded_memories().next().is_some() {
+        println!("   ⚠️  UNBOUNDED: at least one linear memory has no declared maximum and can grow without limit");
+    }
+    
+    if memory_info.is_component {
+        println!("\n🧩 Core Modules:");
+        for module in memory_info.user_modules() {
+            print_core_module(module);
+        }
+        if let Some(adapter) = memory_info.adapter_module() {
+            println!("   WASI adapter (reported separately):");
+            print_core_module(adapter);
+        }
+        let glue_count = memory_info.core_modules.iter()
+            .filter(|module| module.role == CoreModuleRole::Glue)
+            .count();
+        if glue_count > 0 {
+            println!("   • {} generated glue module(s)", glue_count);
+        }
+    }
     
     println!("\n📚 Stack Memory:");
-    println!("   • Stack pointer offset: {:.2} MB", memory_info.stack_pointer_offset as f64 / (1024.0 * 1024.0));
+    println!("   • Stack size: {:.2} MB ({})",
+             memory_info.stack_size_bytes as f64 / (1024.0 * 1024.0),
+             if memory_info.memory_layout.stack_first { "stack first" } else { "after static data" });
+    if let Some(stack_pointer) = memory_info.memory_layout.stack_pointer {
+        println!("   • Initial __stack_pointer: {:#x}", stack_pointer);
+    }
+    if let Some(analysis) = &memory_info.stack_analysis {
+        println!("   • Worst-case shadow stack: {} bytes, native stack: {} bytes",
+                 analysis.max_shadow_stack_bytes,
+                 analysis.max_native_stack_bytes);
+        if analysis.has_unbounded_recursion() {
+            println!("   • ⚠️  Unbounded recursion in: {}", analysis.recursive_functions.join(", "));
+        }
+        if analysis.has_indirect_calls {
+            println!("   • Indirect calls present: depth through call_indirect is not covered");
+        }
+        if !analysis.deepest_chain.is_empty() {
+            println!("   • Deepest call chain: {}", analysis.deepest_chain.join(" -> "));
+        }
+    }
+    println!("   • Static data: {:.2} KB", memory_info.static_data_bytes as f64 / 1024.0);
+    println!("   • Heap start: {:#x}{}",
+             memory_info.heap_start_bytes,
+             if memory_info.memory_layout.heap_base.is_some() { " (__heap_base)" } else { " (derived)" });
+    
+    if let Some(growth) = &memory_info.growth_analysis {
+        println!("\n📈 Memory Growth:");
+        println!("   • Behavior: {}", growth.behavior.as_str());
+        if !growth.allocators.is_empty() {
+            println!("   • Allocators: {}",
+                     growth.allocators.iter().map(|allocator| allocator.as_str()).collect::<Vec<_>>().join(", "));
+        }
+        for site in growth.grow_sites.iter() {
+            println!("   • memory.grow x{} in {}{}",
+                     site.count,
+                     site.function_name,
+                     if site.allocator_driven { " (allocator)" } else { "" });
+        }
+    }
     
     println!("\n🔗 Function Tables:");
     for (i, table_size) in memory_info.function_tables.iter().enumerate() {
         println!("   • Table {}: {} function references", i, table_size);
     }
     println!("   • Total function references: {}", memory_info.total_function_references);
+    for (module_index, stats) in memory_info.element_segments_per_table.iter() {
+        println!("   • Module {} table {}: {} element segment(s), {} slots initialized",
+                 module_index, stats.table_index, stats.active_segments, stats.initialized_slots);
+    }
     
     // Workload analysis
-    if memory_info.is_ml_workload {
+    if memory_info.workload == Workload::Ml {
         println!("\n🤖 ML Workload Analysis:");
         println!("   • Workload Type: Machine Learning / AI Inference");
-        println!("   • High function complexity detected");
     } else {
         println!("\n⚙️ Standard Workload Analysis:");
-        println!("   • Workload Type: General Purpose");
+        println!("   • Workload Type: {}", memory_info.workload.display_name());
+    }
+    for evidence in memory_info.workload_evidence.iter() {
+        println!("   • Evidence: {}", evidence);
+    }
+    
+    if let Some(cwasm) = &memory_info.cwasm {
+        println!("\n⚙️ Native Code:");
+        println!("   • Original wasm: {:.2} MB", memory_info.binary_size_mb);
+        println!("   • Mapped native code: {:.2} MB (of a {:.2} MB .cwasm)",
+                 cwasm.text_size_bytes as f64 / (1024.0 * 1024.0),
+                 cwasm.file_size_bytes as f64 / (1024.0 * 1024.0));
     }
     
     println!("\n💾 Memory Summary:");
+    println!("   • Static footprint: {:.2} MB", memory_info.static_footprint_bytes as f64 / (1024.0 * 1024.0));
+    if let Some(payload) = &memory_info.payload {
+        println!("   • Payload: {:.2} MB for {} bytes of input{}",
+                 payload.total_bytes as f64 / (1024.0 * 1024.0),
+                 payload.features.payload_bytes,
+                 payload.features.matrices.iter()
+                     .map(|shape| format!(", {}x{} matrix", shape.rows, shape.cols))
+                     .collect::<String>());
+    }
+    if let Some(inference) = &memory_info.inference {
+        println!("   • Inference host memory: {:.2} MB (outside linear memory)",
+                 inference.total_bytes as f64 / (1024.0 * 1024.0));
+    }
     println!("   • Minimum memory: {:.2} MB", memory_info.estimated_minimum_memory_bytes as f64 / (1024.0 * 1024.0));
     println!("   • Estimated peak: {:.2} MB", memory_info.estimated_peak_memory_bytes as f64 / (1024.0 * 1024.0));
+    if let Some(interval) = &memory_info.peak_interval {
+        println!("   • Peak interval: p50 {:.2} MB, p95 {:.2} MB, p99 {:.2} MB ({}{})",
+                 interval.p50_bytes as f64 / (1024.0 * 1024.0),
+                 interval.p95_bytes as f64 / (1024.0 * 1024.0),
+                 interval.p99_bytes as f64 / (1024.0 * 1024.0),
+                 interval.source.as_str(),
+                 if interval.samples > 0 { format!(", {} similar jobs", interval.samples) } else { String::new() });
+    }
     
     println!("\n🎯 Recommendations:");
-    if memory_info.is_ml_workload {
+    if memory_info.workload == Workload::Ml {
         println!("   • ML workload detected - allocate extra memory for model operations");
     } else {
         if memory_info.linear_memory_pages < 32 {
@@ -311,3 +644,17 @@ pub fn print_memory_analysis_simple(memory_info: &MemoryInfoEstimator) {
     println!("   • Allocate at least {:.2} MB for safe execution",
              memory_info.estimated_peak_memory_bytes as f64 / (1024.0 * 1024.0));
 }
+
+fn print_core_module(module: &CoreModuleInfo) {
+    println!("   • Module {} ({}{}): {} pages ({:.2} MB) defined, {} imported memories, tables {:?}, {} functions, {} data segments, {} instance(s)",
+             module.index,
+             module.role.as_str(),
+             module.name.as_deref().map(|name| format!(" \"{}\"", name)).unwrap_or_default(),
+             module.linear_memory_pages,
+             module.linear_memory_bytes() as f64 / (1024.0 * 1024.0),
+             module.imported_memory_count,
+             module.function_tables,
+             module.function_count,
+             module.data_segment_count,
+             module.instance_count);
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+/// Toolchain that produced a core module, read from the `producers` custom section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Toolchain {
+    Rust,
+    /// C/C++ compiled against wasi-libc
+    C,
+    TinyGo,
+    Unknown,
+}
+
+impl Toolchain {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Toolchain::Rust => "Rust",
+            Toolchain::C => "C (wasi-libc)",
+            Toolchain::TinyGo => "TinyGo",
+            Toolchain::Unknown => "unknown",
+        }
+    }
+
+    /// Detect the toolchain from `producers` field values (`language` and `processed-by`).
+    /// Rust binaries also list C11 because of the bundled wasi-libc, so Rust wins.
+    pub fn from_producers<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut toolchain = Toolchain::Unknown;
+        for value in values {
+            let value = value.to_ascii_lowercase();
+            if value == "rust" || value == "rustc" {
+                return Toolchain::Rust;
+            }
+            if value.contains("tinygo") {
+                toolchain = Toolchain::TinyGo;
+            } else if toolchain == Toolchain::Unknown && is_c_producer(&value) {
+                toolchain = Toolchain::C;
+            }
+        }
+        toolchain
+    }
+}
+
+fn is_c_producer(value: &str) -> bool {
+    value == "c" || value == "clang" || value.starts_with("c1") || value.starts_with("c9") || value.starts_with("c++")
+}
+
+/// Default `--global-base` used by wasm-ld when the data is not placed after the stack
+const WASM_LD_GLOBAL_BASE: u64 = 1024;
+
+/// Layout of the linear memory of a module as laid out by wasm-ld:
+/// static data, shadow stack and the start of the heap.
+///
+/// With `--stack-first` (Rust, TinyGo) the stack occupies `[0, __stack_pointer)` and
+/// the data follows it; otherwise (C/wasi-libc default) the data starts at the global
+/// base and the stack is placed between `__data_end` and `__heap_base`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryLayout {
+    pub toolchain: Toolchain,
+    /// Initial value of `__stack_pointer`
+    pub stack_pointer: Option<u64>,
+    /// Value of the exported `__heap_base` global, if present
+    pub heap_base: Option<u64>,
+    /// Value of the exported `__data_end` global, if present
+    pub data_end: Option<u64>,
+    /// Lowest address initialized by an active data segment
+    pub data_start: Option<u64>,
+    pub stack_first: bool,
+    pub stack_size_bytes: u64,
+    pub static_data_bytes: u64,
+    pub heap_start_bytes: u64,
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryLayout {
+    pub fn new() -> Self {
+        Self {
+            toolchain: Toolchain::Unknown,
+            stack_pointer: None,
+            heap_base: None,
+            data_end: None,
+            data_start: None,
+            stack_first: false,
+            stack_size_bytes: 0,
+            static_data_bytes: 0,
+            heap_start_bytes: 0,
+        }
+    }
+
+    /// Compute the derived sizes from the symbols found in the module.
+    /// `segment_extent` is the `[start, end)` range covered by active data segments
+    /// and is used whenever `__data_end`/`__heap_base` were not exported.
+    pub fn compute(
+        toolchain: Toolchain,
+        stack_pointer: Option<u64>,
+        heap_base: Option<u64>,
+        data_end: Option<u64>,
+        segment_extent: Option<(u64, u64)>,
+    ) -> Self {
+        let mut layout = Self::new();
+        layout.toolchain = toolchain;
+        layout.stack_pointer = stack_pointer;
+        layout.heap_base = heap_base;
+        layout.data_end = data_end;
+        layout.data_start = segment_extent.map(|(start, _)| start);
+
+        let data_end = data_end.or(segment_extent.map(|(_, end)| end));
+        let sp = stack_pointer.unwrap_or(0);
+
+        // Stack first: the stack pointer starts at or below the first data byte
+        layout.stack_first = match (stack_pointer, layout.data_start) {
+            (Some(sp), Some(start)) => sp <= start,
+            (Some(_), None) => toolchain != Toolchain::C,
+            _ => false,
+        };
+
+        if layout.stack_first {
+            layout.stack_size_bytes = sp;
+            let data_start = layout.data_start.unwrap_or(sp);
+            layout.static_data_bytes = data_end.map(|end| end.saturating_sub(data_start)).unwrap_or(0);
+            layout.heap_start_bytes = heap_base.unwrap_or_else(|| align16(data_end.unwrap_or(sp)));
+        } else {
+            let data_start = layout.data_start.unwrap_or(WASM_LD_GLOBAL_BASE);
+            let data_end = data_end.unwrap_or(data_start);
+            layout.static_data_bytes = data_end.saturating_sub(data_start);
+            layout.stack_size_bytes = sp.saturating_sub(data_end);
+            layout.heap_start_bytes = heap_base.unwrap_or_else(|| align16(sp.max(data_end)));
+        }
+
+        layout
+    }
+}
+
+fn align16(value: u64) -> u64 {
+    (value + 15) & !15
+}
@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::estimator_error::{read_file, EstimatorError};
+
+/// ONNX Runtime's arena never starts smaller than this
+const MIN_ARENA_BYTES: u64 = 1024 * 1024;
+
+/// Minimal protobuf wire format reader, enough for the ONNX graph
+struct ProtoReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+/// A field value; fixed width values are skipped, no field read here uses them
+enum WireValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.position).ok_or("truncated varint")?;
+            self.position += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint too long".to_string())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(len).filter(|&end| end <= self.bytes.len()).ok_or("truncated field")?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Next (field number, value), `None` at the end of the message
+    fn field(&mut self) -> Result<Option<(u64, WireValue<'a>)>, String> {
+        if self.position >= self.bytes.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => WireValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                WireValue::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                WireValue::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                WireValue::Fixed
+            }
+            wire_type => return Err(format!("unsupported wire type {}", wire_type)),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+/// Repeated int64, packed or not
+fn push_ints(value: WireValue, ints: &mut Vec<i64>) -> Result<(), String> {
+    match value {
+        WireValue::Varint(int) => ints.push(int as i64),
+        WireValue::Bytes(packed) => {
+            let mut reader = ProtoReader::new(packed);
+            while reader.position < packed.len() {
+                ints.push(reader.varint()? as i64);
+            }
+        }
+        _ => return Err("unexpected wire type for an int64".to_string()),
+    }
+    Ok(())
+}
+
+/// Size of one element of an ONNX `TensorProto.DataType`
+pub fn element_size(data_type: i32) -> u64 {
+    match data_type {
+        2 | 3 | 9 | 17..=20 => 1,
+        4 | 5 | 10 | 16 => 2,
+        1 | 6 | 12 => 4,
+        7 | 11 | 13 | 14 => 8,
+        15 => 16,
+        // strings and unknown types: count a pointer per element
+        _ => 8,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Tensor {
+    name: String,
+    dims: Vec<i64>,
+    data_type: i32,
+    /// Integer contents, kept for shape inputs (Reshape)
+    ints: Vec<i64>,
+}
+
+impl Tensor {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut tensor = Tensor::default();
+        let mut raw_data: &[u8] = &[];
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, value) => push_ints(value, &mut tensor.dims)?,
+                (2, WireValue::Varint(data_type)) => tensor.data_type = data_type as i32,
+                (7, value) => push_ints(value, &mut tensor.ints)?,
+                (8, WireValue::Bytes(name)) => tensor.name = string(name),
+                (9, WireValue::Bytes(raw)) => raw_data = raw,
+                _ => {}
+            }
+        }
+        if tensor.ints.is_empty() && tensor.data_type == 7 {
+            tensor.ints = raw_data.chunks_exact(8).map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap())).collect();
+        }
+        Ok(tensor)
+    }
+
+    fn bytes(&self) -> u64 {
+        self.dims.iter().map(|&dim| dim.max(0) as u64).product::<u64>() * element_size(self.data_type)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Attribute {
+    name: String,
+    int: Option<i64>,
+    ints: Vec<i64>,
+    string: Option<String>,
+    tensor: Option<Tensor>,
+}
+
+impl Attribute {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut attribute = Attribute::default();
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, WireValue::Bytes(name)) => attribute.name = string(name),
+                (3, WireValue::Varint(int)) => attribute.int = Some(int as i64),
+                (4, WireValue::Bytes(text)) => attribute.string = Some(string(text)),
+                (5, WireValue::Bytes(tensor)) => attribute.tensor = Some(Tensor::parse(tensor)?),
+                (8, value) => push_ints(value, &mut attribute.ints)?,
+                _ => {}
+            }
+        }
+        Ok(attribute)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    attributes: Vec<Attribute>,
+}
+
+impl Node {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut node = Node::default();
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, WireValue::Bytes(input)) => node.inputs.push(string(input)),
+                (2, WireValue::Bytes(output)) => node.outputs.push(string(output)),
+                (4, WireValue::Bytes(op_type)) => node.op_type = string(op_type),
+                (5, WireValue::Bytes(attribute)) => node.attributes.push(Attribute::parse(attribute)?),
+                _ => {}
+            }
+        }
+        Ok(node)
+    }
+
+    fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+
+    fn int(&self, name: &str, default: i64) -> i64 {
+        self.attribute(name).and_then(|attribute| attribute.int).unwrap_or(default)
+    }
+
+    fn ints(&self, name: &str) -> Vec<i64> {
+        self.attribute(name).map(|attribute| attribute.ints.clone()).unwrap_or_default()
+    }
+}
+
+/// A graph input or output: its element type and shape, symbolic dimensions as `None`
+#[derive(Debug, Clone, Default)]
+struct ValueInfo {
+    name: String,
+    elem_type: i32,
+    dims: Vec<Option<i64>>,
+}
+
+impl ValueInfo {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut info = ValueInfo::default();
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, WireValue::Bytes(name)) => info.name = string(name),
+                (2, WireValue::Bytes(type_proto)) => {
+                    // TypeProto.tensor_type
+                    let mut reader = ProtoReader::new(type_proto);
+                    while let Some((field, value)) = reader.field()? {
+                        if let (1, WireValue::Bytes(tensor_type)) = (field, value) {
+                            info.parse_tensor_type(tensor_type)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    fn parse_tensor_type(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, WireValue::Varint(elem_type)) => self.elem_type = elem_type as i32,
+                (2, WireValue::Bytes(shape)) => {
+                    let mut reader = ProtoReader::new(shape);
+                    while let Some((field, value)) = reader.field()? {
+                        if let (1, WireValue::Bytes(dimension)) = (field, value) {
+                            let mut dim = None;
+                            let mut reader = ProtoReader::new(dimension);
+                            while let Some((field, value)) = reader.field()? {
+                                if let (1, WireValue::Varint(dim_value)) = (field, value) {
+                                    dim = Some(dim_value as i64);
+                                }
+                            }
+                            self.dims.push(dim);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    initializers: Vec<Tensor>,
+    inputs: Vec<ValueInfo>,
+    outputs: Vec<ValueInfo>,
+    value_infos: Vec<ValueInfo>,
+}
+
+impl Graph {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut graph = Graph::default();
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, value)) = reader.field()? {
+            match (field, value) {
+                (1, WireValue::Bytes(node)) => graph.nodes.push(Node::parse(node)?),
+                (5, WireValue::Bytes(tensor)) => graph.initializers.push(Tensor::parse(tensor)?),
+                (11, WireValue::Bytes(info)) => graph.inputs.push(ValueInfo::parse(info)?),
+                (12, WireValue::Bytes(info)) => graph.outputs.push(ValueInfo::parse(info)?),
+                (13, WireValue::Bytes(info)) => graph.value_infos.push(ValueInfo::parse(info)?),
+                _ => {}
+            }
+        }
+        Ok(graph)
+    }
+}
+
+/// Shapes and types known so far while walking the graph
+struct ShapeContext {
+    shapes: HashMap<String, Vec<i64>>,
+    types: HashMap<String, i32>,
+    constants: HashMap<String, Vec<i64>>,
+}
+
+impl ShapeContext {
+    fn shape(&self, name: &str) -> Option<&Vec<i64>> {
+        self.shapes.get(name)
+    }
+
+    fn bytes(&self, name: &str) -> u64 {
+        let elements: u64 = self.shapes.get(name).map_or(0, |shape| shape.iter().map(|&dim| dim.max(0) as u64).product());
+        elements * element_size(self.types.get(name).copied().unwrap_or(1))
+    }
+}
+
+/// Spatial output size of a convolution or pooling window
+fn window_output(input: i64, kernel: i64, stride: i64, pad_begin: i64, pad_end: i64, dilation: i64) -> i64 {
+    ((input + pad_begin + pad_end - dilation * (kernel - 1) - 1) / stride.max(1) + 1).max(1)
+}
+
+fn pooled_shape(node: &Node, input: &[i64], channels: i64, kernel: &[i64]) -> Vec<i64> {
+    let spatial = input.len().saturating_sub(2);
+    let strides = node.ints("strides");
+    let pads = node.ints("pads");
+    let dilations = node.ints("dilations");
+    let same_padding = node
+        .attribute("auto_pad")
+        .and_then(|attribute| attribute.string.as_deref())
+        .is_some_and(|auto_pad| auto_pad.starts_with("SAME"));
+    let mut shape = vec![input[0], channels];
+    for axis in 0..spatial {
+        let size = input[axis + 2];
+        let stride = strides.get(axis).copied().unwrap_or(1);
+        let kernel = kernel.get(axis).copied().unwrap_or(1);
+        shape.push(if same_padding {
+            (size + stride - 1) / stride.max(1)
+        } else {
+            window_output(
+                size,
+                kernel,
+                stride,
+                pads.get(axis).copied().unwrap_or(0),
+                pads.get(axis + spatial).copied().unwrap_or(0),
+                dilations.get(axis).copied().unwrap_or(1),
+            )
+        });
+    }
+    shape
+}
+
+/// Output shapes of a node for the operators common in vision and dense models.
+/// `None` for operators it doesn't know.
+fn infer_shapes(node: &Node, context: &ShapeContext) -> Option<Vec<Vec<i64>>> {
+    let input = |index: usize| node.inputs.get(index).and_then(|name| context.shape(name)).cloned();
+    let first = input(0)?;
+    let shape = match node.op_type.as_str() {
+        "Relu" | "LeakyRelu" | "Sigmoid" | "Tanh" | "Clip" | "Dropout" | "BatchNormalization" | "Softmax"
+        | "LogSoftmax" | "Identity" | "Cast" | "Exp" | "Sqrt" | "Neg" | "HardSigmoid" | "HardSwish" | "Erf"
+        | "LRN" | "InstanceNormalization" | "LayerNormalization" | "Gelu" => first,
+        "Add" | "Sub" | "Mul" | "Div" | "Pow" | "Max" | "Min" | "Sum" | "Where" => {
+            // Numpy broadcasting over every input
+            let mut shape = first;
+            for other in (1..node.inputs.len()).filter_map(input) {
+                let rank = shape.len().max(other.len());
+                let pad = |shape: &Vec<i64>| {
+                    let mut padded = vec![1; rank - shape.len()];
+                    padded.extend(shape);
+                    padded
+                };
+                shape = pad(&shape).iter().zip(pad(&other).iter()).map(|(a, b)| *a.max(b)).collect();
+            }
+            shape
+        }
+        "Conv" => {
+            let weight = input(1)?;
+            let kernel = match node.ints("kernel_shape") {
+                kernel if kernel.is_empty() => weight[2..].to_vec(),
+                kernel => kernel,
+            };
+            pooled_shape(node, &first, weight[0], &kernel)
+        }
+        "MaxPool" | "AveragePool" | "LpPool" => pooled_shape(node, &first, first.get(1).copied()?, &node.ints("kernel_shape")),
+        "GlobalAveragePool" | "GlobalMaxPool" => {
+            let mut shape = first.clone();
+            shape.iter_mut().skip(2).for_each(|dim| *dim = 1);
+            shape
+        }
+        "Flatten" => {
+            let axis = node.int("axis", 1).rem_euclid(first.len() as i64 + 1) as usize;
+            vec![first[..axis].iter().product(), first[axis..].iter().product()]
+        }
+        "Gemm" => {
+            let b = input(1)?;
+            let rows = if node.int("transA", 0) != 0 { first[1] } else { first[0] };
+            let cols = if node.int("transB", 0) != 0 { b[0] } else { b[1] };
+            vec![rows, cols]
+        }
+        "MatMul" => {
+            let b = input(1)?;
+            let mut shape = if first.len() >= b.len() { first.clone() } else { b.clone() };
+            let rank = shape.len();
+            if rank >= 2 {
+                shape[rank - 2] = first[first.len().saturating_sub(2)];
+                shape[rank - 1] = *b.last()?;
+            }
+            shape
+        }
+        "Concat" => {
+            let axis = node.int("axis", 0).rem_euclid(first.len() as i64) as usize;
+            let mut shape = first.clone();
+            shape[axis] = (0..node.inputs.len()).filter_map(input).map(|shape| shape[axis]).sum();
+            shape
+        }
+        "Reshape" => {
+            let target = node.inputs.get(1).and_then(|name| context.constants.get(name))?;
+            let total: i64 = first.iter().product();
+            let mut shape: Vec<i64> = target
+                .iter()
+                .enumerate()
+                .map(|(axis, &dim)| if dim == 0 { first.get(axis).copied().unwrap_or(1) } else { dim })
+                .collect();
+            let known: i64 = shape.iter().filter(|&&dim| dim > 0).product();
+            shape.iter_mut().filter(|dim| **dim < 0).for_each(|dim| *dim = total / known.max(1));
+            shape
+        }
+        "Transpose" => {
+            let perm = node.ints("perm");
+            if perm.is_empty() {
+                first.iter().rev().copied().collect()
+            } else {
+                perm.iter().map(|&axis| first[axis as usize]).collect()
+            }
+        }
+        "Squeeze" | "Unsqueeze" | "ReduceMean" | "ReduceSum" | "ReduceMax" => {
+            // Size preserving as far as memory goes, reductions can only be smaller
+            vec![first.iter().product()]
+        }
+        _ => return None,
+    };
+    Some(vec![shape; node.outputs.len().max(1)])
+}
+
+/// What a model needs from the host once loaded through wasi-nn
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnnxModelSummary {
+    pub path: String,
+    pub file_bytes: u64,
+    pub node_count: usize,
+    pub initializer_count: usize,
+    /// Weights held by ONNX Runtime
+    pub weight_bytes: u64,
+    pub batch_size: u64,
+    /// Largest total of intermediate tensors alive at once
+    pub activation_peak_bytes: u64,
+    /// Arena growing by powers of two to hold the activation peak
+    pub arena_bytes: u64,
+    /// Operators whose output shapes were guessed from their largest input
+    pub unknown_ops: Vec<String>,
+}
+
+impl OnnxModelSummary {
+    /// File bytes copied to the host by wasi-nn, weights, and the arena
+    pub fn host_bytes(&self) -> u64 {
+        self.file_bytes + self.weight_bytes + self.arena_bytes
+    }
+}
+
+/// Parse an ONNX model and estimate its memory for `batch_size` inputs.
+/// Symbolic batch dimensions of the graph inputs take `batch_size`, other symbolic ones 1.
+pub fn analyze_onnx_model(path: &str, batch_size: u64) -> Result<OnnxModelSummary, EstimatorError> {
+    let bytes = read_file(path)?;
+    let malformed = |reason: String| EstimatorError::MalformedModel(format!("{}: {}", path, reason));
+    let mut graph = None;
+    let mut reader = ProtoReader::new(&bytes);
+    while let Some((field, value)) = reader.field().map_err(malformed)? {
+        if let (7, WireValue::Bytes(graph_bytes)) = (field, value) {
+            graph = Some(Graph::parse(graph_bytes).map_err(malformed)?);
+        }
+    }
+    let graph = graph.ok_or_else(|| malformed("no graph".to_string()))?;
+    let batch_size = batch_size.max(1);
+
+    let mut context = ShapeContext { shapes: HashMap::new(), types: HashMap::new(), constants: HashMap::new() };
+    for initializer in graph.initializers.iter() {
+        context.shapes.insert(initializer.name.clone(), initializer.dims.clone());
+        context.types.insert(initializer.name.clone(), initializer.data_type);
+        if !initializer.ints.is_empty() {
+            context.constants.insert(initializer.name.clone(), initializer.ints.clone());
+        }
+    }
+    let weight_names: Vec<&str> = graph.initializers.iter().map(|initializer| initializer.name.as_str()).collect();
+    let inputs: Vec<&ValueInfo> = graph.inputs.iter().filter(|input| !weight_names.contains(&input.name.as_str())).collect();
+    for input in inputs.iter() {
+        let dims = input
+            .dims
+            .iter()
+            .enumerate()
+            .map(|(axis, dim)| match dim {
+                Some(dim) if *dim > 0 => *dim,
+                _ if axis == 0 => batch_size as i64,
+                _ => 1,
+            })
+            .collect();
+        context.shapes.insert(input.name.clone(), dims);
+        context.types.insert(input.name.clone(), input.elem_type);
+    }
+    // Shapes stored in the model win over inference, except for the batch dimension
+    let stored: HashMap<&str, &ValueInfo> = graph.value_infos.iter().map(|info| (info.name.as_str(), info)).collect();
+
+    // Last node reading each tensor, to free activations once they are consumed
+    let mut last_use: HashMap<&str, usize> = HashMap::new();
+    for (index, node) in graph.nodes.iter().enumerate() {
+        for input in node.inputs.iter() {
+            last_use.insert(input.as_str(), index);
+        }
+    }
+    let outputs: Vec<&str> = graph.outputs.iter().map(|output| output.name.as_str()).collect();
+
+    let mut unknown_ops: Vec<String> = Vec::new();
+    let mut live: HashMap<String, u64> = inputs.iter().map(|input| (input.name.clone(), context.bytes(&input.name))).collect();
+    let mut activation_peak_bytes: u64 = live.values().sum();
+    for (index, node) in graph.nodes.iter().enumerate() {
+        if node.op_type == "Constant" {
+            if let Some(tensor) = node.attribute("value").and_then(|attribute| attribute.tensor.as_ref()) {
+                let name = node.outputs.first().cloned().unwrap_or_default();
+                context.shapes.insert(name.clone(), tensor.dims.clone());
+                context.types.insert(name.clone(), tensor.data_type);
+                context.constants.insert(name, tensor.ints.clone());
+            }
+            continue;
+        }
+        let element_type = node.inputs.first().and_then(|name| context.types.get(name)).copied().unwrap_or(1);
+        let shapes = infer_shapes(node, &context);
+        if shapes.is_none() && !unknown_ops.contains(&node.op_type) {
+            unknown_ops.push(node.op_type.clone());
+        }
+        // Unknown operators are assumed to keep the shape of their largest input
+        let largest_input = node.inputs.iter().max_by_key(|name| context.bytes(name)).and_then(|name| context.shape(name)).cloned();
+        for (position, output) in node.outputs.iter().enumerate() {
+            let shape = match (shapes.as_ref(), stored.get(output.as_str())) {
+                (Some(shapes), _) => shapes.get(position).cloned(),
+                (None, Some(info)) if info.dims.iter().skip(1).all(Option::is_some) => Some(
+                    info.dims.iter().enumerate().map(|(axis, dim)| dim.unwrap_or(if axis == 0 { batch_size as i64 } else { 1 })).collect(),
+                ),
+                _ => largest_input.clone(),
+            };
+            context.types.insert(output.clone(), element_type);
+            if let Some(shape) = shape {
+                context.shapes.insert(output.clone(), shape);
+            }
+            let bytes = context.bytes(output);
+            live.insert(output.clone(), bytes);
+        }
+        activation_peak_bytes = activation_peak_bytes.max(live.values().sum());
+        live.retain(|name, _| outputs.contains(&name.as_str()) || last_use.get(name.as_str()).is_some_and(|&last| last > index));
+    }
+
+    Ok(OnnxModelSummary {
+        path: path.to_string(),
+        file_bytes: bytes.len() as u64,
+        node_count: graph.nodes.len(),
+        initializer_count: graph.initializers.len(),
+        weight_bytes: graph.initializers.iter().map(Tensor::bytes).sum(),
+        batch_size,
+        activation_peak_bytes,
+        arena_bytes: activation_peak_bytes.max(MIN_ARENA_BYTES).next_power_of_two(),
+        unknown_ops,
+    })
+}
+
+/// `.onnx` files under the folder a job mounts, searched as given and under `models/`
+pub fn find_onnx_models(model_folder_name: &str) -> Vec<String> {
+    let candidates = [PathBuf::from(model_folder_name), Path::new("models").join(model_folder_name)];
+    let Some(folder) = candidates.iter().find(|candidate| candidate.is_dir()) else {
+        return Vec::new();
+    };
+    let mut models = Vec::new();
+    let mut pending = vec![folder.clone()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("onnx")) {
+                models.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    models.sort();
+    models
+}
+
+/// Host memory wasi-nn and ONNX Runtime need for the models of a job, outside guest linear memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InferenceMemory {
+    pub model_folder: String,
+    pub batch_size: u64,
+    pub models: Vec<OnnxModelSummary>,
+    pub total_bytes: u64,
+}
+
+/// Inspect every model in `model_folder_name`; `None` when it holds no ONNX model
+pub fn estimate_inference_memory(model_folder_name: &str, batch_size: u64) -> Result<Option<InferenceMemory>, EstimatorError> {
+    let paths = find_onnx_models(model_folder_name);
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    let models = paths
+        .iter()
+        .map(|path| analyze_onnx_model(path, batch_size))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(InferenceMemory {
+        model_folder: model_folder_name.to_string(),
+        batch_size: batch_size.max(1),
+        total_bytes: models.iter().map(OnnxModelSummary::host_bytes).sum(),
+        models,
+    }))
+}
@@ -0,0 +1,161 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::estimation_rules::PayloadRule;
+
+/// Shortest string taken for base64 encoded binary data
+pub const MIN_BLOB_CHARS: usize = 1024;
+/// File extensions of inputs referenced by path instead of embedded
+const FILE_EXTENSIONS: &[&str] = &[".jpg", ".jpeg", ".png", ".bmp", ".gif", ".webp", ".bin", ".npy"];
+
+/// A rectangular array of numbers found in the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatrixShape {
+    pub rows: u64,
+    pub cols: u64,
+}
+
+/// What a payload contains, independently of the module that will process it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadFeatures {
+    pub payload_bytes: u64,
+    /// Parsed as JSON (possibly encoded twice, as a JSON string)
+    pub json: bool,
+    pub numbers: u64,
+    pub matrices: Vec<MatrixShape>,
+    /// Size of each binary item: decoded base64 strings, or referenced files (0 when not found)
+    pub blobs: Vec<u64>,
+}
+
+impl PayloadFeatures {
+    /// Elements of the largest product of two matrices of the payload with matching inner dimensions
+    pub fn largest_product_elements(&self) -> u64 {
+        let mut largest = 0;
+        for (i, a) in self.matrices.iter().enumerate() {
+            for (j, b) in self.matrices.iter().enumerate() {
+                if i != j && a.cols == b.rows {
+                    largest = largest.max(a.rows.saturating_mul(b.cols));
+                }
+            }
+        }
+        largest
+    }
+}
+
+fn is_base64(text: &str) -> bool {
+    text.len() >= MIN_BLOB_CHARS
+        && text
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'/' | b'=' | b'-' | b'_' | b'\n' | b'\r'))
+}
+
+fn base64_decoded_len(text: &str) -> u64 {
+    let chars = text.bytes().filter(|byte| !matches!(byte, b'=' | b'\n' | b'\r')).count() as u64;
+    chars * 3 / 4
+}
+
+fn referenced_file_size(path: &str, base_dir: Option<&str>) -> Option<u64> {
+    let candidates = [base_dir.map(|dir| Path::new(dir).join(path)), Some(Path::new(path).to_path_buf())];
+    candidates.iter().flatten().find_map(|candidate| std::fs::metadata(candidate).ok()).map(|metadata| metadata.len())
+}
+
+fn matrix_shape(items: &[Value]) -> Option<MatrixShape> {
+    let cols = items.first()?.as_array()?.len();
+    let rectangular = cols > 0
+        && items.iter().all(|row| {
+            row.as_array().is_some_and(|row| row.len() == cols && row.iter().all(Value::is_number))
+        });
+    rectangular.then_some(MatrixShape { rows: items.len() as u64, cols: cols as u64 })
+}
+
+fn visit(value: &Value, base_dir: Option<&str>, features: &mut PayloadFeatures) {
+    match value {
+        Value::Number(_) => features.numbers += 1,
+        Value::String(text) => {
+            let data = text.split_once(";base64,").map_or(text.as_str(), |(_, data)| data);
+            let lower = text.to_ascii_lowercase();
+            if is_base64(data) {
+                features.blobs.push(base64_decoded_len(data));
+            } else if FILE_EXTENSIONS.iter().any(|extension| lower.ends_with(extension)) {
+                features.blobs.push(referenced_file_size(text, base_dir).unwrap_or(0));
+            } else if let Ok(inner @ (Value::Object(_) | Value::Array(_))) = serde_json::from_str::<Value>(text) {
+                // JSON encoded inside a JSON string
+                visit(&inner, base_dir, features);
+            }
+        }
+        Value::Array(items) => match matrix_shape(items) {
+            Some(shape) => {
+                features.numbers = features.numbers.saturating_add(shape.rows.saturating_mul(shape.cols));
+                features.matrices.push(shape);
+            }
+            None => items.iter().for_each(|item| visit(item, base_dir, features)),
+        },
+        Value::Object(fields) => fields.values().for_each(|field| visit(field, base_dir, features)),
+        Value::Bool(_) | Value::Null => {}
+    }
+}
+
+/// Inspect a decompressed payload. Files it references are looked up in `base_dir`
+/// (the folder mounted into the guest) and then relative to the working directory.
+pub fn analyze_payload(payload: &str, base_dir: Option<&str>) -> PayloadFeatures {
+    let mut features = PayloadFeatures {
+        payload_bytes: payload.len() as u64,
+        json: false,
+        numbers: 0,
+        matrices: Vec::new(),
+        blobs: Vec::new(),
+    };
+    match serde_json::from_str::<Value>(payload) {
+        Ok(value) => {
+            features.json = true;
+            visit(&value, base_dir, &mut features);
+        }
+        // Not JSON: a bare base64 blob or file path, or opaque text only copied in
+        Err(_) => visit(&Value::String(payload.trim().to_string()), base_dir, &mut features),
+    }
+    features
+}
+
+/// Memory the payload adds to a job, term by term
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadEstimate {
+    pub features: PayloadFeatures,
+    /// Payload rule of the active profile that priced it
+    pub rule: Option<String>,
+    /// Copies of the payload string in guest memory
+    pub copy_bytes: u64,
+    /// Numbers once parsed into native structures
+    pub parsed_bytes: u64,
+    /// Result of multiplying the payload matrices
+    pub product_bytes: u64,
+    /// Decoded binary items and their per-item tensors
+    pub blob_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl PayloadEstimate {
+    /// Price `features` with `rule`; without a rule the payload adds nothing.
+    /// Saturates instead of wrapping when a payload or profile is out of proportion.
+    pub fn new(features: PayloadFeatures, rule: Option<&PayloadRule>) -> Self {
+        let (copy_bytes, parsed_bytes, product_bytes, blob_bytes) = match rule {
+            Some(rule) => (
+                features.payload_bytes.saturating_mul(rule.copy_percent) / 100,
+                features.numbers.saturating_mul(rule.bytes_per_number),
+                features.largest_product_elements().saturating_mul(rule.bytes_per_product_element),
+                features.blobs.iter().fold(0u64, |total, bytes| {
+                    total.saturating_add(rule.bytes_per_blob.saturating_add(bytes.saturating_mul(rule.blob_percent) / 100))
+                }),
+            ),
+            None => (0, 0, 0, 0),
+        };
+        Self {
+            features,
+            rule: rule.map(|rule| rule.name.clone()),
+            copy_bytes,
+            parsed_bytes,
+            product_bytes,
+            blob_bytes,
+            total_bytes: copy_bytes.saturating_add(parsed_bytes).saturating_add(product_bytes).saturating_add(blob_bytes),
+        }
+    }
+}
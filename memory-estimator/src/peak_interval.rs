@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use crate::estimation_accuracy::AccuracyRecord;
+use crate::estimation_rules::Feature;
+use crate::memory_info_estimator::MemoryInfoEstimator;
+
+/// Below this many similar jobs the fallback interval is used
+pub const MIN_HISTORY_SAMPLES: usize = 5;
+/// Most similar jobs the interval is built from
+pub const NEAREST_NEIGHBORS: usize = 25;
+/// Fallback p95 and p99 as a percentage of the estimated peak
+pub const FALLBACK_P95_PERCENT: u64 = 125;
+pub const FALLBACK_P99_PERCENT: u64 = 150;
+
+/// Where the interval comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntervalSource {
+    /// Measured peaks of similar modules
+    History,
+    /// Not enough history: fixed margins around the estimated peak
+    Fallback,
+}
+
+impl IntervalSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntervalSource::History => "history",
+            IntervalSource::Fallback => "fallback",
+        }
+    }
+}
+
+/// Quantiles of the peak memory a job is expected to reach
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeakInterval {
+    pub p50_bytes: u64,
+    pub p95_bytes: u64,
+    pub p99_bytes: u64,
+    pub source: IntervalSource,
+    /// Jobs the quantiles were taken from, 0 for the fallback
+    pub samples: usize,
+}
+
+impl PeakInterval {
+    pub fn fallback(estimated_peak_bytes: u64) -> Self {
+        Self {
+            p50_bytes: estimated_peak_bytes,
+            p95_bytes: estimated_peak_bytes * FALLBACK_P95_PERCENT / 100,
+            p99_bytes: estimated_peak_bytes * FALLBACK_P99_PERCENT / 100,
+            source: IntervalSource::Fallback,
+            samples: 0,
+        }
+    }
+}
+
+/// Distance between two feature vectors: mean absolute difference of the log sizes,
+/// so a module twice as large is as far away whatever its size
+fn distance(memory_info: &MemoryInfoEstimator, record: &AccuracyRecord) -> f64 {
+    let mut total = 0.0;
+    let mut compared = 0;
+    for feature in Feature::ALL.iter() {
+        if let Some(&value) = record.features.get(feature) {
+            total += ((feature.value(memory_info) as f64).ln_1p() - (value as f64).ln_1p()).abs();
+            compared += 1;
+        }
+    }
+    if compared == 0 {
+        f64::INFINITY
+    } else {
+        total / compared as f64
+    }
+}
+
+fn nearest_rank(sorted: &[f64], q: f64) -> f64 {
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+/// Interval for an estimate on which `calculate_aggregated_memory` already ran.
+/// Similar jobs share its buffer rule (its class) and are the nearest by feature vector.
+/// Their measured peak over their estimated minimum is applied to this minimum, since
+/// the minimum doesn't depend on the rule profile the jobs were estimated with.
+pub fn peak_interval(memory_info: &MemoryInfoEstimator, history: &[AccuracyRecord]) -> PeakInterval {
+    let mut similar: Vec<(f64, &AccuracyRecord)> = history
+        .iter()
+        .filter(|record| record.buffer_rule == memory_info.buffer_rule && record.estimated_minimum_memory_bytes > 0)
+        .map(|record| (distance(memory_info, record), record))
+        .filter(|(distance, _)| distance.is_finite())
+        .collect();
+    if similar.len() < MIN_HISTORY_SAMPLES || memory_info.estimated_minimum_memory_bytes == 0 {
+        return PeakInterval::fallback(memory_info.estimated_peak_memory_bytes);
+    }
+    similar.sort_by(|a, b| a.0.total_cmp(&b.0));
+    similar.truncate(NEAREST_NEIGHBORS);
+
+    let minimum = memory_info.estimated_minimum_memory_bytes as f64;
+    let mut peaks: Vec<f64> = similar
+        .iter()
+        .map(|(_, record)| minimum * record.measured_peak_bytes() as f64 / record.estimated_minimum_memory_bytes as f64)
+        .collect();
+    peaks.sort_by(|a, b| a.total_cmp(b));
+    PeakInterval {
+        p50_bytes: nearest_rank(&peaks, 0.50).ceil() as u64,
+        p95_bytes: nearest_rank(&peaks, 0.95).ceil() as u64,
+        p99_bytes: nearest_rank(&peaks, 0.99).ceil() as u64,
+        source: IntervalSource::History,
+        samples: peaks.len(),
+    }
+}
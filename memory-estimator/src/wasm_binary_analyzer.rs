@@ -0,0 +1,734 @@
+use serde::{Deserialize, Serialize};
+use wasmparser::{ComponentExternalKind, ComponentName, CompositeInnerType, ConstExpr, DataKind, ElementItems, ElementKind, Encoding, ExternalKind, Instance, KnownCustom, MemoryType, Name, Operator, Parser, Payload, TypeRef};
+use crate::call_graph::{CallGraph, FunctionSummary, StackAnalysis};
+use crate::estimator_error::{read_file, EstimatorError};
+use crate::memory_info_estimator::{classify_workload, MemoryInfoEstimator};
+use crate::memory_growth::{analyze_growth, GrowthAnalysis, GrowthBehavior};
+use crate::memory_layout::{MemoryLayout, Toolchain};
+use crate::workload_signals::WorkloadSignals;
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// Role of a core module inside a component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreModuleRole {
+    /// The user's own code (first non-generated module)
+    Main,
+    /// Additional user modules linked into the same component
+    Library,
+    /// The wasi-preview1 adapter injected by wit-component
+    Adapter,
+    /// Shim/fixup modules generated by wit-component to wire up indirect calls
+    Glue,
+}
+
+impl CoreModuleRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoreModuleRole::Main => "main",
+            CoreModuleRole::Library => "library",
+            CoreModuleRole::Adapter => "adapter",
+            CoreModuleRole::Glue => "glue",
+        }
+    }
+}
+
+/// Limits and flags of one 32-bit linear memory, as declared in the binary; memory64 is rejected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryDescriptor {
+    pub module_index: u32,
+    pub memory_index: u32,
+    pub imported: bool,
+    pub initial_pages: u64,
+    pub maximum_pages: Option<u64>,
+    pub shared: bool,
+    /// Bytes written by active data segments at instantiation
+    pub active_data_bytes: u64,
+    /// Bytes held by passive segments (copied later with `memory.init`)
+    pub passive_data_bytes: u64,
+    /// End of the highest range written by an active segment
+    pub highest_data_offset: u64,
+}
+
+impl MemoryDescriptor {
+    fn from_type(module_index: u32, memory_index: u32, imported: bool, ty: &MemoryType) -> Self {
+        Self {
+            module_index,
+            memory_index,
+            imported,
+            initial_pages: ty.initial,
+            maximum_pages: ty.maximum,
+            shared: ty.shared,
+            active_data_bytes: 0,
+            passive_data_bytes: 0,
+            highest_data_offset: 0,
+        }
+    }
+
+    pub fn initial_bytes(&self) -> u64 {
+        self.initial_pages.saturating_mul(WASM_PAGE_SIZE)
+    }
+
+    pub fn maximum_bytes(&self) -> Option<u64> {
+        self.maximum_pages.map(|pages| pages.saturating_mul(WASM_PAGE_SIZE))
+    }
+
+    /// A memory without a declared maximum can grow until the engine limit
+    /// (4 GiB for 32-bit memories)
+    pub fn is_unbounded(&self) -> bool {
+        self.maximum_pages.is_none()
+    }
+}
+
+/// Element segments targeting one table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableElementStats {
+    pub table_index: u32,
+    pub active_segments: u32,
+    /// Table slots initialized by the active segments
+    pub initialized_slots: u32,
+}
+
+/// Memory relevant statistics of a single core module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreModuleInfo {
+    pub index: u32,
+    pub name: Option<String>,
+    pub role: CoreModuleRole,
+    pub instance_count: u32,
+    pub linear_memory_pages: u64,
+    pub imported_memory_count: u32,
+    pub memories: Vec<MemoryDescriptor>,
+    pub function_tables: Vec<u32>,
+    pub imported_table_count: u32,
+    pub function_count: u32,
+    pub global_count: u32,
+    pub data_segment_count: u32,
+    pub element_segment_count: u32,
+    pub passive_element_segment_count: u32,
+    pub table_elements: Vec<TableElementStats>,
+    pub import_count: u32,
+    pub export_count: u32,
+    pub exported_functions: Vec<String>,
+    pub layout: MemoryLayout,
+    pub stack_analysis: Option<StackAnalysis>,
+    pub growth: Option<GrowthAnalysis>,
+    /// f32/f64 memory accesses inside nested loops, summed over the defined functions
+    #[serde(default)]
+    pub nested_loop_float_accesses: u32,
+    // Raw symbols collected while walking the sections, resolved into `layout` at the end;
+    // only the resolved results are part of the report
+    #[serde(skip)]
+    import_modules: Vec<String>,
+    #[serde(skip)]
+    global_values: Vec<Option<u64>>,
+    #[serde(skip)]
+    first_mutable_global: Option<u32>,
+    #[serde(skip)]
+    global_names: Vec<(u32, String)>,
+    #[serde(skip)]
+    exported_globals: Vec<(String, u32)>,
+    #[serde(skip)]
+    producers: Vec<String>,
+    #[serde(skip)]
+    data_extent: Option<(u64, u64)>,
+    #[serde(skip)]
+    type_param_counts: Vec<u32>,
+    #[serde(skip)]
+    function_types: Vec<u32>,
+    #[serde(skip)]
+    exported_function_indices: Vec<u32>,
+    #[serde(skip, default = "CallGraph::new")]
+    call_graph: CallGraph,
+}
+
+impl CoreModuleInfo {
+    pub fn new(index: u32) -> Self {
+        Self {
+            index,
+            name: None,
+            role: CoreModuleRole::Main,
+            instance_count: 0,
+            linear_memory_pages: 0,
+            imported_memory_count: 0,
+            memories: Vec::new(),
+            function_tables: Vec::new(),
+            imported_table_count: 0,
+            function_count: 0,
+            global_count: 0,
+            data_segment_count: 0,
+            element_segment_count: 0,
+            passive_element_segment_count: 0,
+            table_elements: Vec::new(),
+            import_count: 0,
+            export_count: 0,
+            exported_functions: Vec::new(),
+            layout: MemoryLayout::new(),
+            stack_analysis: None,
+            growth: None,
+            nested_loop_float_accesses: 0,
+            import_modules: Vec::new(),
+            global_values: Vec::new(),
+            first_mutable_global: None,
+            global_names: Vec::new(),
+            exported_globals: Vec::new(),
+            producers: Vec::new(),
+            data_extent: None,
+            type_param_counts: Vec::new(),
+            function_types: Vec::new(),
+            exported_function_indices: Vec::new(),
+            call_graph: CallGraph::new(),
+        }
+    }
+
+    pub fn linear_memory_bytes(&self) -> u64 {
+        self.linear_memory_pages.saturating_mul(WASM_PAGE_SIZE)
+    }
+
+    /// Bytes initialized from active and passive data segments, across all memories
+    pub fn data_bytes(&self) -> u64 {
+        self.memories
+            .iter()
+            .map(|memory| memory.active_data_bytes + memory.passive_data_bytes)
+            .sum()
+    }
+
+    fn table_elements_mut(&mut self, table_index: u32) -> &mut TableElementStats {
+        if let Some(position) = self.table_elements.iter().position(|stats| stats.table_index == table_index) {
+            return &mut self.table_elements[position];
+        }
+        self.table_elements.push(TableElementStats { table_index, active_segments: 0, initialized_slots: 0 });
+        self.table_elements.last_mut().unwrap()
+    }
+
+    fn global_value(&self, index: u32) -> Option<u64> {
+        self.global_values.get(index as usize).copied().flatten()
+    }
+
+    /// Find a global by symbol name, first in the exports then in the `name` section
+    fn named_global(&self, symbol: &str) -> Option<u32> {
+        self.exported_globals
+            .iter()
+            .find(|(name, _)| name == symbol)
+            .map(|(_, index)| *index)
+            .or_else(|| {
+                self.global_names
+                    .iter()
+                    .find(|(_, name)| name == symbol)
+                    .map(|(index, _)| *index)
+            })
+    }
+
+    /// Without symbol names, LLVM always emits the stack pointer as the first mutable global
+    fn stack_pointer_global(&self) -> Option<u32> {
+        self.named_global("__stack_pointer").or(self.first_mutable_global)
+    }
+
+    fn resolve_layout(&self) -> MemoryLayout {
+        let stack_pointer = self.stack_pointer_global().and_then(|index| self.global_value(index));
+        let heap_base = self.named_global("__heap_base").and_then(|index| self.global_value(index));
+        let data_end = self.named_global("__data_end").and_then(|index| self.global_value(index));
+        let toolchain = Toolchain::from_producers(self.producers.iter().map(|value| value.as_str()));
+
+        MemoryLayout::compute(toolchain, stack_pointer, heap_base, data_end, self.data_extent)
+    }
+
+    fn detect_role(&self) -> CoreModuleRole {
+        let is_adapter = self.name.as_deref().is_some_and(|name| name.starts_with("wit-component:adapter"))
+            || self.import_modules.iter().any(|module| module == "__main_module__");
+        if is_adapter {
+            return CoreModuleRole::Adapter;
+        }
+
+        // wit-component shims export their table slots as "0", "1", ... and the
+        // fixup module imports them back from the "" namespace
+        let is_shim = self.export_count > 0
+            && self.import_count == 0
+            && self.linear_memory_pages == 0
+            && self.exported_functions.iter().all(|name| name.parse::<u32>().is_ok());
+        let is_fixup = self.import_count > 0
+            && self.function_count <= 1
+            && self.import_modules.iter().all(|module| module.is_empty());
+        let is_generated = self.name.as_deref().is_some_and(|name| name.starts_with("wit-component:"));
+        if is_shim || is_fixup || is_generated {
+            return CoreModuleRole::Glue;
+        }
+
+        CoreModuleRole::Main
+    }
+}
+
+/// Analyze memory requirements directly from a .wasm binary, section by section.
+/// Works on plain core modules as well as components, where each nested core module
+/// is analyzed separately and then aggregated.
+pub fn analyze_wasm_binary(wasm_path: &str, memory_info: &mut MemoryInfoEstimator) -> Result<(), EstimatorError> {
+    let wasm_bytes = read_file(wasm_path)?;
+    analyze_wasm_bytes(&wasm_bytes, memory_info)
+}
+
+/// Same as `analyze_wasm_binary` but on bytes already in memory
+pub fn analyze_wasm_bytes(wasm_bytes: &[u8], memory_info: &mut MemoryInfoEstimator) -> Result<(), EstimatorError> {
+    let mut modules: Vec<CoreModuleInfo> = Vec::new();
+    // Stack of open modules/components; for components we keep the global indices
+    // of the core modules in their local index space so instantiations can be attributed
+    let mut current_module: Option<CoreModuleInfo> = None;
+    let mut component_stack: Vec<Vec<usize>> = Vec::new();
+
+    for payload in Parser::new(0).parse_all(wasm_bytes) {
+        let payload = match payload {
+            Ok(payload) => payload,
+            Err(e) => {
+                return Err(match &current_module {
+                    Some(module) => EstimatorError::from(e).in_module(module.index),
+                    None if !component_stack.is_empty() => EstimatorError::ComponentParse(e),
+                    None => EstimatorError::from(e),
+                })
+            }
+        };
+
+        if let Some(module) = current_module.as_mut() {
+            match payload {
+                Payload::End(_) => {
+                    let mut module = current_module.take().unwrap();
+                    module.layout = module.resolve_layout();
+                    if !module.call_graph.functions.is_empty() {
+                        let analysis = module
+                            .call_graph
+                            .analyze_stack(module.stack_pointer_global(), &module.exported_function_indices);
+                        module.stack_analysis = Some(analysis);
+                        let exports: Vec<(String, u32)> = module
+                            .exported_functions
+                            .iter()
+                            .cloned()
+                            .zip(module.exported_function_indices.iter().copied())
+                            .collect();
+                        module.growth = Some(analyze_growth(&module.call_graph, &exports));
+                        module.nested_loop_float_accesses = module
+                            .call_graph
+                            .functions
+                            .iter()
+                            .fold(0u32, |total, function| total.saturating_add(function.nested_loop_float_accesses));
+                    }
+                    if let Some(component) = component_stack.last_mut() {
+                        component.push(modules.len());
+                    }
+                    modules.push(module);
+                }
+                other => analyze_module_payload(other, module).map_err(|e| e.in_module(module.index))?,
+            }
+            continue;
+        }
+
+        match payload {
+            Payload::Version { encoding: Encoding::Module, .. } => {
+                current_module = Some(CoreModuleInfo::new(modules.len() as u32));
+            }
+            Payload::Version { encoding: Encoding::Component, .. } => {
+                memory_info.is_component = true;
+                component_stack.push(Vec::new());
+            }
+            Payload::InstanceSection(reader) => {
+                for instance in reader {
+                    memory_info.core_instance_count += 1;
+                    if let Instance::Instantiate { module_index, .. } = instance? {
+                        let target = component_stack
+                            .last()
+                            .and_then(|component| component.get(module_index as usize));
+                        if let Some(&global_index) = target {
+                            modules[global_index].instance_count += 1;
+                        }
+                    }
+                }
+            }
+            Payload::ComponentImportSection(reader) => {
+                for import in reader {
+                    memory_info.signals.imports.insert(import?.name.0.to_string());
+                }
+            }
+            Payload::ComponentExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    if matches!(export.kind, ComponentExternalKind::Func | ComponentExternalKind::Instance) {
+                        memory_info.signals.exports.insert(export.name.0.to_string());
+                    }
+                }
+            }
+            Payload::CustomSection(reader) => match reader.as_known() {
+                KnownCustom::ComponentName(names) => {
+                    for name in names.into_iter().flatten() {
+                        if let ComponentName::Component { name, .. } = name {
+                            memory_info.signals.names.insert(name.to_string());
+                        }
+                    }
+                }
+                KnownCustom::Producers(producers) => {
+                    for field in producers.into_iter().flatten() {
+                        for value in field.values.into_iter().flatten() {
+                            memory_info.signals.producers.insert(value.name.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Payload::End(_) => {
+                component_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let mut seen_main = false;
+    for module in modules.iter_mut() {
+        module.role = module.detect_role();
+        if module.role == CoreModuleRole::Main {
+            if seen_main {
+                module.role = CoreModuleRole::Library;
+            }
+            seen_main = true;
+        }
+    }
+
+    for module in modules.iter().filter(|module| matches!(module.role, CoreModuleRole::Main | CoreModuleRole::Library)) {
+        collect_signals(module, &mut memory_info.signals);
+    }
+    memory_info.core_modules = modules;
+    aggregate_core_modules(memory_info);
+    classify_workload(memory_info);
+
+    Ok(())
+}
+
+fn analyze_module_payload(payload: Payload<'_>, module: &mut CoreModuleInfo) -> Result<(), EstimatorError> {
+    match payload {
+        Payload::TypeSection(reader) => {
+            for rec_group in reader {
+                for sub_type in rec_group?.into_types() {
+                    let param_count = match &sub_type.composite_type.inner {
+                        CompositeInnerType::Func(func_type) => func_type.params().len() as u32,
+                        _ => 0,
+                    };
+                    module.type_param_counts.push(param_count);
+                }
+            }
+        }
+        Payload::ImportSection(reader) => {
+            for import in reader {
+                let import = import?;
+                module.import_count += 1;
+                if !module.import_modules.iter().any(|name| name == import.module) {
+                    module.import_modules.push(import.module.to_string());
+                }
+                match import.ty {
+                    TypeRef::Memory(ty) => {
+                        add_memory(module, true, &ty)?;
+                        module.imported_memory_count += 1;
+                    }
+                    TypeRef::Table(_) => module.imported_table_count += 1,
+                    TypeRef::Global(_) => module.global_values.push(None),
+                    TypeRef::Func(_) => module.call_graph.imported_function_count += 1,
+                    _ => {}
+                }
+            }
+        }
+        Payload::FunctionSection(reader) => {
+            module.function_count += reader.count();
+            for type_index in reader {
+                module.function_types.push(type_index?);
+            }
+        }
+        Payload::CodeSectionEntry(body) => {
+            let defined_index = module.call_graph.functions.len();
+            let param_count = module
+                .function_types
+                .get(defined_index)
+                .and_then(|type_index| module.type_param_counts.get(*type_index as usize))
+                .copied()
+                .unwrap_or(0);
+            module.call_graph.functions.push(FunctionSummary::from_body(&body, param_count)?);
+        }
+        Payload::TableSection(reader) => {
+            for table in reader {
+                let table = table?;
+                module.function_tables.push(table.ty.initial as u32);
+            }
+        }
+        Payload::MemorySection(reader) => {
+            for memory in reader {
+                let memory = memory?;
+                add_memory(module, false, &memory)?;
+                module.linear_memory_pages = module.linear_memory_pages.saturating_add(memory.initial);
+            }
+        }
+        Payload::GlobalSection(reader) => {
+            for global in reader {
+                let global = global?;
+                module.global_count += 1;
+                if global.ty.mutable && module.first_mutable_global.is_none() {
+                    module.first_mutable_global = Some(module.global_values.len() as u32);
+                }
+                module.global_values.push(const_expr_value(&global.init_expr));
+            }
+        }
+        Payload::ExportSection(reader) => {
+            for export in reader {
+                let export = export?;
+                module.export_count += 1;
+                match export.kind {
+                    ExternalKind::Func => {
+                        module.exported_functions.push(export.name.to_string());
+                        module.exported_function_indices.push(export.index);
+                    }
+                    ExternalKind::Global => module.exported_globals.push((export.name.to_string(), export.index)),
+                    _ => {}
+                }
+            }
+        }
+        Payload::ElementSection(reader) => {
+            module.element_segment_count += reader.count();
+            for element in reader {
+                let element = element?;
+                let slots = match &element.items {
+                    ElementItems::Functions(items) => items.count(),
+                    ElementItems::Expressions(_, items) => items.count(),
+                };
+                match element.kind {
+                    ElementKind::Active { table_index, .. } => {
+                        let stats = module.table_elements_mut(table_index.unwrap_or(0));
+                        stats.active_segments += 1;
+                        stats.initialized_slots += slots;
+                    }
+                    ElementKind::Passive => module.passive_element_segment_count += 1,
+                    ElementKind::Declared => {}
+                }
+            }
+        }
+        Payload::DataSection(reader) => {
+            module.data_segment_count += reader.count();
+            for data in reader {
+                let data = data?;
+                let len = data.data.len() as u64;
+                match data.kind {
+                    DataKind::Active { memory_index, offset_expr } => {
+                        let offset = const_expr_value(&offset_expr);
+                        if let Some(memory) = module.memories.get_mut(memory_index as usize) {
+                            memory.active_data_bytes += len;
+                            if let Some(offset) = offset {
+                                memory.highest_data_offset = memory.highest_data_offset.max(offset + len);
+                            }
+                        }
+                        if let (0, Some(offset)) = (memory_index, offset) {
+                            let end = offset + len;
+                            module.data_extent = Some(match module.data_extent {
+                                Some((start, current_end)) => (start.min(offset), current_end.max(end)),
+                                None => (offset, end),
+                            });
+                        }
+                    }
+                    // Passive segments are not bound to a memory; attribute them to the default one
+                    DataKind::Passive => {
+                        if let Some(memory) = module.memories.first_mut() {
+                            memory.passive_data_bytes += len;
+                        }
+                    }
+                }
+            }
+        }
+        Payload::CustomSection(reader) => match reader.as_known() {
+            KnownCustom::Name(names) => {
+                for name in names.into_iter().flatten() {
+                    match name {
+                        Name::Module { name, .. } => module.name = Some(name.to_string()),
+                        Name::Function(map) => {
+                            for naming in map.into_iter().flatten() {
+                                module.call_graph.function_names.push((naming.index, naming.name.to_string()));
+                            }
+                        }
+                        Name::Global(map) => {
+                            for naming in map.into_iter().flatten() {
+                                module.global_names.push((naming.index, naming.name.to_string()));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            KnownCustom::Producers(producers) => {
+                for field in producers.into_iter().flatten() {
+                    for value in field.values.into_iter().flatten() {
+                        module.producers.push(value.name.to_string());
+                    }
+                }
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+/// What a user module tells about the workload; the adapter and glue modules are
+/// the same in every component and would only add noise
+fn collect_signals(module: &CoreModuleInfo, signals: &mut WorkloadSignals) {
+    signals.imports.extend(module.import_modules.iter().filter(|name| !name.is_empty()).cloned());
+    signals.exports.extend(module.exported_functions.iter().cloned());
+    signals.producers.extend(module.producers.iter().cloned());
+    signals.names.extend(module.name.iter().cloned());
+    for (_, name) in module.call_graph.function_names.iter() {
+        signals.add_function_name(name);
+    }
+}
+
+/// Record a memory of the module. The layout, stack and data analyses assume a single
+/// 32-bit memory, so anything else is rejected rather than estimated wrongly.
+fn add_memory(module: &mut CoreModuleInfo, imported: bool, ty: &MemoryType) -> Result<(), EstimatorError> {
+    if ty.memory64 {
+        return Err(EstimatorError::UnsupportedFeature {
+            module_index: module.index,
+            feature: "memory64".to_string(),
+        });
+    }
+    if !module.memories.is_empty() {
+        return Err(EstimatorError::UnsupportedFeature {
+            module_index: module.index,
+            feature: "multi-memory".to_string(),
+        });
+    }
+    let memory_index = module.memories.len() as u32;
+    module.memories.push(MemoryDescriptor::from_type(module.index, memory_index, imported, ty));
+    Ok(())
+}
+
+/// Value of a constant `i32.const`/`i64.const` initializer expression
+fn const_expr_value(expr: &ConstExpr<'_>) -> Option<u64> {
+    match expr.get_operators_reader().read().ok()? {
+        Operator::I32Const { value } => Some(value as u32 as u64),
+        Operator::I64Const { value } => Some(value as u64),
+        _ => None,
+    }
+}
+
+/// Fill the aggregate fields of `memory_info` from its per-module breakdown.
+/// Every instantiation of a module allocates its own memories and tables.
+pub fn aggregate_core_modules(memory_info: &mut MemoryInfoEstimator) {
+    memory_info.linear_memory_pages = 0;
+    memory_info.memories.clear();
+    memory_info.maximum_memory_pages = Some(0);
+    memory_info.warnings.clear();
+    memory_info.function_tables.clear();
+    memory_info.function_count = 0;
+    memory_info.global_count = 0;
+    memory_info.data_segment_count = 0;
+    memory_info.element_segment_count = 0;
+    memory_info.active_data_bytes = 0;
+    memory_info.passive_data_bytes = 0;
+    memory_info.highest_data_offset = 0;
+    memory_info.element_segments_per_table.clear();
+    memory_info.import_count = 0;
+    memory_info.imported_memory_count = 0;
+    memory_info.imported_table_count = 0;
+    memory_info.export_count = 0;
+    memory_info.exported_functions.clear();
+    memory_info.nested_loop_float_accesses = 0;
+
+    for module in memory_info.core_modules.iter() {
+        // Saturates rather than wraps: a component may instantiate a large memory many times
+        let instances = module.instance_count.max(1) as u64;
+        memory_info.linear_memory_pages = memory_info
+            .linear_memory_pages
+            .saturating_add(module.linear_memory_pages.saturating_mul(instances));
+        for memory in module.memories.iter() {
+            memory_info.memories.push(memory.clone());
+            if memory.imported {
+                continue;
+            }
+            memory_info.maximum_memory_pages = match (memory_info.maximum_memory_pages, memory.maximum_pages) {
+                (Some(total), Some(maximum)) => Some(total.saturating_add(maximum.saturating_mul(instances))),
+                _ => None,
+            };
+            if memory.is_unbounded() {
+                memory_info.warnings.push(format!(
+                    "unbounded: memory {} of module {} ({}) declares no maximum and can grow until the engine limit",
+                    memory.memory_index, module.index, module.role.as_str()
+                ));
+            }
+        }
+        for _ in 0..instances {
+            memory_info.function_tables.extend(module.function_tables.iter().copied());
+        }
+        memory_info.function_count += module.function_count;
+        memory_info.global_count += module.global_count;
+        memory_info.data_segment_count += module.data_segment_count;
+        memory_info.element_segment_count += module.element_segment_count;
+        for memory in module.memories.iter() {
+            memory_info.active_data_bytes += memory.active_data_bytes;
+            memory_info.passive_data_bytes += memory.passive_data_bytes;
+            memory_info.highest_data_offset = memory_info.highest_data_offset.max(memory.highest_data_offset);
+        }
+        for stats in module.table_elements.iter() {
+            memory_info.element_segments_per_table.push((module.index, stats.clone()));
+        }
+        memory_info.import_count += module.import_count;
+        memory_info.imported_memory_count += module.imported_memory_count;
+        memory_info.imported_table_count += module.imported_table_count;
+        memory_info.export_count += module.export_count;
+        memory_info.exported_functions.extend(module.exported_functions.iter().cloned());
+        // Only the user's code says what the workload does; the adapter and glue are the same everywhere
+        if matches!(module.role, CoreModuleRole::Main | CoreModuleRole::Library) {
+            memory_info.nested_loop_float_accesses =
+                memory_info.nested_loop_float_accesses.saturating_add(module.nested_loop_float_accesses);
+        }
+    }
+
+    // The layout that matters is the one of the user's module; the adapter
+    // allocates its own state through the main module's `cabi_realloc`
+    let layout = memory_info
+        .core_modules
+        .iter()
+        .find(|module| module.role == CoreModuleRole::Main)
+        .or_else(|| memory_info.core_modules.first())
+        .map(|module| module.layout.clone())
+        .unwrap_or_else(MemoryLayout::new);
+    memory_info.stack_size_bytes = layout.stack_size_bytes;
+    memory_info.static_data_bytes = layout.static_data_bytes;
+    memory_info.heap_start_bytes = layout.heap_start_bytes;
+    memory_info.memory_layout = layout;
+    memory_info.stack_analysis = memory_info
+        .core_modules
+        .iter()
+        .find(|module| module.role == CoreModuleRole::Main)
+        .and_then(|module| module.stack_analysis.clone());
+    if let Some(analysis) = &memory_info.stack_analysis {
+        if analysis.has_unbounded_recursion() {
+            memory_info.warnings.push(format!(
+                "unbounded recursion: {} function(s) in a call cycle ({}); stack depth is only bounded by the reserved stack",
+                analysis.recursive_functions.len(),
+                analysis.recursive_functions.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+
+    memory_info.growth_analysis = memory_info
+        .core_modules
+        .iter()
+        .find(|module| module.role == CoreModuleRole::Main)
+        .and_then(|module| module.growth.clone());
+    if let Some(growth) = &memory_info.growth_analysis {
+        if growth.behavior == GrowthBehavior::Explicit {
+            let sites: Vec<String> = growth
+                .grow_sites
+                .iter()
+                .filter(|site| !site.allocator_driven)
+                .map(|site| site.function_name.clone())
+                .collect();
+            memory_info.warnings.push(format!(
+                "explicit growth: memory.grow outside a recognized allocator in {}; growth does not follow heap usage",
+                sites.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+
+    memory_info.linear_memory_bytes = memory_info.linear_memory_pages.saturating_mul(WASM_PAGE_SIZE);
+    memory_info.maximum_memory_bytes = memory_info.maximum_memory_pages.map(|pages| pages.saturating_mul(WASM_PAGE_SIZE));
+    memory_info.total_function_references = memory_info.function_tables.iter().fold(0u32, |total, &slots| total.saturating_add(slots));
+}
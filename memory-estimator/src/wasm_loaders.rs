@@ -1,9 +1,13 @@
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use anyhow::{anyhow, Context};
 use serde::{Deserialize, Serialize};
 use wasmtime::*;
 use wasmtime::{Config, Engine, Store};
 use wasmtime_wasi::p2::{self, IoView, WasiCtx, WasiCtxBuilder, WasiView};
+use wasmtime_wasi::p2::pipe::MemoryOutputPipe;
 use wasmtime_wasi_nn::wit::{add_to_linker as add_wasi_nn};
 use wasmtime_wasi_nn::wit::{ WasiNnCtx, WasiNnView};
 use wasmtime::component::{Component, Func, Linker, Val};
@@ -11,6 +15,8 @@ use wasmtime_wasi_nn::{InMemoryRegistry, Registry};
 use wasmtime_wasi_nn::Backend;
 use wasmtime_wasi::{DirPerms, FilePerms};
 use wasmtime_wasi_nn::backend::onnx::OnnxBackend;
+use crate::job_error::JobError;
+use crate::guest_memory::{GuestMemoryPeak, GuestMemoryTracker, MemoryLimit};
 
 // pub struct ModuleWasmLoader{
 //     engine:Engine,
@@ -67,6 +73,7 @@ struct HostState {
     wasi: WasiCtx,
     table: wasmtime::component::ResourceTable,
     wasi_nn: WasiNnCtx,
+    limiter: JobLimiter,
 }
 
 impl HostState {
@@ -80,6 +87,46 @@ impl WasiView for HostState {
         &mut self.wasi
     }
 }
+/// Installed on the store so every memory and table growth of the guest is recorded.
+/// With a limit, growth past the estimate traps with `JobError::EstimateExceeded`
+/// and `limits` caps the number of instances, tables and memories.
+struct JobLimiter {
+    guest_memory: GuestMemoryTracker,
+    limits: Option<StoreLimits>,
+}
+
+impl ResourceLimiter for JobLimiter {
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> Result<bool> {
+        Ok(self.guest_memory.memory_growing(current, desired, maximum)?)
+    }
+
+    fn memory_grow_failed(&mut self, _error: anyhow::Error) -> Result<()> {
+        self.guest_memory.memory_grow_failed();
+        Ok(())
+    }
+
+    fn table_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> Result<bool> {
+        Ok(self.guest_memory.table_growing(current, desired, maximum)?)
+    }
+
+    fn table_grow_failed(&mut self, _error: anyhow::Error) -> Result<()> {
+        self.guest_memory.table_grow_failed();
+        Ok(())
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.as_ref().map_or(DEFAULT_INSTANCE_LIMIT, |limits| limits.instances())
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.as_ref().map_or(DEFAULT_TABLE_LIMIT, |limits| limits.tables())
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.as_ref().map_or(DEFAULT_MEMORY_LIMIT, |limits| limits.memories())
+    }
+}
+
 impl IoView for HostState {
     fn table(&mut self) -> &mut wasmtime::component::ResourceTable {
         &mut self.table
@@ -87,11 +134,29 @@ impl IoView for HostState {
 }
 
 
+/// Most of a guest's stdout or stderr that is kept; writes past it fail in the guest
+const GUEST_OUTPUT_CAPACITY: usize = 1024 * 1024;
+/// Time between two epoch increments while a timeout is enforced
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+/// Epoch deadline of a store without a timeout; the epoch only advances with one
+const NO_EPOCH_DEADLINE: u64 = u64::MAX / 2;
+
+/// What the guest wrote, kept apart from the output of the process running it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
 pub struct WasmComponentLoader{
     engine:Engine,
     //  pub store: Store<WasiP1Ctx>,
     store: Store<HostState>,
     linker: Linker<HostState>,
+    guest_stdout: MemoryOutputPipe,
+    guest_stderr: MemoryOutputPipe,
+    /// Set to stop the thread advancing the epoch
+    epoch_ticker_stop: Option<Arc<AtomicBool>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -100,26 +165,52 @@ struct WasmResult {
 }
 
 
+/// Engine configuration shared by the loader and the precompiled artifact checks.
+/// `epoch_interruption` is only on for jobs with a timeout: it changes the compiled code,
+/// so precompiled artifacts match one setting or the other.
+fn engine_config(epoch_interruption: bool) -> Config {
+    let mut config = Config::new();
+    config.async_support(true).wasm_component_model(true);
+    
+    // Disable ALL threading-related features to prevent mutex issues
+    config.wasm_threads(false);
+    config.wasm_multi_memory(false);
+    
+    // Disable additional features that might cause threading issues
+    config.wasm_reference_types(true);
+    config.wasm_bulk_memory(true);
+    
+    // Disable parallel compilation to avoid threading issues
+    config.parallel_compilation(false);
+
+    // Lets a timeout trap the guest wherever it loops
+    config.epoch_interruption(epoch_interruption);
+    config
+}
+
+/// Check that a .cwasm was produced by an engine compatible with ours (same wasmtime
+/// version, target and compiler settings). Wasmtime validates all of this when
+/// deserializing, so we just try it and report its error. `epoch_interruption` is
+/// whether the job runs with a timeout, see `engine_config`.
+pub fn check_precompiled_compatibility(cwasm_path: &str, epoch_interruption: bool) -> Result<(), Error> {
+    let engine = Engine::new(&engine_config(epoch_interruption))?;
+    let bytes = std::fs::read(cwasm_path)
+        .with_context(|| format!("failed to read {:?}", cwasm_path))?;
+    match Engine::detect_precompiled(&bytes) {
+        // Safety: the artifact is only deserialized to validate it, never instantiated
+        Some(Precompiled::Component) => unsafe { Component::deserialize(&engine, &bytes).map(|_| ()) },
+        Some(Precompiled::Module) => unsafe { Module::deserialize(&engine, &bytes).map(|_| ()) },
+        None => Err(anyhow!("{:?} is not a wasmtime precompiled artifact", cwasm_path)),
+    }
+}
+
 impl WasmComponentLoader{
-    pub fn new(folder_to_mount:String)->Self{
+    /// `epoch_interruption` must be set for `enforce_timeout` to have any effect
+    pub fn new(folder_to_mount:String, epoch_interruption: bool)->Self{
         println!("Loading wasm component");
 
         // initialize engine
-        let mut config = Config::new();
-        config.async_support(true).wasm_component_model(true);
-        
-        // Disable ALL threading-related features to prevent mutex issues
-        config.wasm_threads(false);
-        config.wasm_multi_memory(false);
-        
-        // Disable additional features that might cause threading issues
-        config.wasm_reference_types(true);
-        config.wasm_bulk_memory(true);
-        
-        // Disable parallel compilation to avoid threading issues
-        config.parallel_compilation(false);
-        
-        let engine = Engine::new(&config).unwrap();
+        let engine = Engine::new(&engine_config(epoch_interruption)).unwrap();
 
         // initialize linker
         let mut linker: Linker<HostState> = Linker::new(&engine);
@@ -128,12 +219,18 @@ impl WasmComponentLoader{
         add_wasi_nn(&mut linker, |host: &mut HostState| {
             HostState::wasi_nn_view(host)
         }).context("failed to add wasi-nn to linker").unwrap();
+        let guest_stdout = MemoryOutputPipe::new(GUEST_OUTPUT_CAPACITY);
+        let guest_stderr = MemoryOutputPipe::new(GUEST_OUTPUT_CAPACITY);
        let wasi = match folder_to_mount.as_str() {
             "" => {WasiCtxBuilder::new()
-            .inherit_stdio()
+            .inherit_stdin()
+            .stdout(guest_stdout.clone())
+            .stderr(guest_stderr.clone())
             .build()}
             _ => {WasiCtxBuilder::new()
-            .inherit_stdio()
+            .inherit_stdin()
+            .stdout(guest_stdout.clone())
+            .stderr(guest_stderr.clone())
             .preopened_dir(
                 folder_to_mount.clone(),   // host path
                 folder_to_mount.clone(),  // guest path
@@ -179,33 +276,67 @@ impl WasmComponentLoader{
         // Create the WasiNnCtx with the ONNX backend
         let wasi_nn = WasiNnCtx::new(vec![onnx_backend], registry);
 
-        let store: Store<HostState> = Store::new(
+        let mut store: Store<HostState> = Store::new(
             &engine,
             HostState {
                 wasi,
                 table: wasmtime::component::ResourceTable::new(),
                 wasi_nn,
+                limiter: JobLimiter { guest_memory: GuestMemoryTracker::new(), limits: None },
             },
         );
+        store.limiter(|host| &mut host.limiter);
+        store.set_epoch_deadline(NO_EPOCH_DEADLINE);
 
-        Self {engine, store, linker}
+        Self {engine, store, linker, guest_stdout, guest_stderr, epoch_ticker_stop: None}
     }
 
-    pub async fn load_func(&mut self, wasm_component_path:String, func_name:String)->Func{
+    /// Trap the guest with `Trap::Interrupt` once `timeout` has passed from now;
+    /// the loader must have been created with epoch interruption
+    pub fn enforce_timeout(&mut self, timeout: Duration) {
+        let ticks = (timeout.as_millis() / EPOCH_TICK.as_millis()).max(1) as u64;
+        self.store.set_epoch_deadline(ticks);
+        let stop = Arc::new(AtomicBool::new(false));
+        let (engine, ticker_stop) = (self.engine.clone(), stop.clone());
+        std::thread::spawn(move || {
+            while !ticker_stop.load(Ordering::Relaxed) {
+                std::thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        if let Some(previous) = self.epoch_ticker_stop.replace(stop) {
+            previous.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Enforce `limit` on everything instantiated from now on; call before `load_func`
+    pub fn enforce_memory_limit(&mut self, limit: MemoryLimit) {
+        let limits = StoreLimitsBuilder::new()
+            .instances(limit.instances)
+            .tables(limit.tables)
+            .memories(limit.memories)
+            .build();
+        self.store.data_mut().limiter = JobLimiter {
+            guest_memory: GuestMemoryTracker::with_limit(limit),
+            limits: Some(limits),
+        };
+    }
+
+    pub async fn load_func(&mut self, wasm_component_path:String, func_name:String)->Result<Func, Error>{
         let component = Component::from_file(&self.engine, wasm_component_path.clone())
-        .with_context(|| format!("failed to compile component at {:?}", wasm_component_path)).unwrap();
+        .with_context(|| format!("failed to compile component at {:?}", wasm_component_path))?;
 
-        // 4) Instantiate
+        // 4) Instantiate; fails instead of panicking when an enforced limit is hit
         let instance = self.linker.instantiate_async(&mut self.store, &component)
         .await
-        .context("instantiate_async failed").unwrap();
+        .context("instantiate_async failed")?;
 
         // 5) Lookup exported function by its world export name (usually the same as in the WIT).
         let func: Func = instance
             .get_func(&mut self.store, &func_name)
-            .ok_or_else(|| anyhow!("exported function `{func_name}` not found")).unwrap();
+            .ok_or_else(|| anyhow!("exported function `{func_name}` not found"))?;
 
-        return func;
+        return Ok(func);
     }
 
     pub async fn run_func(&mut self, input:Vec<Val>, func:Func)->Result<Vec<Val>, anyhow::Error>{
@@ -219,15 +350,48 @@ impl WasmComponentLoader{
         // println!("load result {:?}", results);
         return Ok(results)
     }
+
+    /// Exact linear memory and table growth of everything instantiated in this store so far
+    pub fn guest_memory(&self) -> GuestMemoryPeak {
+        self.store.data().limiter.guest_memory.peak()
+    }
+
+    /// Everything the guest wrote to stdout and stderr so far
+    pub fn guest_output(&self) -> GuestOutput {
+        GuestOutput {
+            stdout: String::from_utf8_lossy(&self.guest_stdout.contents()).to_string(),
+            stderr: String::from_utf8_lossy(&self.guest_stderr.contents()).to_string(),
+        }
+    }
+}
+
+impl Drop for WasmComponentLoader {
+    fn drop(&mut self) {
+        if let Some(stop) = &self.epoch_ticker_stop {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
 }
 
-pub async fn run_wasm_job_component(task_id: usize, component_name:String, func_name:String, payload:String, folder_to_mount:String)->Result<Vec<Val>, Error>{
+/// Run a job in a fresh store; the guest memory growth and output are returned whether the call succeeded or not.
+/// With `memory_limit` the job fails with `JobError::EstimateExceeded` once it grows past it,
+/// with `timeout` it traps with `Trap::Interrupt` once it runs longer, instantiation included.
+pub async fn run_wasm_job_component(task_id: usize, component_name:String, func_name:String, payload:String, folder_to_mount:String, memory_limit: Option<MemoryLimit>, timeout: Option<Duration>)->(Result<Vec<Val>, Error>, GuestMemoryPeak, GuestOutput){
     // Set up Wasmtime engine and module outside blocking
     // let component_name ="math_tasks".to_string();
     let folder_to_mount = "models".to_string();
-    let mut shared_wasm_loader = WasmComponentLoader::new(folder_to_mount);
+    let mut shared_wasm_loader = WasmComponentLoader::new(folder_to_mount, timeout.is_some());
+    if let Some(memory_limit) = memory_limit {
+        shared_wasm_loader.enforce_memory_limit(memory_limit);
+    }
+    if let Some(timeout) = timeout {
+        shared_wasm_loader.enforce_timeout(timeout);
+    }
     // Use the shared wasm_loader instead of creating a new one
-    let func_to_run: wasmtime::component::Func = shared_wasm_loader.load_func(component_name, func_name).await;
+    let func_to_run = match shared_wasm_loader.load_func(component_name, func_name).await {
+        Ok(func) => func,
+        Err(e) => return (Err(e), shared_wasm_loader.guest_memory(), shared_wasm_loader.guest_output()),
+    };
     let input = vec![input_to_wasm_event_val(payload)];
 
     let result: Result<Vec<Val>, anyhow::Error> = shared_wasm_loader.run_func(input, func_to_run).await;
@@ -238,12 +402,22 @@ pub async fn run_wasm_job_component(task_id: usize, component_name:String, func_
     println!("Finished wasm task {}", task_id);
     
     // Return the result instead of unwrapping
-    match result {
-        Ok(val) => Ok(val),
-        Err(e) => Err(e)
-    }
+    (result, shared_wasm_loader.guest_memory(), shared_wasm_loader.guest_output())
     }
 
+/// The `EstimateExceeded` error behind a failed job, if an enforced limit is what stopped it
+pub fn estimate_exceeded(error: &Error) -> Option<&JobError> {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<JobError>())
+        .find(|cause| matches!(cause, JobError::EstimateExceeded { .. }))
+}
+
+/// Whether the job was interrupted at the deadline set by `enforce_timeout`
+pub fn timed_out(error: &Error) -> bool {
+    matches!(error.downcast_ref::<Trap>(), Some(Trap::Interrupt))
+}
+
 fn input_to_wasm_event_val(input:String) -> wasmtime::component::Val {
     let event_val = wasmtime::component::Val::String(input.into());
     let record_fields = vec![
@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+use serde::{Deserialize, Serialize};
+
+/// Crates every Rust binary links; they say nothing about the workload
+const STANDARD_CRATES: &[&str] = &["core", "alloc", "std", "compiler_builtins", "panic_abort", "panic_unwind"];
+
+/// What a binary declares and links, the input of the workload classification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkloadSignals {
+    /// Imported interfaces (`wasi:nn/graph@...`) and core import modules (`wasi_ephemeral_nn`)
+    pub imports: BTreeSet<String>,
+    /// Exported component functions and interfaces, and core function exports of user modules
+    pub exports: BTreeSet<String>,
+    /// Values of the producers sections: languages, tools and SDKs
+    pub producers: BTreeSet<String>,
+    /// Libraries (Rust crates, C++ namespaces) found in the mangled function names
+    pub libraries: BTreeSet<String>,
+    /// Component and module names
+    pub names: BTreeSet<String>,
+}
+
+impl Default for WorkloadSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkloadSignals {
+    pub fn new() -> Self {
+        Self {
+            imports: BTreeSet::new(),
+            exports: BTreeSet::new(),
+            producers: BTreeSet::new(),
+            libraries: BTreeSet::new(),
+            names: BTreeSet::new(),
+        }
+    }
+
+    /// Record the library a function name belongs to, if it tells one
+    pub fn add_function_name(&mut self, name: &str) {
+        if let Some(library) = library_of(name) {
+            if !STANDARD_CRATES.contains(&library.as_str()) {
+                self.libraries.insert(library);
+            }
+        }
+    }
+}
+
+/// Length-prefixed identifier at the start of `symbol`, as both manglings encode them
+fn length_prefixed(symbol: &str) -> Option<&str> {
+    let digits = symbol.bytes().take_while(u8::is_ascii_digit).count();
+    let len: usize = symbol[..digits].parse().ok()?;
+    let rest = &symbol[digits..];
+    // v0 separates an identifier starting with `_` or a digit from its length
+    let rest = rest.strip_prefix('_').filter(|_| len > 0 && rest.len() > len).unwrap_or(rest);
+    rest.get(..len)
+}
+
+/// First path segment of a mangled name: the crate of a Rust symbol (legacy `_ZN` or
+/// v0 `_R` mangling) or the outer namespace of a C++ one. `None` for unmangled names.
+pub fn library_of(symbol: &str) -> Option<String> {
+    let library = if let Some(legacy) = symbol.strip_prefix("_ZN") {
+        length_prefixed(legacy)?
+    } else if let Some(v0) = symbol.strip_prefix("_R") {
+        // Skip the nested-path tags down to the crate root: `N<namespace>`... `C[s<disambiguator>_]<ident>`
+        let mut path = v0;
+        while let Some(rest) = path.strip_prefix('N') {
+            path = rest.get(1..)?;
+        }
+        let root = path.strip_prefix('C')?;
+        let root = match root.strip_prefix('s') {
+            Some(disambiguated) => &disambiguated[disambiguated.find('_')? + 1..],
+            None => root,
+        };
+        length_prefixed(root)?
+    } else {
+        return None;
+    };
+    let is_identifier = !library.starts_with('_')
+        && library.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+    is_identifier.then(|| library.to_string())
+}
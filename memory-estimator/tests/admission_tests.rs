@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use memory_estimator::admission::{fits, AdmissionScheduler};
+use memory_estimator::job_error::JobError;
+
+const MB: u64 = 1024 * 1024;
+
+#[test]
+fn test_fits_keeps_the_reserve() {
+    assert!(fits(100 * MB, 0, 400 * MB, 256 * MB));
+    assert!(!fits(200 * MB, 0, 400 * MB, 256 * MB));
+    assert!(!fits(100 * MB, 50 * MB, 400 * MB, 256 * MB));
+    // Less available than the reserve fits nothing
+    assert!(!fits(1, 0, 100 * MB, 256 * MB));
+}
+
+#[test]
+fn test_jobs_are_packed_while_they_fit() {
+    let scheduler = AdmissionScheduler::with_available_memory(100 * MB, || 400 * MB);
+    scheduler.admit(120 * MB, &|| false).unwrap();
+    scheduler.admit(150 * MB, &|| false).unwrap();
+
+    let stats = scheduler.stats();
+    assert_eq!(stats.running_jobs, 2);
+    assert_eq!(stats.committed_bytes, 270 * MB);
+    assert_eq!(stats.available_bytes, 400 * MB);
+    assert_eq!(stats.admitted_jobs, 2);
+
+    scheduler.release(120 * MB);
+    let stats = scheduler.stats();
+    assert_eq!((stats.running_jobs, stats.committed_bytes), (1, 150 * MB));
+}
+
+#[test]
+fn test_job_that_never_fits_is_rejected() {
+    let scheduler = AdmissionScheduler::with_available_memory(100 * MB, || 400 * MB);
+    let result = scheduler.admit(301 * MB, &|| false);
+    assert!(matches!(
+        result,
+        Err(JobError::InsufficientMemory { estimated_peak, available, reserve })
+            if estimated_peak == 301 * MB && available == 400 * MB && reserve == 100 * MB
+    ));
+    let stats = scheduler.stats();
+    assert_eq!((stats.rejected_jobs, stats.running_jobs, stats.waiting_jobs), (1, 0, 0));
+}
+
+#[test]
+fn test_job_waits_for_a_release() {
+    let scheduler = Arc::new(AdmissionScheduler::with_available_memory(0, || 300 * MB));
+    scheduler.admit(200 * MB, &|| false).unwrap();
+
+    let waiting = scheduler.clone();
+    let admitted = std::thread::spawn(move || waiting.admit(200 * MB, &|| false));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(scheduler.stats().waiting_jobs, 1);
+
+    scheduler.release(200 * MB);
+    admitted.join().unwrap().unwrap();
+    let stats = scheduler.stats();
+    assert_eq!((stats.running_jobs, stats.committed_bytes, stats.waiting_jobs), (1, 200 * MB, 0));
+}
+
+#[test]
+fn test_available_memory_is_read_on_every_check() {
+    let available = Arc::new(AtomicU64::new(100 * MB));
+    let source = available.clone();
+    let scheduler = AdmissionScheduler::with_available_memory(0, move || source.load(Ordering::Relaxed));
+    assert!(scheduler.admit(200 * MB, &|| false).is_err());
+
+    available.store(500 * MB, Ordering::Relaxed);
+    scheduler.admit(200 * MB, &|| false).unwrap();
+    assert_eq!(scheduler.stats().available_bytes, 500 * MB);
+}
+
+#[test]
+fn test_cancelled_job_stops_waiting() {
+    let scheduler = Arc::new(AdmissionScheduler::with_available_memory(0, || 300 * MB));
+    scheduler.admit(200 * MB, &|| false).unwrap();
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (waiting, cancel) = (scheduler.clone(), cancelled.clone());
+    let admitted = std::thread::spawn(move || waiting.admit(200 * MB, &|| cancel.load(Ordering::Relaxed)));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(scheduler.stats().waiting_jobs, 1);
+
+    // No job finishes: the waiting one notices on its next recheck
+    cancelled.store(true, Ordering::Relaxed);
+    assert!(matches!(admitted.join().unwrap(), Err(JobError::AdmissionCancelled)));
+    let stats = scheduler.stats();
+    assert_eq!((stats.running_jobs, stats.waiting_jobs, stats.rejected_jobs), (1, 0, 0));
+}
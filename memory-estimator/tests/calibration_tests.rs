@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use memory_estimator::calibration::{calibrate, fit_buffer, CalibrationSample};
+use memory_estimator::estimation_accuracy::AccuracyRecord;
+use memory_estimator::estimation_rules::{Feature, RuleSet};
+use memory_estimator::memory_info_estimator::{build_memory_info, build_memory_info_with_payload};
+use memory_estimator::memory_info_monitor::MemoryMonitor;
+
+const MB: u64 = 1024 * 1024;
+
+fn sample(binary_size_bytes: u64, needed_buffer_bytes: u64) -> CalibrationSample {
+    CalibrationSample {
+        binary_name: format!("module_{}.wasm", binary_size_bytes),
+        buffer_rule: "ml".to_string(),
+        features: BTreeMap::from([(Feature::BinarySizeBytes, binary_size_bytes)]),
+        needed_buffer_bytes,
+    }
+}
+
+#[test]
+fn test_fit_constant_buffer_covers_the_quantile() {
+    // One binary size: the buffer is the nearest-rank 95th percentile of the samples
+    let samples: Vec<CalibrationSample> = (1..=20).map(|i| sample(MB, i * MB)).collect();
+    let formula = fit_buffer(&samples, Feature::BinarySizeBytes, 0.95);
+    assert_eq!(formula.base_bytes, 19 * MB);
+    assert!(formula.per_unit.is_empty());
+}
+
+#[test]
+fn test_fit_linear_buffer() {
+    // needed = 2 MB + 3 × binary size, plus noise of at most 1 MB
+    let samples: Vec<CalibrationSample> = [(1, 0), (2, 1), (3, 0), (4, 1), (5, 0), (6, 1)]
+        .iter()
+        .map(|&(size_mb, noise_mb)| sample(size_mb * MB, 2 * MB + 3 * size_mb * MB + noise_mb * MB))
+        .collect();
+    let formula = fit_buffer(&samples, Feature::BinarySizeBytes, 1.0);
+    let slope = formula.per_unit[&Feature::BinarySizeBytes];
+    assert!((slope - 3.0).abs() < 0.1, "slope {}", slope);
+
+    // With the full quantile every sample is covered
+    for sample in samples.iter() {
+        let predicted = formula.base_bytes as f64 + slope * sample.features[&Feature::BinarySizeBytes] as f64;
+        assert!(predicted + 1.0 >= sample.needed_buffer_bytes as f64);
+    }
+}
+
+#[test]
+fn test_calibrate_refits_measured_rules_only() {
+    let rules = RuleSet::default_profile();
+    let report = build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm").expect("estimate");
+    let rule_name = report.classification.buffer_rule.clone().expect("buffer rule");
+
+    let records: Vec<AccuracyRecord> = [4 * MB, 6 * MB, 5 * MB]
+        .iter()
+        .enumerate()
+        .map(|(task_id, extra)| {
+            let mut monitor = MemoryMonitor::new();
+            monitor.kernel_peak_memory_bytes = Some(report.estimate.minimum_bytes + extra);
+            AccuracyRecord::new("fibonacci.wasm", task_id, &report, &monitor).unwrap()
+        })
+        .collect();
+
+    let calibration = calibrate(&rules, &records, "calibrated-test").expect("calibration");
+    assert_eq!(calibration.rules.profile, "calibrated-test");
+    assert_eq!(calibration.fitted.len(), 1);
+    assert_eq!(calibration.fitted[0].rule, rule_name);
+    assert_eq!(calibration.fitted[0].samples, 3);
+    assert_eq!(calibration.fitted[0].binaries, vec!["fibonacci.wasm".to_string()]);
+
+    // The needed buffer is unscaled from the growth behavior before fitting
+    let percent = report
+        .classification
+        .growth_behavior
+        .map(|behavior| rules.growth_scaling_percent.percent(behavior))
+        .unwrap_or(100);
+    let fitted = calibration.rules.buffers.iter().find(|rule| rule.name == rule_name).unwrap();
+    assert_eq!(fitted.buffer.base_bytes, 6 * MB * 100 / percent);
+    for (before, after) in rules.buffers.iter().zip(calibration.rules.buffers.iter()) {
+        if before.name != rule_name {
+            assert_eq!(before, after);
+        }
+    }
+    assert_eq!(calibration.rules.classification, rules.classification);
+
+    // The written profile loads back
+    let reloaded = RuleSet::from_json(&calibration.rules.to_json().unwrap()).unwrap();
+    assert_eq!(reloaded, calibration.rules);
+
+    assert!(calibrate(&rules, &[], "empty").is_err());
+}
+
+#[test]
+fn test_calibration_jobs_cover_every_shipped_component() {
+    let jobs: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string("wasm-modules/calibration_jobs.json").unwrap()).unwrap();
+    let mut components = 0;
+    for entry in std::fs::read_dir("wasm-modules").unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().to_string();
+        let Some(stem) = name.strip_suffix(".wasm") else { continue };
+        let wasm_file = format!("wasm-modules/{}", name);
+        let cwasm_file = format!("wasm-modules/{}.cwasm", stem);
+        if !build_memory_info(&cwasm_file, &wasm_file).unwrap().features.is_component {
+            continue;
+        }
+        components += 1;
+
+        let job = jobs.get(&name).unwrap_or_else(|| panic!("no calibration job for {}", name));
+        assert_eq!(job["func_name"], "run", "{}", name);
+        let payload = job["payload"].as_str().unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(payload).is_ok(), "{}", name);
+        build_memory_info_with_payload(&cwasm_file, &wasm_file, payload, job["model_folder_name"].as_str())
+            .unwrap_or_else(|e| panic!("{}: {}", name, e));
+    }
+    assert_eq!(components, jobs.len());
+}
@@ -0,0 +1,88 @@
+use memory_estimator::call_graph::{demangle, CallGraph, FunctionSummary};
+use memory_estimator::memory_info_estimator::{estimate_native_stack_usage, estimate_stack_usage, MemoryInfoEstimator};
+use memory_estimator::wasm_binary_analyzer::{analyze_wasm_binary, analyze_wasm_bytes};
+
+#[test]
+fn test_shadow_stack_along_call_chain() {
+    // (module
+    //   (global (mut i32) (i32.const 1024))
+    //   (func $a (export "a") global.get 0 i32.const 32 i32.sub global.set 0 call $b)
+    //   (func $b global.get 0 i32.const 16 i32.sub global.set 0))
+    let wasm: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x03, 0x02, 0x00, 0x00,
+        0x06, 0x07, 0x01, 0x7f, 0x01, 0x41, 0x80, 0x08, 0x0b,
+        0x07, 0x05, 0x01, 0x01, b'a', 0x00, 0x00,
+        0x0a, 0x17, 0x02,
+        0x0b, 0x00, 0x23, 0x00, 0x41, 0x20, 0x6b, 0x24, 0x00, 0x10, 0x01, 0x0b,
+        0x09, 0x00, 0x23, 0x00, 0x41, 0x10, 0x6b, 0x24, 0x00, 0x0b,
+    ];
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_bytes(wasm, &mut memory_info).expect("Failed to analyze wasm bytes");
+
+    let analysis = memory_info.stack_analysis.as_ref().expect("stack analysis");
+    assert_eq!(analysis.max_shadow_stack_bytes, 48);
+    assert!(analysis.max_native_stack_bytes > 0);
+    assert_eq!(analysis.deepest_chain, vec!["func[0]".to_string(), "func[1]".to_string()]);
+    assert!(!analysis.has_unbounded_recursion());
+    assert!(!analysis.has_indirect_calls);
+
+    // Bounded call graph: only the worst-case depth counts, not the reserved 1024 bytes
+    assert_eq!(
+        estimate_stack_usage(&memory_info),
+        analysis.max_shadow_stack_bytes + analysis.max_native_stack_bytes
+    );
+    // The shadow stack is in linear memory already, only the native stack adds to it
+    assert_eq!(estimate_native_stack_usage(&memory_info), analysis.max_native_stack_bytes);
+}
+
+#[test]
+fn test_fibonacci_recursion_is_reported() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/fibonacci.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    let analysis = memory_info.stack_analysis.as_ref().expect("stack analysis");
+    assert!(analysis.has_unbounded_recursion());
+    assert!(analysis.recursive_functions.iter().any(|name| name == "fibonacci::fib_recursive"));
+    assert_eq!(analysis.deepest_chain.first().map(|name| name.as_str()), Some("run"));
+    assert!(analysis.max_shadow_stack_bytes > 0);
+    assert!(memory_info.warnings.iter().any(|warning| warning.starts_with("unbounded recursion")));
+
+    // Recursion falls back to the whole reserved stack
+    assert!(estimate_stack_usage(&memory_info) >= memory_info.stack_size_bytes);
+}
+
+#[test]
+fn test_long_call_chain_does_not_overflow() {
+    // func[i] calls func[i + 1], deep enough to overflow a recursive walk
+    const CHAIN_LENGTH: u32 = 200_000;
+    let mut call_graph = CallGraph::new();
+    call_graph.functions = (0..CHAIN_LENGTH)
+        .map(|index| FunctionSummary {
+            param_count: 0,
+            local_count: 0,
+            stack_adjustments: vec![(0, 16)],
+            callees: if index + 1 < CHAIN_LENGTH { vec![index + 1] } else { Vec::new() },
+            calls_indirect: false,
+            memory_grow_count: 0,
+            nested_loop_float_accesses: 0,
+        })
+        .collect();
+
+    let analysis = call_graph.analyze_stack(Some(0), &[0]);
+    assert_eq!(analysis.max_shadow_stack_bytes, 16 * CHAIN_LENGTH as u64);
+    assert_eq!(analysis.deepest_chain.len(), CHAIN_LENGTH as usize);
+    assert_eq!(analysis.deepest_chain.last().map(|name| name.as_str()), Some("func[199999]"));
+    assert!(!analysis.has_unbounded_recursion());
+}
+
+#[test]
+fn test_demangle() {
+    assert_eq!(demangle("_ZN9fibonacci13fib_recursive17h2a7b3cf17f1605cbE"), "fibonacci::fib_recursive");
+    assert_eq!(
+        demangle("_ZN4core3ptr42drop_in_place$LT$alloc..string..String$GT$17h0123456789abcdefE"),
+        "core::ptr::drop_in_place<alloc::string::String>"
+    );
+    assert_eq!(demangle("dlmalloc"), "dlmalloc");
+}
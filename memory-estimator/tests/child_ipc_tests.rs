@@ -0,0 +1,101 @@
+use std::io::Cursor;
+use memory_estimator::child_ipc::{read_frame, val_to_json, write_frame, ChildChannel, ChildListener, ChildMessage, ChildStage, MAX_FRAME_BYTES};
+use memory_estimator::memory_info_monitor::MemoryMonitor;
+use serde_json::json;
+use wasmtime::component::Val;
+
+#[test]
+fn test_frames_round_trip() {
+    let mut monitor = MemoryMonitor::new();
+    monitor.peak_memory_bytes = 42;
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, &ChildMessage::Progress { stage: ChildStage::Running, elapsed_ms: 7 }).unwrap();
+    write_frame(&mut buffer, &ChildMessage::Memory { monitor }).unwrap();
+    let length = u32::from_be_bytes(buffer[..4].try_into().unwrap()) as usize;
+    assert!(buffer[4..4 + length].starts_with(b"{\"type\":\"progress\""));
+
+    let mut reader = Cursor::new(buffer);
+    assert!(matches!(
+        read_frame(&mut reader).unwrap(),
+        Some(ChildMessage::Progress { stage: ChildStage::Running, elapsed_ms: 7 })
+    ));
+    assert!(matches!(read_frame(&mut reader).unwrap(), Some(ChildMessage::Memory { monitor }) if monitor.peak_memory_bytes == 42));
+    assert!(read_frame(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn test_corrupted_frames_are_rejected() {
+    let oversized = ((MAX_FRAME_BYTES + 1) as u32).to_be_bytes().to_vec();
+    assert!(read_frame(&mut Cursor::new(oversized)).is_err());
+
+    // Cut off in the middle of the JSON
+    let mut buffer = Vec::new();
+    write_frame(&mut buffer, &ChildMessage::Error { message: "boom".to_string() }).unwrap();
+    buffer.truncate(buffer.len() - 2);
+    assert!(read_frame(&mut Cursor::new(buffer)).is_err());
+
+    let mut not_json = 3u32.to_be_bytes().to_vec();
+    not_json.extend_from_slice(b"abc");
+    assert!(read_frame(&mut Cursor::new(not_json)).is_err());
+}
+
+#[test]
+fn test_child_messages_reach_the_listener() {
+    let path = std::env::temp_dir().join(format!("memory-estimator-ipc-{}.sock", std::process::id()));
+    let listener = ChildListener::bind(&path).unwrap();
+    let stages = std::sync::Mutex::new(Vec::new());
+
+    let messages = std::thread::scope(|scope| {
+        let receiver = scope.spawn(|| {
+            listener.receive(|message| {
+                if let ChildMessage::Progress { stage, .. } = message {
+                    stages.lock().unwrap().push(*stage);
+                }
+            })
+        });
+        let channel = ChildChannel::connect(listener.path()).unwrap();
+        assert!(channel.is_connected());
+        channel.progress(ChildStage::Started);
+        channel.send(&ChildMessage::Error { message: "rejecting task 1".to_string() });
+        channel.progress(ChildStage::Finished);
+        drop(channel);
+        receiver.join().unwrap()
+    });
+
+    assert_eq!(messages.len(), 3);
+    assert!(matches!(&messages[1], ChildMessage::Error { message } if message == "rejecting task 1"));
+    assert_eq!(*stages.lock().unwrap(), vec![ChildStage::Started, ChildStage::Finished]);
+    drop(listener);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_wake_unblocks_a_listener_without_child() {
+    let path = std::env::temp_dir().join(format!("memory-estimator-ipc-wake-{}.sock", std::process::id()));
+    let listener = ChildListener::bind(&path).unwrap();
+    let messages = std::thread::scope(|scope| {
+        let receiver = scope.spawn(|| listener.receive(|_| {}));
+        listener.wake();
+        receiver.join().unwrap()
+    });
+    assert!(messages.is_empty());
+
+    // A child started by hand has nobody to talk to
+    ChildChannel::disconnected().send(&ChildMessage::Error { message: "dropped".to_string() });
+}
+
+#[test]
+fn test_values_render_as_json() {
+    let record = Val::Record(vec![
+        ("label".to_string(), Val::String("tabby cat".into())),
+        ("score".to_string(), Val::Float32(0.5)),
+        ("top".to_string(), Val::List(vec![Val::U32(281), Val::U32(282)])),
+    ]);
+    assert_eq!(val_to_json(&record), json!({ "label": "tabby cat", "score": 0.5, "top": [281, 282] }));
+    assert_eq!(val_to_json(&Val::Option(None)), json!(null));
+    assert_eq!(val_to_json(&Val::Result(Ok(Some(Box::new(Val::S64(-3)))))), json!({ "ok": -3 }));
+    assert_eq!(val_to_json(&Val::Result(Err(None))), json!({ "err": null }));
+    assert_eq!(val_to_json(&Val::Variant("done".to_string(), None)), json!({ "done": null }));
+    assert_eq!(val_to_json(&Val::Enum("fast".to_string())), json!("fast"));
+    assert_eq!(val_to_json(&Val::Float64(f64::NAN)), json!(null));
+}
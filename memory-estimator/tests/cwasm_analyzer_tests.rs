@@ -0,0 +1,74 @@
+use memory_estimator::cwasm_analyzer::{analyze_cwasm, host_target_mismatch, is_cwasm, parse_cwasm};
+use object::{Object, ObjectSection};
+use memory_estimator::memory_info_estimator::MemoryInfoEstimator;
+use std::fs;
+
+#[test]
+fn test_cwasm_detection() {
+    let cwasm = fs::read("wasm-modules/fibonacci.cwasm").expect("Failed to read cwasm");
+    let wasm = fs::read("wasm-modules/fibonacci.wasm").expect("Failed to read wasm");
+
+    assert!(is_cwasm(&cwasm));
+    assert!(!is_cwasm(&wasm));
+}
+
+#[test]
+fn test_cwasm_sections() {
+    let bytes = fs::read("wasm-modules/fibonacci.cwasm").expect("Failed to read cwasm");
+    let cwasm = parse_cwasm(&bytes).expect("Failed to parse cwasm");
+
+    assert_eq!(cwasm.file_size_bytes, bytes.len() as u64);
+    assert_eq!(cwasm.text_size_bytes, 0x38000);
+    assert_eq!(cwasm.wasm_data_bytes, 0x8000);
+    assert!(cwasm.metadata_bytes > 0);
+
+    // The bundled artifacts were compiled on an Apple silicon machine
+    assert_eq!(cwasm.target_triple.as_deref(), Some("aarch64-apple-darwin"));
+    assert!(cwasm.wasmtime_version.is_some());
+    assert!(cwasm.compiler_flags.iter().any(|(name, value)| name == "opt_level" && value == "speed"));
+}
+
+#[test]
+fn test_native_code_is_reported_separately() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_cwasm("wasm-modules/matrix_transpose.cwasm", &mut memory_info).expect("Failed to analyze cwasm");
+
+    let cwasm = memory_info.cwasm.as_ref().expect("cwasm info");
+    assert_eq!(memory_info.native_code_bytes, cwasm.text_size_bytes);
+    assert!(memory_info.native_code_bytes < cwasm.file_size_bytes);
+}
+
+#[test]
+fn test_plain_wasm_is_rejected() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    assert!(analyze_cwasm("wasm-modules/fibonacci.wasm", &mut memory_info).is_err());
+}
+
+#[test]
+fn test_host_target_mismatch() {
+    let bytes = fs::read("wasm-modules/fibonacci.cwasm").expect("Failed to read cwasm");
+    let mut cwasm = parse_cwasm(&bytes).expect("Failed to parse cwasm");
+
+    cwasm.target_triple = Some("riscv32imac-unknown-none-elf".to_string());
+    assert!(host_target_mismatch(&cwasm).is_some());
+
+    cwasm.target_triple = None;
+    assert!(host_target_mismatch(&cwasm).is_none());
+}
+
+#[test]
+fn test_corrupted_engine_section_lengths() {
+    let mut bytes = fs::read("wasm-modules/fibonacci.cwasm").expect("Failed to read cwasm");
+    let (offset, size) = {
+        let file = object::File::parse(&*bytes).expect("Failed to parse ELF");
+        file.section_by_name(".wasmtime.engine").and_then(|section| section.file_range()).expect("engine section")
+    };
+    let engine = &mut bytes[offset as usize..(offset + size) as usize];
+    // A target triple length of u64::MAX right after the version string
+    let position = 2 + engine[1] as usize;
+    engine[position..position + 10].copy_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+
+    let cwasm = parse_cwasm(&bytes).expect("Failed to parse cwasm");
+    assert!(cwasm.wasmtime_version.is_some());
+    assert_eq!(cwasm.target_triple, None);
+}
@@ -0,0 +1,82 @@
+use memory_estimator::estimation_accuracy::{AccuracyHistory, AccuracyRecord, AccuracySummary, EstimateVerdict};
+use memory_estimator::estimation_report::EstimationReport;
+use memory_estimator::memory_info_estimator::build_memory_info;
+use memory_estimator::memory_info_monitor::MemoryMonitor;
+
+fn fibonacci_report() -> EstimationReport {
+    build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm").expect("estimate")
+}
+
+fn measured(rss_peak_bytes: u64) -> MemoryMonitor {
+    let mut monitor = MemoryMonitor::new();
+    monitor.kernel_peak_memory_bytes = Some(rss_peak_bytes);
+    monitor.virtual_peak_memory_bytes = Some(rss_peak_bytes * 100);
+    monitor
+}
+
+#[test]
+fn test_record_errors_and_verdict() {
+    let report = fibonacci_report();
+    let peak = report.estimate.peak_bytes;
+
+    let over = AccuracyRecord::new("fibonacci.wasm", 1, &report, &measured(peak / 2)).expect("record");
+    assert_eq!(over.estimated_peak_memory_bytes, peak);
+    assert_eq!(over.estimated_minimum_memory_bytes, report.estimate.minimum_bytes);
+    assert_eq!(over.absolute_error_bytes, (peak - peak / 2) as i64);
+    assert!(over.relative_error > 0.9);
+    assert_eq!(over.verdict, EstimateVerdict::Overestimate);
+    assert_eq!(over.measured_vm_peak_bytes, Some(peak / 2 * 100));
+    assert_eq!(over.workload, report.classification.workload);
+
+    let under = AccuracyRecord::new("fibonacci.wasm", 2, &report, &measured(peak * 2)).expect("record");
+    assert_eq!(under.absolute_error_bytes, -(peak as i64));
+    assert!((under.relative_error + 0.5).abs() < 1e-9);
+    assert_eq!(under.verdict, EstimateVerdict::Underestimate);
+
+    let close = AccuracyRecord::new("fibonacci.wasm", 3, &report, &measured(peak + peak / 20)).expect("record");
+    assert_eq!(close.verdict, EstimateVerdict::Accurate);
+
+    // The cgroup peak takes over from the RSS peak
+    let cgroup = over.clone().with_cgroup_peak(peak * 2);
+    assert_eq!(cgroup.measured_cgroup_peak_bytes, Some(peak * 2));
+    assert_eq!(cgroup.measured_peak_bytes(), peak * 2);
+    assert_eq!(cgroup.measured_rss_peak_bytes, peak / 2);
+    assert_eq!(cgroup.verdict, EstimateVerdict::Underestimate);
+    assert_eq!(over.measured_peak_bytes(), peak / 2);
+
+    // Nothing measured, nothing to compare
+    assert!(AccuracyRecord::new("fibonacci.wasm", 4, &report, &MemoryMonitor::new()).is_none());
+
+    let json = serde_json::to_string(&over).unwrap();
+    assert_eq!(serde_json::from_str::<AccuracyRecord>(&json).unwrap(), over);
+}
+
+#[test]
+fn test_history_accumulates_per_binary() {
+    let dir = std::env::temp_dir().join(format!("memory-estimator-accuracy-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let history = AccuracyHistory::new(&dir);
+    let report = fibonacci_report();
+    let peak = report.estimate.peak_bytes;
+
+    assert!(history.records("fibonacci.wasm").unwrap().is_empty());
+    for (task_id, rss) in [(1, peak / 2), (2, peak * 2), (3, peak)].into_iter() {
+        let record = AccuracyRecord::new("fibonacci.wasm", task_id, &report, &measured(rss)).unwrap();
+        history.append(&record).unwrap();
+    }
+    let other = AccuracyRecord::new("nested/other.wasm", 9, &report, &measured(peak)).unwrap();
+    history.append(&other).unwrap();
+
+    let records = history.records("fibonacci.wasm").unwrap();
+    assert_eq!(records.iter().map(|record| record.task_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(history.records("nested/other.wasm").unwrap(), vec![other]);
+
+    let summary = AccuracySummary::from_records(&records).expect("summary");
+    assert_eq!(summary.jobs, 3);
+    assert_eq!((summary.overestimates, summary.underestimates, summary.accurate), (1, 1, 1));
+    assert_eq!(summary.worst_underestimate_bytes, peak);
+    assert!((summary.mean_relative_error - (records[0].relative_error - 0.5) / 3.0).abs() < 1e-9);
+    assert!(AccuracySummary::from_records(&[]).is_none());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,56 @@
+use memory_estimator::estimation_report::{EstimationReport, REPORT_VERSION};
+use memory_estimator::memory_growth::GrowthBehavior;
+use memory_estimator::memory_info_estimator::build_memory_info;
+
+#[test]
+fn test_report_breakdown() {
+    let report = build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm").expect("Failed to build estimate");
+
+    assert_eq!(report.report_version, REPORT_VERSION);
+    assert_eq!(report.wasm_file, "wasm-modules/fibonacci.wasm");
+    assert_eq!(report.rule_profile, "default");
+    assert_eq!(report.classification.growth_behavior, Some(GrowthBehavior::AllocatorDriven));
+    assert!(report.classification.buffer_rule.is_some());
+
+    let estimate = &report.estimate;
+    assert_eq!(
+        estimate.minimum_bytes,
+        estimate.linear_memory_bytes
+            + estimate.native_stack_bytes
+            + estimate.data_segment_bytes
+            + estimate.table_bytes
+            + estimate.native_code_bytes
+    );
+    assert_eq!(estimate.peak_bytes, estimate.minimum_bytes + estimate.buffer_bytes);
+    assert_eq!(estimate.native_code_bytes, 0x38000);
+
+    // Warnings live at the top level only
+    assert!(report.warnings.iter().any(|warning| warning.starts_with("unbounded")));
+    assert!(report.features.warnings.is_empty());
+}
+
+#[test]
+fn test_report_json_round_trip() {
+    let report = build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm").expect("Failed to build estimate");
+    let json = report.to_json().expect("Failed to serialize report");
+    let parsed = EstimationReport::from_json(&json).expect("Failed to parse report");
+
+    assert_eq!(parsed.estimate, report.estimate);
+    assert_eq!(parsed.classification, report.classification);
+    assert_eq!(parsed.warnings, report.warnings);
+    assert_eq!(parsed.features.memories, report.features.memories);
+    assert_eq!(parsed.features.core_modules.len(), report.features.core_modules.len());
+
+    let value: serde_json::Value = serde_json::from_str(&json).expect("valid JSON");
+    assert_eq!(value["features"]["memory_layout"]["toolchain"], "rust");
+    assert_eq!(value["features"]["core_modules"][0]["role"], "main");
+}
+
+#[test]
+fn test_newer_report_version_is_rejected() {
+    let report = build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm").expect("Failed to build estimate");
+    let mut value: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    value["report_version"] = serde_json::json!(REPORT_VERSION + 1);
+
+    assert!(EstimationReport::from_json(&value.to_string()).is_err());
+}
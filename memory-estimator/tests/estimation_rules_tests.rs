@@ -0,0 +1,183 @@
+use memory_estimator::estimation_rules::{Evidence, Feature, Range, RuleSet, Signal, Workload};
+use memory_estimator::memory_info_estimator::MemoryInfoEstimator;
+
+fn features(binary_size_bytes: u64, function_count: u32, data_segment_count: u32) -> MemoryInfoEstimator {
+    let mut memory_info = MemoryInfoEstimator::new();
+    memory_info.binary_size_bytes = binary_size_bytes;
+    memory_info.function_count = function_count;
+    memory_info.data_segment_count = data_segment_count;
+    memory_info
+}
+
+#[test]
+fn test_default_profile_classification() {
+    let rules = RuleSet::default_profile();
+    assert_eq!(rules.profile, "default");
+
+    // Size alone no longer makes a binary ML
+    let mut large = features(700_000, 600, 3);
+    rules.classify(&mut large);
+    assert_eq!(large.workload, Workload::Unclassified);
+    assert!(large.workload_evidence.is_empty());
+
+    let mut ml = features(100_000, 100, 1);
+    ml.signals.imports.insert("wasi:nn/graph@0.2.0-rc-2024-10-28".to_string());
+    rules.classify(&mut ml);
+    assert_eq!(ml.workload, Workload::Ml);
+    assert_eq!(
+        ml.workload_evidence,
+        vec![Evidence::Signal {
+            signal: Signal::Import("wasi:nn/".to_string()),
+            matched: "wasi:nn/graph@0.2.0-rc-2024-10-28".to_string(),
+        }]
+    );
+
+    let mut matrix = features(250_000, 300, 1);
+    matrix.signals.libraries.insert("ndarray".to_string());
+    rules.classify(&mut matrix);
+    assert_eq!(matrix.workload, Workload::Matrix);
+
+    // Float arrays walked in nested loops make a matrix workload whatever the module is called
+    let mut nested_loops = features(250_000, 300, 1);
+    nested_loops.nested_loop_float_accesses = 4;
+    nested_loops.signals.names.insert("kernel".to_string());
+    rules.classify(&mut nested_loops);
+    assert_eq!(nested_loops.workload, Workload::Matrix);
+    assert_eq!(
+        nested_loops.workload_evidence,
+        vec![Evidence::Feature {
+            feature: Feature::NestedLoopFloatAccesses,
+            value: 4,
+            range: Range { ge: Some(1), ..Range::default() },
+        }]
+    );
+
+    // The name is only a hint, used when nothing in the code points at matrices
+    let mut named = features(80_000, 150, 1);
+    named.signals.names.insert("matrix_tools".to_string());
+    rules.classify(&mut named);
+    assert_eq!(named.workload, Workload::Matrix);
+    assert_eq!(
+        named.workload_evidence,
+        vec![Evidence::Signal { signal: Signal::Name("matrix".to_string()), matched: "matrix_tools".to_string() }]
+    );
+
+    let mut simple = features(80_000, 150, 1);
+    rules.classify(&mut simple);
+    assert_eq!(simple.workload, Workload::Simple);
+    assert_eq!(
+        simple.workload_evidence,
+        vec![Evidence::Feature {
+            feature: Feature::FunctionCount,
+            value: 150,
+            range: Range { le: Some(400), ..Range::default() },
+        }]
+    );
+    assert_eq!(simple.workload_evidence[0].to_string(), "function_count = 150 (<= 400)");
+}
+
+#[test]
+fn test_default_profile_buffers() {
+    let rules = RuleSet::default_profile();
+    let cases: &[(Workload, u64, u32, u32, &str, u64)] = &[
+        (Workload::Ml, 700_000, 100, 0, "large ML model", 15),
+        (Workload::Ml, 450_000, 450, 0, "medium ML model", 12),
+        (Workload::Matrix, 250_000, 300, 0, "large matrix operations", 8),
+        (Workload::Matrix, 180_000, 300, 0, "medium matrix operations", 6),
+        (Workload::Simple, 80_000, 150, 60, "complex simple workload", 3),
+        (Workload::Simple, 80_000, 150, 10, "basic simple workload", 2),
+        (Workload::Unclassified, 160_000, 501, 0, "unclassified", 5),
+    ];
+    for (workload, size, functions, references, name, megabytes) in cases {
+        let mut memory_info = features(*size, *functions, 1);
+        memory_info.total_function_references = *references;
+        memory_info.workload = workload.clone();
+        let (rule, bytes) = rules.buffer_bytes(&memory_info);
+        assert_eq!(rule.map(|rule| rule.name.as_str()), Some(*name));
+        assert_eq!(bytes, megabytes * 1024 * 1024);
+    }
+}
+
+#[test]
+fn test_custom_workload_class() {
+    let rules = RuleSet::from_json(
+        r#"{
+            "profile": "video",
+            "classification": [{ "workload": "video", "signals": [{ "library": "ffmpeg" }, { "export": "transcode" }] }],
+            "buffers": [
+                { "name": "video frames", "workload": "video", "buffer": { "base_bytes": 64 } },
+                { "name": "fallback", "buffer": { "base_bytes": 1 } }
+            ]
+        }"#,
+    )
+    .expect("valid rules");
+
+    let mut memory_info = features(0, 0, 0);
+    memory_info.signals.exports.insert("Transcode-Stream".to_string());
+    rules.classify(&mut memory_info);
+    assert_eq!(memory_info.workload, Workload::Custom("video".to_string()));
+    assert_eq!(memory_info.workload.display_name(), "video");
+    assert_eq!(memory_info.workload_evidence[0].to_string(), "export Transcode-Stream");
+    assert_eq!(rules.buffer_bytes(&memory_info).0.map(|rule| rule.name.as_str()), Some("video frames"));
+
+    // Names round-trip, built-in ones included
+    assert_eq!(serde_json::to_string(&Workload::Custom("video".to_string())).unwrap(), "\"video\"");
+    assert_eq!(serde_json::from_str::<Workload>("\"ml\"").unwrap(), Workload::Ml);
+}
+
+#[test]
+fn test_custom_profile_with_formula() {
+    let rules = RuleSet::from_json(
+        r#"{
+            "profile": "rpi5",
+            "classification": [],
+            "buffers": [
+                {
+                    "name": "per function",
+                    "when": [{ "function_count": { "ge": 10 } }],
+                    "buffer": { "base_bytes": 1000, "per_unit": { "function_count": 100.0 }, "max_bytes": 5000 }
+                },
+                { "name": "fallback", "buffer": { "base_bytes": 42 } }
+            ]
+        }"#,
+    )
+    .expect("valid rules");
+
+    let memory_info = features(0, 20, 0);
+    let (rule, bytes) = rules.buffer_bytes(&memory_info);
+    assert_eq!(rule.map(|rule| rule.name.as_str()), Some("per function"));
+    assert_eq!(bytes, 3000);
+
+    // Capped by max_bytes
+    let (_, bytes) = rules.buffer_bytes(&features(0, 100, 0));
+    assert_eq!(bytes, 5000);
+
+    let (rule, bytes) = rules.buffer_bytes(&features(0, 5, 0));
+    assert_eq!(rule.map(|rule| rule.name.as_str()), Some("fallback"));
+    assert_eq!(bytes, 42);
+}
+
+#[test]
+fn test_invalid_profiles_are_rejected() {
+    // Unknown feature
+    assert!(RuleSet::from_json(
+        r#"{ "profile": "bad", "buffers": [{ "name": "x", "when": [{ "cpu_count": { "gt": 1 } }], "buffer": { "base_bytes": 1 } }] }"#
+    )
+    .is_err());
+    // No buffer rules
+    assert!(RuleSet::from_json(r#"{ "profile": "empty", "buffers": [] }"#).is_err());
+    assert!(RuleSet::from_file("rules/missing.json").is_err());
+    assert!(RuleSet::from_file("rules/default.json").is_ok());
+}
+
+#[test]
+fn test_range_bounds() {
+    let range = Range { gt: Some(10), le: Some(20), ..Range::default() };
+    assert!(!range.contains(10));
+    assert!(range.contains(11));
+    assert!(range.contains(20));
+    assert!(!range.contains(21));
+    assert!(!Range { lt: Some(0), ..Range::default() }.contains(0));
+    assert!(Range { ge: Some(5), ..Range::default() }.contains(u64::MAX));
+    assert_eq!(Feature::FunctionCount.value(&features(0, 7, 0)), 7);
+}
@@ -0,0 +1,67 @@
+use memory_estimator::estimation_rules::RuleSet;
+use memory_estimator::estimator_error::EstimatorError;
+use memory_estimator::memory_info_estimator::{build_memory_info, MemoryInfoEstimator};
+use memory_estimator::wasm_binary_analyzer::{analyze_wasm_binary, analyze_wasm_bytes};
+
+const MODULE_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+const COMPONENT_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00];
+
+fn analyze(sections: &[u8], header: [u8; 8]) -> Result<MemoryInfoEstimator, EstimatorError> {
+    let mut wasm = header.to_vec();
+    wasm.extend_from_slice(sections);
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_bytes(&wasm, &mut memory_info)?;
+    Ok(memory_info)
+}
+
+#[test]
+fn test_missing_files_are_rejected() {
+    let result = build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/does_not_exist.wasm");
+    assert!(matches!(result, Err(EstimatorError::MissingFile { path }) if path == "wasm-modules/does_not_exist.wasm"));
+
+    let mut memory_info = MemoryInfoEstimator::new();
+    let result = analyze_wasm_binary("wasm-modules/does_not_exist.wasm", &mut memory_info);
+    assert!(matches!(result, Err(EstimatorError::MissingFile { .. })));
+
+    // The precompiled artifact is required as well
+    let result = build_memory_info("wasm-modules/does_not_exist.cwasm", "wasm-modules/fibonacci.wasm");
+    assert!(matches!(result, Err(EstimatorError::MissingFile { .. })));
+}
+
+#[test]
+fn test_malformed_inputs() {
+    // Type section cut short
+    let result = analyze(&[0x01, 0x05, 0x01, 0x60], MODULE_HEADER);
+    assert!(matches!(result, Err(EstimatorError::MalformedWasm { module_index: Some(0), .. })));
+
+    // Unknown component section id
+    let result = analyze(&[0xff, 0x01, 0x00], COMPONENT_HEADER);
+    assert!(matches!(result, Err(EstimatorError::ComponentParse(_))));
+
+    // A wasm binary passed where the .cwasm is expected
+    let result = build_memory_info("wasm-modules/fibonacci.wasm", "wasm-modules/fibonacci.wasm");
+    assert!(matches!(result, Err(EstimatorError::MalformedCwasm(_))));
+}
+
+#[test]
+fn test_unsupported_memories() {
+    // (memory i64 1)
+    let result = analyze(&[0x05, 0x03, 0x01, 0x04, 0x01], MODULE_HEADER);
+    assert!(matches!(result, Err(EstimatorError::UnsupportedFeature { feature, .. }) if feature == "memory64"));
+
+    // (memory 1) (memory 1)
+    let result = analyze(&[0x05, 0x05, 0x02, 0x00, 0x01, 0x00, 0x01], MODULE_HEADER);
+    assert!(matches!(result, Err(EstimatorError::UnsupportedFeature { feature, .. }) if feature == "multi-memory"));
+}
+
+#[test]
+fn test_rule_config_errors() {
+    let result = RuleSet::from_json(r#"{ "profile": "empty", "buffers": [] }"#);
+    assert!(matches!(result, Err(EstimatorError::RuleConfig(_))));
+
+    let result = RuleSet::from_json("not json");
+    assert!(matches!(result, Err(EstimatorError::RuleConfig(_))));
+
+    let result = RuleSet::from_file("rules/missing.json");
+    assert!(matches!(result, Err(EstimatorError::MissingFile { .. })));
+}
@@ -0,0 +1,106 @@
+use memory_estimator::estimation_report::EstimateBreakdown;
+use memory_estimator::job_error::JobError;
+use memory_estimator::guest_memory::{GrowthTarget, GuestMemoryTracker, MemoryLimit};
+use memory_estimator::memory_info_estimator::build_memory_info;
+
+const PAGE: usize = 64 * 1024;
+
+#[test]
+fn test_peak_adds_up_all_memories() {
+    let mut tracker = GuestMemoryTracker::new();
+    // Two memories instantiated, then the first one grows twice
+    assert!(tracker.memory_growing(0, 17 * PAGE, None).unwrap());
+    assert!(tracker.memory_growing(0, PAGE, Some(PAGE)).unwrap());
+    assert!(tracker.memory_growing(17 * PAGE, 18 * PAGE, None).unwrap());
+    assert!(tracker.memory_growing(18 * PAGE, 20 * PAGE, None).unwrap());
+    assert!(tracker.table_growing(0, 10, Some(10)).unwrap());
+
+    let peak = tracker.peak();
+    assert_eq!(peak.peak_linear_memory_bytes, 21 * PAGE as u64);
+    assert_eq!(peak.final_linear_memory_bytes, 21 * PAGE as u64);
+    assert_eq!(peak.largest_memory_bytes, 20 * PAGE as u64);
+    assert_eq!(peak.peak_table_elements, 10);
+    assert_eq!(peak.memory_grow_requests, 4);
+    assert_eq!(peak.table_grow_requests, 1);
+    assert_eq!(peak.requests.len(), 5);
+    assert_eq!(peak.requests[4].target, GrowthTarget::Table);
+    assert!(peak.requests.windows(2).all(|pair| pair[0].elapsed_us <= pair[1].elapsed_us));
+}
+
+#[test]
+fn test_failed_growth_is_not_counted() {
+    let mut tracker = GuestMemoryTracker::new();
+    tracker.memory_growing(0, PAGE, Some(2 * PAGE)).unwrap();
+    tracker.memory_growing(PAGE, 3 * PAGE, Some(2 * PAGE)).unwrap();
+    tracker.memory_grow_failed();
+
+    let peak = tracker.peak();
+    assert_eq!(tracker.linear_memory_bytes(), PAGE as u64);
+    assert_eq!(peak.final_linear_memory_bytes, PAGE as u64);
+    assert_eq!(peak.denied_requests, 1);
+    assert!(peak.requests[0].granted);
+    assert!(!peak.requests[1].granted);
+    assert_eq!(peak.requests[1].maximum, Some(2 * PAGE as u64));
+}
+
+#[test]
+fn test_compare_with_estimate() {
+    let mut tracker = GuestMemoryTracker::new();
+    tracker.memory_growing(0, 17 * PAGE, None).unwrap();
+    tracker.memory_growing(17 * PAGE, 19 * PAGE, None).unwrap();
+    let estimate = EstimateBreakdown {
+        linear_memory_bytes: 17 * PAGE as u64,
+        stack_usage_bytes: 0,
+        native_stack_bytes: 0,
+        data_segment_bytes: 0,
+        table_bytes: 0,
+        native_code_bytes: 0,
+        payload_bytes: 0,
+        inference_host_bytes: 0,
+        minimum_bytes: 17 * PAGE as u64,
+        buffer_bytes: PAGE as u64,
+        peak_bytes: 18 * PAGE as u64,
+        peak_interval: None,
+    };
+
+    let comparison = tracker.peak().compare(&estimate);
+    assert_eq!(comparison.measured_linear_memory_bytes, 19 * PAGE as u64);
+    assert_eq!(comparison.growth_beyond_estimate_bytes, 2 * PAGE as i64);
+    assert!(!comparison.within_estimated_peak);
+}
+
+#[test]
+fn test_enforced_limit_rejects_growth_past_the_estimate() {
+    let report = build_memory_info("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm").expect("estimate");
+    let limit = MemoryLimit::from_report(&report, 1.5);
+    // Only the linear memory part of the estimate is enforced on the guest, not the whole peak
+    let estimate = &report.estimate;
+    let linear_memory = estimate.linear_memory_bytes + estimate.payload_bytes + estimate.buffer_bytes;
+    assert_eq!(limit.estimated_linear_memory_bytes, linear_memory);
+    assert!(linear_memory < estimate.peak_bytes);
+    assert_eq!(limit.linear_memory_bytes, (linear_memory as f64 * 1.5).ceil() as u64);
+    assert!(limit.instances >= 1 && limit.memories >= 1 && limit.tables >= 1);
+    // A factor below 1 would reject jobs the estimate allows
+    assert_eq!(MemoryLimit::from_report(&report, 0.5).linear_memory_bytes, linear_memory);
+
+    let cap = limit.linear_memory_bytes as usize;
+    let mut tracker = GuestMemoryTracker::with_limit(limit);
+    assert!(tracker.memory_growing(0, cap / 2, None).unwrap());
+    let error = tracker.memory_growing(cap / 2, cap + 1, None).unwrap_err();
+    match &error {
+        JobError::EstimateExceeded { target, requested, estimated, limit } => {
+            assert_eq!(*target, GrowthTarget::Memory);
+            assert_eq!(*requested, cap as u64 + 1);
+            assert_eq!(*estimated, linear_memory);
+            assert_eq!(*limit, cap as u64);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(error.to_string().starts_with("estimate exceeded"));
+    assert!(error.to_string().contains(&(cap + 1).to_string()));
+
+    // The denied request is recorded but does not count towards the peak
+    let peak = tracker.peak();
+    assert_eq!(peak.denied_requests, 1);
+    assert_eq!(peak.peak_linear_memory_bytes, (cap / 2) as u64);
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::process::Command;
+use memory_estimator::job_cgroup::{job_memory_max, parse_flat_keyed, ChildBaseline, JobCgroup, DEFAULT_CHILD_BASELINE_BYTES};
+
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("memory-estimator-cgroup-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_parse_memory_events() {
+    let events = parse_flat_keyed("low 0\nhigh 0\nmax 12\noom 2\noom_kill 1\noom_group_kill 0\n");
+    assert_eq!(events.get("oom"), Some(&2));
+    assert_eq!(events.get("oom_kill"), Some(&1));
+    assert_eq!(events.get("max"), Some(&12));
+    assert!(parse_flat_keyed("garbage\nanon not-a-number\n").is_empty());
+}
+
+#[test]
+fn test_create_fails_outside_cgroup_v2() {
+    let dir = scratch_dir("plain");
+    assert!(JobCgroup::create(&dir.join("memory-estimator"), "job-1", Some(1024)).is_err());
+    assert!(!dir.join("memory-estimator").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_job_cgroup_in_a_delegated_hierarchy() {
+    // A directory laid out like a cgroup v2 mount, with the memory controller available
+    let root = scratch_dir("v2");
+    fs::write(root.join("cgroup.controllers"), "cpu memory pids\n").unwrap();
+    let base = root.join("memory-estimator");
+
+    let cgroup = JobCgroup::create(&base, "job-1", Some(64 * 1024 * 1024)).unwrap();
+    assert_eq!(cgroup.path(), base.join("job-1"));
+    assert_eq!(fs::read_to_string(root.join("cgroup.subtree_control")).unwrap(), "+memory");
+    assert_eq!(fs::read_to_string(base.join("cgroup.subtree_control")).unwrap(), "+memory");
+    assert_eq!(fs::read_to_string(cgroup.path().join("memory.max")).unwrap(), "67108864");
+
+    // What the kernel would report once the job exited
+    fs::write(cgroup.path().join("memory.peak"), "52428800\n").unwrap();
+    fs::write(cgroup.path().join("memory.stat"), "anon 41943040\nfile 4096\nkernel_stack 16384\n").unwrap();
+    fs::write(cgroup.path().join("memory.events"), "low 0\nhigh 0\nmax 3\noom 1\noom_kill 1\n").unwrap();
+    let usage = cgroup.usage();
+    assert_eq!(usage.peak_bytes, Some(50 * 1024 * 1024));
+    assert_eq!(usage.memory_max_bytes, Some(64 * 1024 * 1024));
+    assert_eq!(usage.stat.get("anon"), Some(&(40 * 1024 * 1024)));
+    assert_eq!((usage.oom_events, usage.oom_kill_events), (1, 1));
+    assert!(usage.oom_killed());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_child_joins_before_exec() {
+    let root = scratch_dir("join");
+    fs::write(root.join("cgroup.controllers"), "memory\n").unwrap();
+    let cgroup = JobCgroup::create(&root.join("memory-estimator"), "job-1", None).unwrap();
+
+    // Without cgroup.procs the child can't join, and it never runs
+    let mut command = Command::new("true");
+    cgroup.join_on_spawn(&mut command).unwrap();
+    assert_eq!(command.spawn().unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+    // The kernel creates cgroup.procs; the child writes "0" to it before exec
+    fs::write(cgroup.path().join("cgroup.procs"), "").unwrap();
+    let mut command = Command::new("true");
+    cgroup.join_on_spawn(&mut command).unwrap();
+    assert!(command.status().unwrap().success());
+    assert_eq!(fs::read_to_string(cgroup.path().join("cgroup.procs")).unwrap(), "0");
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_memory_max_leaves_room_for_the_child() {
+    const MB: u64 = 1024 * 1024;
+    assert_eq!(job_memory_max(10 * MB, 1.5, 40 * MB), 55 * MB);
+    // The headroom never shrinks the estimate
+    assert_eq!(job_memory_max(10 * MB, 0.5, 40 * MB), 50 * MB);
+    assert_eq!(job_memory_max(u64::MAX, 1.0, 40 * MB), u64::MAX);
+
+    // The least a child used besides its guest, the default until one reported
+    let baseline = ChildBaseline::measured();
+    assert_eq!(baseline.bytes(), DEFAULT_CHILD_BASELINE_BYTES);
+    baseline.observe(0);
+    assert_eq!(baseline.bytes(), DEFAULT_CHILD_BASELINE_BYTES);
+    baseline.observe(90 * MB);
+    baseline.observe(30 * MB);
+    baseline.observe(200 * MB);
+    assert_eq!(baseline.bytes(), 30 * MB);
+
+    let fixed = ChildBaseline::fixed(48 * MB);
+    fixed.observe(30 * MB);
+    assert_eq!(fixed.bytes(), 48 * MB);
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use std::time::Duration;
+use memory_estimator::child_ipc::ChildStage;
+use memory_estimator::job_queue::{Cancellation, GuestResult, JobQueue, JobState, Termination};
+use memory_estimator::memory_info_monitor::MemoryMonitor;
+
+fn guest_result(success: bool) -> GuestResult {
+    GuestResult {
+        success,
+        values: if success { vec![serde_json::json!(55)] } else { Vec::new() },
+        error: (!success).then(|| "wasm trap: unreachable".to_string()),
+        timed_out: false,
+        stdout: String::new(),
+        stderr: String::new(),
+    }
+}
+
+#[test]
+fn test_jobs_are_taken_in_submission_order() {
+    let queue = JobQueue::new();
+    let first = queue.submit(7, "fibonacci.wasm", "run", "first");
+    let second = queue.submit(8, "fibonacci.wasm", "run", "second");
+    assert!(second > first);
+    assert_eq!(queue.depth(), 2);
+    assert_eq!(queue.get(first).unwrap().state, JobState::Queued);
+
+    assert_eq!(queue.next(), (first, "first"));
+    let job = queue.get(first).unwrap();
+    assert_eq!(job.state, JobState::Estimating);
+    assert!(job.started_at_unix_ms.is_some());
+
+    queue.progress(first, ChildStage::Running);
+    queue.warn(first, &["fibonacci.cwasm is incompatible with this engine".to_string()]);
+    let job = queue.get(first).unwrap();
+    assert_eq!(job.stage, Some(ChildStage::Running));
+    assert_eq!(job.warnings.len(), 1);
+    assert_eq!(queue.depth(), 1);
+
+    let listed: Vec<(u64, JobState)> = queue.list().iter().map(|job| (job.id, job.state)).collect();
+    assert_eq!(listed, vec![(first, JobState::Estimating), (second, JobState::Queued)]);
+    assert!(queue.get(second + 1).is_none());
+}
+
+#[test]
+fn test_worker_waits_for_submissions() {
+    let queue = Arc::new(JobQueue::new());
+    let worker_queue = queue.clone();
+    let worker = std::thread::spawn(move || worker_queue.next());
+    std::thread::sleep(Duration::from_millis(20));
+
+    let id = queue.submit(1, "fibonacci.wasm", "run", 42u32);
+    assert_eq!(worker.join().unwrap(), (id, 42));
+}
+
+#[test]
+fn test_finished_state_follows_the_guest_result() {
+    let queue = JobQueue::new();
+    let ids: Vec<u64> = (0..4).map(|task_id| queue.submit(task_id, "fibonacci.wasm", "run", ())).collect();
+    for _ in ids.iter() {
+        queue.next();
+    }
+    let mut monitor = MemoryMonitor::new();
+    monitor.peak_memory_bytes = 42;
+
+    queue.finish(ids[0], Some(monitor), Some(guest_result(true)), None, None);
+    let job = queue.get(ids[0]).unwrap();
+    assert_eq!(job.state, JobState::Succeeded);
+    assert!(job.state.is_finished());
+    assert_eq!(job.measured.unwrap().peak_memory_bytes, 42);
+    assert_eq!(job.result.unwrap().values, vec![serde_json::json!(55)]);
+    assert!(job.error.is_none() && job.finished_at_unix_ms.is_some());
+
+    queue.finish(ids[1], None, Some(guest_result(false)), None, None);
+    assert_eq!(queue.get(ids[1]).unwrap().state, JobState::Failed);
+
+    queue.finish(ids[2], None, None, None, None);
+    let job = queue.get(ids[2]).unwrap();
+    assert_eq!(job.state, JobState::Failed);
+    assert!(job.error.is_some());
+
+    queue.fail(ids[3], "rejected, no estimate".to_string());
+    let job = queue.get(ids[3]).unwrap();
+    assert_eq!(job.state, JobState::Failed);
+    assert_eq!(job.error.as_deref(), Some("rejected, no estimate"));
+}
+
+#[test]
+fn test_cancellation_depends_on_the_state() {
+    let queue = JobQueue::new();
+    let running = queue.submit(1, "fibonacci.wasm", "run", "running");
+    let queued = queue.submit(2, "fibonacci.wasm", "run", "queued");
+    let last = queue.submit(3, "fibonacci.wasm", "run", "last");
+    assert_eq!(queue.next().0, running);
+
+    // A queued job is dropped and never handed to a worker
+    assert_eq!(queue.cancel(queued), Some(Cancellation::Cancelled));
+    let job = queue.get(queued).unwrap();
+    assert_eq!(job.state, JobState::Cancelled);
+    assert_eq!(job.termination, Some(Termination::Cancelled));
+    assert!(job.state.is_finished());
+    assert_eq!(queue.depth(), 1);
+    assert_eq!(queue.next(), (last, "last"));
+
+    // A started one only gets the request; the worker stops it
+    assert!(!queue.cancel_requested(running));
+    assert_eq!(queue.cancel(running), Some(Cancellation::Requested));
+    assert!(queue.cancel_requested(running));
+    queue.finish(running, None, None, None, Some(Termination::Cancelled));
+    assert_eq!(queue.get(running).unwrap().state, JobState::Cancelled);
+    assert_eq!(queue.cancel(running), Some(Cancellation::AlreadyFinished(JobState::Cancelled)));
+
+    assert_eq!(queue.cancel(last + 1), None);
+}
+
+#[test]
+fn test_timeouts_and_oom_kills_fail_the_job() {
+    let queue = JobQueue::new();
+    let timed_out = queue.submit(1, "fibonacci.wasm", "run", ());
+    let oom_killed = queue.submit(2, "fibonacci.wasm", "run", ());
+    queue.next();
+    queue.next();
+
+    let mut interrupted = guest_result(false);
+    interrupted.timed_out = true;
+    queue.finish(timed_out, None, Some(interrupted), None, Some(Termination::TimedOut));
+    let job = queue.get(timed_out).unwrap();
+    assert_eq!((job.state, job.termination), (JobState::Failed, Some(Termination::TimedOut)));
+    assert_eq!(job.error.as_deref(), Some("the job was stopped: timed_out"));
+
+    queue.finish(oom_killed, None, None, Some("child was OOM-killed".to_string()), Some(Termination::OomKilled));
+    let job = queue.get(oom_killed).unwrap();
+    assert_eq!((job.state, job.termination), (JobState::Failed, Some(Termination::OomKilled)));
+    assert_eq!(queue.list()[1].termination, Some(Termination::OomKilled));
+}
+
+#[test]
+fn test_only_the_latest_finished_jobs_are_retained() {
+    let queue = JobQueue::with_retention(2);
+    let ids: Vec<u64> = (0..4).map(|task_id| queue.submit(task_id, "fibonacci.wasm", "run", task_id)).collect();
+    let unfinished = queue.submit(4, "fibonacci.wasm", "run", 4);
+
+    // Finished out of submission order: eviction follows the finish order
+    queue.next();
+    queue.next();
+    queue.finish(ids[1], None, Some(guest_result(true)), None, None);
+    queue.finish(ids[0], None, Some(guest_result(true)), None, None);
+    assert_eq!(queue.cancel(ids[2]), Some(Cancellation::Cancelled));
+    assert!(queue.get(ids[1]).is_none());
+    assert!(queue.get(ids[0]).is_some());
+
+    // Finishing a job twice doesn't evict anything else
+    queue.finish(ids[2], None, None, None, Some(Termination::Cancelled));
+    assert!(queue.get(ids[0]).is_some());
+
+    let listed: Vec<u64> = queue.list().iter().map(|job| job.id).collect();
+    assert_eq!(listed, vec![ids[0], ids[2], ids[3], unfinished]);
+}
@@ -77,23 +77,22 @@ fn test_ml_component_analysis() {
 }
 
 #[test]
-fn test_wat_memory_analysis() {
-    let wat_file = "wasm_tasks/matrix_multiplication_component.wat";
-    
-    // Only run this test if the WAT file exists
-    if fs::metadata(wat_file).is_ok() {
-        let memory_info = analyze_wat_memory_simple(wat_file).expect("Failed to analyze WAT memory");
-        
-        // Should have some memory pages
-        assert!(memory_info.linear_memory_pages > 0);
-        
-        // Should have function tables
-        assert!(!memory_info.function_tables.is_empty());
-        
-        // Should have reasonable memory estimates
-        assert!(memory_info.estimated_minimum_memory > 0);
-        assert!(memory_info.estimated_peak_memory > memory_info.estimated_minimum_memory);
-    }
+fn test_wasm_binary_memory_analysis() {
+    let wasm_file = "wasm-modules/matrix_multiplication_component.wasm";
+    
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary(wasm_file, &mut memory_info).expect("Failed to analyze wasm binary");
+    calculate_aggregated_memory(&mut memory_info);
+    
+    // Should have some memory pages
+    assert!(memory_info.linear_memory_pages > 0);
+    
+    // Should have function tables
+    assert!(!memory_info.function_tables.is_empty());
+    
+    // Should have reasonable memory estimates
+    assert!(memory_info.estimated_minimum_memory_bytes > 0);
+    assert!(memory_info.estimated_peak_memory_bytes > memory_info.estimated_minimum_memory_bytes);
 }
 
 #[test]
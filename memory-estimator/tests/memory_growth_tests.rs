@@ -0,0 +1,78 @@
+use memory_estimator::memory_growth::{Allocator, GrowthBehavior};
+use memory_estimator::memory_info_estimator::{calculate_aggregated_memory, MemoryInfoEstimator};
+use memory_estimator::wasm_binary_analyzer::{analyze_wasm_binary, analyze_wasm_bytes};
+
+#[test]
+fn test_fibonacci_growth_is_allocator_driven() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/fibonacci.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    let growth = memory_info.growth_analysis.as_ref().expect("growth analysis");
+    assert_eq!(growth.behavior, GrowthBehavior::AllocatorDriven);
+    assert!(growth.allocators.contains(&Allocator::RustDefault));
+    assert!(growth.allocators.contains(&Allocator::Dlmalloc));
+    // dlmalloc grows the heap through wasi-libc's sbrk
+    assert!(growth.grow_sites.iter().any(|site| site.function_name == "sbrk" && site.allocator_driven));
+    assert!(growth.grow_instruction_count() >= 1);
+}
+
+#[test]
+fn test_explicit_memory_grow() {
+    // (module (memory 1) (func (export "grow") i32.const 1 memory.grow drop))
+    let wasm: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x02, 0x01, 0x00,
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        0x07, 0x08, 0x01, 0x04, b'g', b'r', b'o', b'w', 0x00, 0x00,
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x41, 0x01, 0x40, 0x00, 0x1a, 0x0b,
+    ];
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_bytes(wasm, &mut memory_info).expect("Failed to analyze wasm bytes");
+
+    let growth = memory_info.growth_analysis.as_ref().expect("growth analysis");
+    assert_eq!(growth.behavior, GrowthBehavior::Explicit);
+    assert!(growth.allocators.is_empty());
+    assert_eq!(growth.grow_sites.len(), 1);
+    assert_eq!(growth.grow_sites[0].function_index, 0);
+    assert!(!growth.grow_sites[0].allocator_driven);
+    assert!(memory_info.warnings.iter().any(|warning| warning.starts_with("explicit growth")));
+}
+
+#[test]
+fn test_absent_growth_shrinks_peak_buffer() {
+    // (module (memory 1) (func (export "run")))
+    let wasm: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x02, 0x01, 0x00,
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        0x07, 0x07, 0x01, 0x03, b'r', b'u', b'n', 0x00, 0x00,
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_bytes(wasm, &mut memory_info).expect("Failed to analyze wasm bytes");
+    assert_eq!(memory_info.growth_analysis.as_ref().map(|growth| growth.behavior), Some(GrowthBehavior::Absent));
+
+    calculate_aggregated_memory(&mut memory_info);
+    let buffer = memory_info.estimated_peak_memory_bytes - memory_info.estimated_minimum_memory_bytes;
+
+    memory_info.growth_analysis = None;
+    calculate_aggregated_memory(&mut memory_info);
+    let unknown_growth_buffer = memory_info.estimated_peak_memory_bytes - memory_info.estimated_minimum_memory_bytes;
+
+    assert_eq!(buffer * 2, unknown_growth_buffer);
+}
+
+#[test]
+fn test_allocator_symbols() {
+    assert_eq!(Allocator::from_symbol("_RNvCs73fAdSrgOJL_7___rustc12___rust_alloc"), Some(Allocator::RustDefault));
+    assert_eq!(Allocator::from_symbol("__rust_alloc_error_handler"), None);
+    assert_eq!(Allocator::from_symbol("dlmalloc"), Some(Allocator::Dlmalloc));
+    assert_eq!(
+        Allocator::from_symbol("_ZN9wee_alloc17WeeAlloc$LT$_$GT$12alloc_impl17h0123456789abcdefE"),
+        Some(Allocator::WeeAlloc)
+    );
+    assert_eq!(Allocator::from_symbol("emmalloc_malloc"), Some(Allocator::Emmalloc));
+    assert_eq!(Allocator::from_symbol("fib_recursive"), None);
+}
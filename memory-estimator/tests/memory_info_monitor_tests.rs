@@ -0,0 +1,30 @@
+use memory_estimator::memory_info_monitor::{MemoryMonitor, MemorySampler};
+use std::time::Duration;
+
+#[test]
+fn test_current_memory_usage() {
+    let rss = MemoryMonitor::get_current_memory_usage().expect("RSS should be readable");
+    assert!(rss > 0);
+}
+
+#[test]
+fn test_sampler_records_peak() {
+    let sampler = MemorySampler::start(Duration::from_millis(1));
+
+    // Touch 64 MB so it becomes resident, hold it for a few samples, then release it
+    let buffer = vec![1u8; 64 * 1024 * 1024];
+    std::thread::sleep(Duration::from_millis(50));
+    let checksum: u64 = buffer.iter().step_by(4096).map(|&byte| byte as u64).sum();
+    drop(buffer);
+    std::thread::sleep(Duration::from_millis(20));
+
+    let monitor = sampler.stop();
+    assert!(checksum > 0);
+    assert!(monitor.samples.len() >= 2);
+    assert_eq!(monitor.sample_interval_ms, 1);
+    assert_eq!(monitor.initial_memory_bytes, monitor.samples[0].rss_bytes);
+    assert_eq!(monitor.final_memory_bytes, monitor.samples.last().unwrap().rss_bytes);
+    assert!(monitor.peak_memory_bytes >= monitor.initial_memory_bytes + 32 * 1024 * 1024);
+    assert!(monitor.samples.windows(2).all(|pair| pair[0].elapsed_ms <= pair[1].elapsed_ms));
+    assert!(monitor.execution_time_ms >= 70);
+}
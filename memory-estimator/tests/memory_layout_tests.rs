@@ -0,0 +1,55 @@
+use memory_estimator::memory_info_estimator::MemoryInfoEstimator;
+use memory_estimator::memory_layout::{MemoryLayout, Toolchain};
+use memory_estimator::wasm_binary_analyzer::analyze_wasm_binary;
+
+#[test]
+fn test_rust_stack_first_layout() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/fibonacci.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    let layout = &memory_info.memory_layout;
+    assert_eq!(layout.toolchain, Toolchain::Rust);
+    assert!(layout.stack_first);
+    assert_eq!(layout.data_start, Some(1048576));
+
+    // 1 MB shadow stack below the data, heap right after the data
+    assert_eq!(memory_info.stack_size_bytes, 1048576);
+    assert!(memory_info.static_data_bytes > 0);
+    assert!(memory_info.heap_start_bytes >= 1048576 + memory_info.static_data_bytes);
+    assert_eq!(memory_info.heap_start_bytes % 16, 0);
+}
+
+#[test]
+fn test_wasi_libc_layout_with_exported_symbols() {
+    // clang default: data at 1024, 64 KB stack after __data_end, heap after the stack
+    let layout = MemoryLayout::compute(
+        Toolchain::C,
+        Some(1024 + 4096 + 65536),
+        Some(1024 + 4096 + 65536),
+        Some(1024 + 4096),
+        Some((1024, 1024 + 4000)),
+    );
+
+    assert!(!layout.stack_first);
+    assert_eq!(layout.static_data_bytes, 4096);
+    assert_eq!(layout.stack_size_bytes, 65536);
+    assert_eq!(layout.heap_start_bytes, 1024 + 4096 + 65536);
+}
+
+#[test]
+fn test_layout_without_data_end_uses_segments() {
+    let layout = MemoryLayout::compute(Toolchain::C, Some(70000), None, None, Some((1024, 2000)));
+
+    assert!(!layout.stack_first);
+    assert_eq!(layout.static_data_bytes, 976);
+    assert_eq!(layout.stack_size_bytes, 68000);
+    assert_eq!(layout.heap_start_bytes, 70000);
+}
+
+#[test]
+fn test_toolchain_detection() {
+    assert_eq!(Toolchain::from_producers(["Rust", "C11", "rustc", "clang"]), Toolchain::Rust);
+    assert_eq!(Toolchain::from_producers(["C11", "clang"]), Toolchain::C);
+    assert_eq!(Toolchain::from_producers(["Go", "TinyGo"]), Toolchain::TinyGo);
+    assert_eq!(Toolchain::from_producers(["wit-component"]), Toolchain::Unknown);
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+use memory_estimator::estimator_error::EstimatorError;
+use memory_estimator::onnx_model::{analyze_onnx_model, estimate_inference_memory, find_onnx_models};
+
+fn varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn int_field(field: u64, value: i64, out: &mut Vec<u8>) {
+    varint(field << 3, out);
+    varint(value as u64, out);
+}
+
+fn bytes_field(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+    varint(field << 3 | 2, out);
+    varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+/// Float initializer with zeroed raw data
+fn initializer(name: &str, dims: &[i64]) -> Vec<u8> {
+    let mut tensor = Vec::new();
+    dims.iter().for_each(|&dim| int_field(1, dim, &mut tensor));
+    int_field(2, 1, &mut tensor);
+    bytes_field(8, name.as_bytes(), &mut tensor);
+    bytes_field(9, &vec![0u8; dims.iter().product::<i64>() as usize * 4], &mut tensor);
+    tensor
+}
+
+/// Float tensor value info; `None` dimensions are symbolic
+fn value_info(name: &str, dims: &[Option<i64>]) -> Vec<u8> {
+    let mut shape = Vec::new();
+    for dim in dims {
+        let mut dimension = Vec::new();
+        match dim {
+            Some(dim) => int_field(1, *dim, &mut dimension),
+            None => bytes_field(2, b"batch", &mut dimension),
+        }
+        bytes_field(1, &dimension, &mut shape);
+    }
+    let mut tensor_type = Vec::new();
+    int_field(1, 1, &mut tensor_type);
+    bytes_field(2, &shape, &mut tensor_type);
+    let mut type_proto = Vec::new();
+    bytes_field(1, &tensor_type, &mut type_proto);
+    let mut info = Vec::new();
+    bytes_field(1, name.as_bytes(), &mut info);
+    bytes_field(2, &type_proto, &mut info);
+    info
+}
+
+fn ints_attribute(name: &str, ints: &[i64]) -> Vec<u8> {
+    let mut attribute = Vec::new();
+    bytes_field(1, name.as_bytes(), &mut attribute);
+    ints.iter().for_each(|&int| int_field(8, int, &mut attribute));
+    attribute
+}
+
+fn node(op_type: &str, inputs: &[&str], output: &str, attributes: &[Vec<u8>]) -> Vec<u8> {
+    let mut node = Vec::new();
+    inputs.iter().for_each(|input| bytes_field(1, input.as_bytes(), &mut node));
+    bytes_field(2, output.as_bytes(), &mut node);
+    bytes_field(4, op_type.as_bytes(), &mut node);
+    attributes.iter().for_each(|attribute| bytes_field(5, attribute, &mut node));
+    node
+}
+
+/// x[batch, 3, 8, 8] -> Conv(4 filters, 3x3, padded) -> Relu -> GlobalAveragePool -> Flatten -> Gemm(10) -> y,
+/// with `middle` (an operator the shape inference may not know) between Relu and the pooling
+fn small_cnn(middle: &str) -> Vec<u8> {
+    let mut graph = Vec::new();
+    let nodes = [
+        node("Conv", &["x", "conv_w"], "conv", &[ints_attribute("pads", &[1, 1, 1, 1])]),
+        node("Relu", &["conv"], "relu", &[]),
+        node(middle, &["relu"], "middle", &[]),
+        node("GlobalAveragePool", &["middle"], "pool", &[]),
+        node("Flatten", &["pool"], "flat", &[]),
+        node("Gemm", &["flat", "fc_w"], "y", &[]),
+    ];
+    nodes.iter().for_each(|node| bytes_field(1, node, &mut graph));
+    bytes_field(5, &initializer("conv_w", &[4, 3, 3, 3]), &mut graph);
+    bytes_field(5, &initializer("fc_w", &[4, 10]), &mut graph);
+    bytes_field(11, &value_info("x", &[None, Some(3), Some(8), Some(8)]), &mut graph);
+    bytes_field(12, &value_info("y", &[None, Some(10)]), &mut graph);
+    let mut model = Vec::new();
+    int_field(1, 7, &mut model);
+    bytes_field(7, &graph, &mut model);
+    model
+}
+
+fn model_folder(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("onnx_model_tests_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    for (file, bytes) in files {
+        std::fs::write(folder.join(file), bytes).unwrap();
+    }
+    folder
+}
+
+#[test]
+fn test_weights_and_activations() {
+    let model = small_cnn("Identity");
+    let folder = model_folder("weights", &[("cnn.onnx", &model)]);
+    let summary = analyze_onnx_model(folder.join("cnn.onnx").to_str().unwrap(), 2).expect("parse model");
+
+    assert_eq!(summary.file_bytes, model.len() as u64);
+    assert_eq!(summary.node_count, 6);
+    assert_eq!(summary.initializer_count, 2);
+    assert_eq!(summary.weight_bytes, (4 * 3 * 3 * 3 + 4 * 10) * 4);
+    assert_eq!(summary.batch_size, 2);
+    // Largest live set: the padded conv output [2, 4, 8, 8] and the Relu output of the same shape
+    assert_eq!(summary.activation_peak_bytes, 2 * (2 * 4 * 8 * 8 * 4));
+    assert_eq!(summary.arena_bytes, 1024 * 1024);
+    assert!(summary.unknown_ops.is_empty());
+
+    // Activations follow the batch size, weights don't
+    let larger = analyze_onnx_model(folder.join("cnn.onnx").to_str().unwrap(), 8).unwrap();
+    assert_eq!(larger.weight_bytes, summary.weight_bytes);
+    assert_eq!(larger.activation_peak_bytes, 4 * summary.activation_peak_bytes);
+}
+
+#[test]
+fn test_unknown_operator_takes_its_largest_input() {
+    let folder = model_folder("unknown", &[("cnn.onnx", &small_cnn("CustomActivation"))]);
+    let summary = analyze_onnx_model(folder.join("cnn.onnx").to_str().unwrap(), 1).unwrap();
+    assert_eq!(summary.unknown_ops, vec!["CustomActivation".to_string()]);
+    assert_eq!(summary.activation_peak_bytes, 2 * (4 * 8 * 8 * 4));
+}
+
+#[test]
+fn test_inference_memory_of_a_folder() {
+    let model = small_cnn("Identity");
+    let folder = model_folder("folder", &[("cnn.onnx", &model), ("labels.txt", b"cat\ndog\n")]);
+    let folder_name = folder.to_str().unwrap();
+    assert_eq!(find_onnx_models(folder_name), vec![folder.join("cnn.onnx").to_string_lossy().to_string()]);
+
+    let inference = estimate_inference_memory(folder_name, 1).unwrap().expect("a model");
+    assert_eq!(inference.models.len(), 1);
+    let summary = &inference.models[0];
+    assert_eq!(inference.total_bytes, summary.file_bytes + summary.weight_bytes + summary.arena_bytes);
+
+    // The bundled model folder only holds labels
+    assert!(estimate_inference_memory("models/model_1", 1).unwrap().is_none());
+    assert!(estimate_inference_memory("no-such-folder", 1).unwrap().is_none());
+}
+
+#[test]
+fn test_malformed_model() {
+    let mut truncated = small_cnn("Identity");
+    truncated.truncate(truncated.len() / 2);
+    let folder = model_folder("malformed", &[("broken.onnx", &truncated)]);
+
+    match estimate_inference_memory(folder.to_str().unwrap(), 1) {
+        Err(EstimatorError::MalformedModel(reason)) => assert!(reason.contains("broken.onnx")),
+        other => panic!("expected a malformed model error, got {:?}", other.map(|_| ())),
+    }
+}
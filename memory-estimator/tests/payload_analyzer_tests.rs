@@ -0,0 +1,126 @@
+use base64::{engine::general_purpose, Engine as _};
+use memory_estimator::estimation_report::EstimationReport;
+use memory_estimator::estimation_rules::RuleSet;
+use memory_estimator::memory_info_estimator::{build_memory_info, build_memory_info_with_payload};
+use memory_estimator::payload_analyzer::{analyze_payload, MatrixShape, PayloadEstimate, PayloadFeatures};
+
+/// Matrices shaped like `create_large_matrices`: A is rows x inner, B is inner x cols
+fn matrix_payload(rows: usize, inner: usize, cols: usize) -> String {
+    let a: Vec<Vec<f64>> = (0..rows).map(|i| (0..inner).map(|j| (i + j) as f64).collect()).collect();
+    let b: Vec<Vec<f64>> = (0..inner).map(|i| (0..cols).map(|j| (i * j) as f64).collect()).collect();
+    serde_json::json!({ "matrix_a": a, "matrix_b": b }).to_string()
+}
+
+fn image_batch_payload(images: usize) -> String {
+    let image: Vec<u8> = (0..30_000u32).map(|i| (i % 251) as u8).collect();
+    let encoded = general_purpose::STANDARD.encode(&image);
+    serde_json::json!({ "images": vec![encoded; images] }).to_string()
+}
+
+fn estimate(name: &str, payload: &str) -> EstimationReport {
+    build_memory_info_with_payload(
+        &format!("wasm-modules/{}.cwasm", name),
+        &format!("wasm-modules/{}.wasm", name),
+        payload,
+        None,
+    )
+    .expect("estimate")
+}
+
+#[test]
+fn test_matrix_payload_features() {
+    let features = analyze_payload(&matrix_payload(1000, 20, 150), None);
+    assert!(features.json);
+    assert_eq!(features.matrices, vec![MatrixShape { rows: 1000, cols: 20 }, MatrixShape { rows: 20, cols: 150 }]);
+    assert_eq!(features.numbers, 1000 * 20 + 20 * 150);
+    assert_eq!(features.largest_product_elements(), 1000 * 150);
+    assert!(features.blobs.is_empty());
+
+    // The same payload encoded as a JSON string is still understood
+    let double_encoded = serde_json::to_string(&matrix_payload(10, 10, 10)).unwrap();
+    let features = analyze_payload(&double_encoded, None);
+    assert_eq!(features.matrices.len(), 2);
+    assert_eq!(features.largest_product_elements(), 100);
+}
+
+#[test]
+fn test_blob_and_file_features() {
+    let features = analyze_payload(&image_batch_payload(3), None);
+    assert_eq!(features.blobs, vec![30_000; 3]);
+    assert_eq!(features.numbers, 0);
+
+    let files = serde_json::json!({ "images": ["rhino.jpg", "missing.png"], "top_k": 5 }).to_string();
+    let features = analyze_payload(&files, Some("models"));
+    assert_eq!(features.blobs.len(), 2);
+    assert_eq!(features.blobs[1], 0);
+    assert_eq!(features.numbers, 1);
+
+    let text = analyze_payload("just some text", None);
+    assert!(!text.json);
+    assert_eq!(text.payload_bytes, 14);
+    assert!(text.blobs.is_empty() && text.matrices.is_empty());
+}
+
+#[test]
+fn test_payload_terms() {
+    let rules = RuleSet::default_profile();
+    let matrix_rule = rules.payload.iter().find(|rule| rule.name == "matrix input").unwrap();
+    let features = analyze_payload(&matrix_payload(1000, 20, 150), None);
+    let estimate = PayloadEstimate::new(features.clone(), Some(matrix_rule));
+    assert_eq!(estimate.rule.as_deref(), Some("matrix input"));
+    assert_eq!(estimate.copy_bytes, features.payload_bytes);
+    assert_eq!(estimate.parsed_bytes, 23_000 * matrix_rule.bytes_per_number);
+    assert_eq!(estimate.product_bytes, 150_000 * matrix_rule.bytes_per_product_element);
+    assert_eq!(estimate.total_bytes, estimate.copy_bytes + estimate.parsed_bytes + estimate.product_bytes);
+
+    assert_eq!(PayloadEstimate::new(features, None).total_bytes, 0);
+}
+
+#[test]
+fn test_payload_terms_saturate() {
+    let mut rule = RuleSet::default_profile().payload.into_iter().find(|rule| rule.name == "matrix input").unwrap();
+    rule.bytes_per_number = u64::MAX / 2;
+    rule.bytes_per_blob = u64::MAX;
+    let features = PayloadFeatures {
+        payload_bytes: u64::MAX,
+        json: true,
+        numbers: 3,
+        matrices: vec![MatrixShape { rows: u64::MAX, cols: 2 }, MatrixShape { rows: 2, cols: u64::MAX }],
+        blobs: vec![u64::MAX, 1],
+    };
+
+    assert_eq!(features.largest_product_elements(), u64::MAX);
+    let estimate = PayloadEstimate::new(features, Some(&rule));
+    assert_eq!(estimate.parsed_bytes, u64::MAX);
+    assert_eq!(estimate.blob_bytes, u64::MAX);
+    assert_eq!(estimate.total_bytes, u64::MAX);
+}
+
+#[test]
+fn test_matrix_estimate_follows_the_payload() {
+    let without = build_memory_info(
+        "wasm-modules/matrix_multiplication_component.cwasm",
+        "wasm-modules/matrix_multiplication_component.wasm",
+    )
+    .unwrap();
+    let small = estimate("matrix_multiplication_component", &matrix_payload(10, 10, 10));
+    let large = estimate("matrix_multiplication_component", &matrix_payload(1000, 20, 150));
+
+    assert_eq!(without.estimate.payload_bytes, 0);
+    assert!(small.estimate.payload_bytes > 0);
+    assert!(large.estimate.payload_bytes > 2 * 1024 * 1024);
+    assert_eq!(large.estimate.minimum_bytes, without.estimate.minimum_bytes + large.estimate.payload_bytes);
+    assert!(large.estimate.peak_bytes > small.estimate.peak_bytes + 2 * 1024 * 1024);
+    assert!(large.features.payload.is_some());
+}
+
+#[test]
+fn test_image_batch_estimate_follows_the_batch_size() {
+    let single = estimate("image_classification_squeezenet_onnx_batch", &image_batch_payload(1));
+    let batch = estimate("image_classification_squeezenet_onnx_batch", &image_batch_payload(8));
+    let payload = batch.features.payload.as_ref().unwrap();
+    assert_eq!(payload.rule.as_deref(), Some("ML input"));
+    assert_eq!(payload.features.blobs.len(), 8);
+    // Each extra image adds at least its input tensor
+    assert!(batch.estimate.peak_bytes >= single.estimate.peak_bytes + 7 * 602_112);
+}
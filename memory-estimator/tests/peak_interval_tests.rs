@@ -0,0 +1,73 @@
+use memory_estimator::estimation_accuracy::AccuracyRecord;
+use memory_estimator::estimation_report::EstimationReport;
+use memory_estimator::estimation_rules::Feature;
+use memory_estimator::memory_info_estimator::build_memory_info;
+use memory_estimator::memory_info_monitor::MemoryMonitor;
+use memory_estimator::peak_interval::{peak_interval, IntervalSource, PeakInterval, MIN_HISTORY_SAMPLES};
+
+fn report(name: &str) -> EstimationReport {
+    build_memory_info(&format!("wasm-modules/{}.cwasm", name), &format!("wasm-modules/{}.wasm", name)).expect("estimate")
+}
+
+/// A job of `report` that peaked at `percent`% of its estimated minimum
+fn record(report: &EstimationReport, task_id: usize, percent: u64) -> AccuracyRecord {
+    let mut monitor = MemoryMonitor::new();
+    monitor.kernel_peak_memory_bytes = Some(report.estimate.minimum_bytes * percent / 100);
+    AccuracyRecord::new("fibonacci.wasm", task_id, report, &monitor).unwrap()
+}
+
+#[test]
+fn test_report_has_fallback_interval_without_history() {
+    let report = report("fibonacci");
+    let interval = report.estimate.peak_interval.clone().expect("interval");
+    assert_eq!(interval, PeakInterval::fallback(report.estimate.peak_bytes));
+    assert_eq!(interval.source, IntervalSource::Fallback);
+    assert!(interval.p50_bytes <= interval.p95_bytes && interval.p95_bytes <= interval.p99_bytes);
+    assert_eq!(report.features.peak_interval, Some(interval));
+
+    let json = report.to_json().unwrap();
+    assert!(json.contains("\"p95_bytes\""));
+}
+
+#[test]
+fn test_interval_from_similar_jobs() {
+    let report = report("fibonacci");
+    let memory_info = &report.features;
+
+    // Too few similar jobs
+    let history: Vec<AccuracyRecord> = (0..MIN_HISTORY_SAMPLES - 1).map(|i| record(&report, i, 200)).collect();
+    assert_eq!(peak_interval(memory_info, &history).source, IntervalSource::Fallback);
+
+    // 100 jobs peaking at 101%..200% of the minimum
+    let history: Vec<AccuracyRecord> = (1..=100).map(|i| record(&report, i as usize, 100 + i)).collect();
+    let interval = peak_interval(memory_info, &history);
+    assert_eq!(interval.source, IntervalSource::History);
+    assert_eq!(interval.samples, 25);
+    assert!(interval.p50_bytes <= interval.p95_bytes && interval.p95_bytes <= interval.p99_bytes);
+    let minimum = report.estimate.minimum_bytes;
+    assert!(interval.p50_bytes >= minimum * 101 / 100 && interval.p99_bytes <= minimum * 2 + 1);
+
+    // Jobs of another class are not similar
+    let mut other_class = history.clone();
+    for record in other_class.iter_mut() {
+        record.buffer_rule = Some("some_other_rule".to_string());
+    }
+    assert_eq!(peak_interval(memory_info, &other_class).source, IntervalSource::Fallback);
+}
+
+#[test]
+fn test_nearest_modules_are_preferred() {
+    let fibonacci = report("fibonacci");
+    let squeezenet = report("image_classification_squeezenet_onnx");
+
+    // Same class, but one group of jobs comes from a very different module
+    let mut history: Vec<AccuracyRecord> = (0..25).map(|i| record(&fibonacci, i, 110)).collect();
+    history.extend((0..25).map(|i| {
+        let mut far = record(&fibonacci, 100 + i, 300);
+        far.features = Feature::vector(&squeezenet.features);
+        far
+    }));
+    let interval = peak_interval(&fibonacci.features, &history);
+    assert_eq!(interval.samples, 25);
+    assert!(interval.p99_bytes <= fibonacci.estimate.minimum_bytes * 111 / 100);
+}
@@ -0,0 +1,176 @@
+use memory_estimator::memory_info_estimator::{active_data_beyond_initial_bytes, calculate_aggregated_memory, MemoryInfoEstimator};
+use memory_estimator::wasm_binary_analyzer::{aggregate_core_modules, analyze_wasm_binary, analyze_wasm_bytes, CoreModuleInfo, CoreModuleRole, MemoryDescriptor};
+
+#[test]
+fn test_fibonacci_memory_and_tables() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/fibonacci.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    // The main module declares `(memory 17)`, the adapter only imports it
+    assert_eq!(memory_info.linear_memory_pages, 17);
+    assert_eq!(memory_info.linear_memory_bytes, 17 * 65536);
+    assert!(memory_info.imported_memory_count >= 1);
+
+    // Main module table (94) and the shim table (15)
+    assert_eq!(memory_info.function_tables, vec![94, 15]);
+    assert_eq!(memory_info.total_function_references, 109);
+
+    // __stack_pointer starts at 1 MB
+    assert_eq!(memory_info.memory_layout.stack_pointer, Some(1048576));
+}
+
+#[test]
+fn test_section_counts() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/matrix_transpose.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    assert!(memory_info.function_count > 0);
+    assert!(memory_info.data_segment_count > 0);
+    assert!(memory_info.global_count > 0);
+    assert!(memory_info.import_count > 0);
+    assert!(memory_info.exported_functions.iter().any(|name| name == "cabi_realloc"));
+}
+
+#[test]
+fn test_component_core_modules() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/fibonacci.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    assert!(memory_info.is_component);
+    assert_eq!(memory_info.core_modules.len(), 4);
+    assert!(memory_info.core_instance_count > 4);
+
+    let roles: Vec<CoreModuleRole> = memory_info.core_modules.iter().map(|module| module.role).collect();
+    assert_eq!(roles, vec![CoreModuleRole::Main, CoreModuleRole::Adapter, CoreModuleRole::Glue, CoreModuleRole::Glue]);
+
+    // Each core module is instantiated exactly once
+    assert!(memory_info.core_modules.iter().all(|module| module.instance_count == 1));
+
+    let main = memory_info.user_modules().next().expect("main module");
+    assert_eq!(main.linear_memory_pages, 17);
+
+    // The adapter borrows the main module's memory instead of defining one
+    let adapter = memory_info.adapter_module().expect("adapter module");
+    assert_eq!(adapter.linear_memory_pages, 0);
+    assert_eq!(adapter.imported_memory_count, 1);
+}
+
+#[test]
+fn test_memory_limits_and_unbounded_warning() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary("wasm-modules/fibonacci.wasm", &mut memory_info).expect("Failed to analyze wasm binary");
+
+    // Main module defines `(memory 17)` without a maximum, the adapter imports it
+    let defined: Vec<_> = memory_info.memories.iter().filter(|memory| !memory.imported).collect();
+    assert_eq!(defined.len(), 1);
+    assert_eq!(defined[0].initial_pages, 17);
+    assert_eq!(defined[0].maximum_pages, None);
+    assert!(!defined[0].shared);
+
+    assert_eq!(memory_info.maximum_memory_pages, None);
+    assert_eq!(memory_info.unbounded_memories().count(), 1);
+    assert!(memory_info.warnings.iter().any(|warning| warning.starts_with("unbounded")));
+}
+
+#[test]
+fn test_bounded_memory() {
+    // (module (memory 1 2))
+    let wasm: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x05, 0x04, 0x01, 0x01, 0x01, 0x02,
+    ];
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_bytes(wasm, &mut memory_info).expect("Failed to analyze wasm bytes");
+
+    assert!(!memory_info.is_component);
+    assert_eq!(memory_info.linear_memory_pages, 1);
+    assert_eq!(memory_info.maximum_memory_pages, Some(2));
+    assert_eq!(memory_info.maximum_memory_bytes, Some(2 * 65536));
+    assert!(memory_info.warnings.is_empty());
+}
+
+#[test]
+fn test_data_and_element_footprint() {
+    // (module
+    //   (memory 1)
+    //   (table 4 funcref)
+    //   (func)
+    //   (elem (i32.const 0) func 0 0)
+    //   (data (i32.const 16) "abcd")
+    //   (data "xyz"))
+    let wasm: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x02, 0x01, 0x00,
+        0x04, 0x04, 0x01, 0x70, 0x00, 0x04,
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        0x09, 0x08, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x02, 0x00, 0x00,
+        0x0c, 0x01, 0x02,
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        0x0b, 0x0f, 0x02,
+        0x00, 0x41, 0x10, 0x0b, 0x04, b'a', b'b', b'c', b'd',
+        0x01, 0x03, b'x', b'y', b'z',
+    ];
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_bytes(wasm, &mut memory_info).expect("Failed to analyze wasm bytes");
+
+    assert_eq!(memory_info.active_data_bytes, 4);
+    assert_eq!(memory_info.passive_data_bytes, 3);
+    assert_eq!(memory_info.highest_data_offset, 20);
+    assert_eq!(memory_info.memories[0].active_data_bytes, 4);
+
+    assert_eq!(memory_info.element_segments_per_table.len(), 1);
+    let (_, stats) = &memory_info.element_segments_per_table[0];
+    assert_eq!(stats.table_index, 0);
+    assert_eq!(stats.active_segments, 1);
+    assert_eq!(stats.initialized_slots, 2);
+
+    // 3 passive data bytes + 4 table slots of 8 bytes; the active segment lies in the initial page
+    calculate_aggregated_memory(&mut memory_info);
+    assert_eq!(memory_info.static_footprint_bytes, 3 + 4 * 8);
+    assert_eq!(
+        memory_info.estimated_minimum_memory_bytes,
+        65536 + memory_info.native_stack_bytes + 3 + 4 * 8
+    );
+
+    // Only what an active segment writes past the initial pages adds to linear memory
+    memory_info.memories[0].highest_data_offset = 65536 + 2;
+    assert_eq!(active_data_beyond_initial_bytes(&memory_info), 2);
+}
+
+#[test]
+fn test_aggregate_saturates_large_instantiated_memories() {
+    // A 4 GiB memory with a 4 GiB maximum, instantiated more times than 32 bits of pages can count
+    let mut module = CoreModuleInfo::new(0);
+    module.instance_count = 70_000;
+    module.linear_memory_pages = 65_536;
+    module.memories.push(MemoryDescriptor {
+        module_index: 0,
+        memory_index: 0,
+        imported: false,
+        initial_pages: 65_536,
+        maximum_pages: Some(65_536),
+        shared: false,
+        active_data_bytes: 0,
+        passive_data_bytes: 0,
+        highest_data_offset: 0,
+    });
+    let mut memory_info = MemoryInfoEstimator::new();
+    memory_info.core_modules.push(module);
+    aggregate_core_modules(&mut memory_info);
+
+    assert_eq!(memory_info.linear_memory_pages, 65_536 * 70_000);
+    assert_eq!(memory_info.maximum_memory_pages, Some(65_536 * 70_000));
+    assert_eq!(memory_info.linear_memory_bytes, 65_536 * 70_000 * 65_536);
+
+    memory_info.core_modules[0].linear_memory_pages = u64::MAX / 2;
+    aggregate_core_modules(&mut memory_info);
+    assert_eq!(memory_info.linear_memory_pages, u64::MAX);
+    assert_eq!(memory_info.linear_memory_bytes, u64::MAX);
+}
+
+#[test]
+fn test_missing_file() {
+    let mut memory_info = MemoryInfoEstimator::new();
+    assert!(analyze_wasm_binary("wasm-modules/does_not_exist.wasm", &mut memory_info).is_err());
+}
@@ -1 +1,36 @@
+use memory_estimator::guest_memory::DEFAULT_SAFETY_FACTOR;
+use memory_estimator::job_cgroup::{job_memory_max, ChildBaseline};
+use memory_estimator::memory_info_estimator::build_memory_info_with_payload;
+use memory_estimator::memory_info_monitor::MemoryMonitor;
+use memory_estimator::wasm_loaders::run_wasm_job_component;
 
+#[tokio::test]
+async fn test_small_module_completes_under_its_memory_max() {
+    // fibonacci needs far less than the runtime compiling and running it; memory.max sized
+    // to its estimate alone would kill the child
+    let payload = r#"{"n":20}"#;
+    let report = build_memory_info_with_payload("wasm-modules/fibonacci.cwasm", "wasm-modules/fibonacci.wasm", payload, None)
+        .expect("estimate");
+    // What the first job gets, before any child measured the baseline
+    let memory_max = job_memory_max(report.estimate.peak_bytes, DEFAULT_SAFETY_FACTOR, ChildBaseline::measured().bytes());
+
+    // This process runs the job the way a child does, so its high water mark stands in for the child's
+    let (result, guest_memory, _) = run_wasm_job_component(
+        1,
+        "wasm-modules/fibonacci.wasm".to_string(),
+        "run".to_string(),
+        payload.to_string(),
+        "models".to_string(),
+        None,
+        None,
+    )
+    .await;
+    assert!(result.is_ok(), "{:?}", result.err());
+    let peak = MemoryMonitor::get_kernel_peak_memory_usage().expect("VmHWM");
+    assert!(peak <= memory_max, "peak {} over memory.max {}", peak, memory_max);
+
+    // The baseline this child measures leaves the same room for the next one
+    let baseline = ChildBaseline::measured();
+    baseline.observe(peak.saturating_sub(guest_memory.peak_linear_memory_bytes));
+    assert!(job_memory_max(report.estimate.peak_bytes, DEFAULT_SAFETY_FACTOR, baseline.bytes()) >= peak);
+}
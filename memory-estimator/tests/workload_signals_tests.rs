@@ -0,0 +1,103 @@
+use memory_estimator::estimation_rules::{Evidence, Feature, Signal, Workload};
+use memory_estimator::memory_info_estimator::{build_memory_info, MemoryInfoEstimator};
+use memory_estimator::wasm_binary_analyzer::analyze_wasm_binary;
+use memory_estimator::workload_signals::{library_of, WorkloadSignals};
+
+fn analyze(name: &str) -> MemoryInfoEstimator {
+    let mut memory_info = MemoryInfoEstimator::new();
+    analyze_wasm_binary(&format!("wasm-modules/{}.wasm", name), &mut memory_info).expect("analysis");
+    memory_info
+}
+
+#[test]
+fn test_library_of_mangled_names() {
+    assert_eq!(library_of("_ZN9fibonacci13fib_recursive17h2a7b3cf17f1605cbE").as_deref(), Some("fibonacci"));
+    assert_eq!(library_of("_ZN10serde_json2de21Deserializer5parse17h0123456789abcdefE").as_deref(), Some("serde_json"));
+    assert_eq!(library_of("_RNvCs73fAdSrgOJL_7ndarray3dot").as_deref(), Some("ndarray"));
+    assert_eq!(library_of("_RNvNtCs1234_5tract4plan3run").as_deref(), Some("tract"));
+    // rustc's own shims and impl blocks are not libraries
+    assert_eq!(library_of("_RNvCs73fAdSrgOJL_7___rustc12___rust_abort"), None);
+    assert_eq!(library_of("_ZN60_$LT$alloc..string..String$u20$as$u20$core..fmt..Display$GT$3fmt17hE"), None);
+    assert_eq!(library_of("memcpy"), None);
+
+    let mut signals = WorkloadSignals::new();
+    signals.add_function_name("_ZN4core3fmt5write17h0123456789abcdefE");
+    signals.add_function_name("_ZN8nalgebra6linalg3svd17h0123456789abcdefE");
+    assert_eq!(signals.libraries.iter().collect::<Vec<_>>(), vec!["nalgebra"]);
+}
+
+#[test]
+fn test_signals_of_bundled_modules() {
+    let squeezenet = analyze("image_classification_squeezenet_onnx");
+    assert!(squeezenet.signals.imports.contains("wasi:nn/graph@0.2.0-rc-2024-10-28"));
+    assert!(squeezenet.signals.exports.contains("run"));
+    assert!(squeezenet.signals.names.contains("image_classification_squeezenet_onnx"));
+    assert!(squeezenet.signals.producers.contains("Rust"));
+
+    let fibonacci = analyze("fibonacci");
+    assert!(fibonacci.signals.libraries.contains("fibonacci"));
+    assert!(fibonacci.signals.libraries.contains("serde_json"));
+    assert!(!fibonacci.signals.libraries.contains("core"));
+    // The adapter's imports are left out
+    assert!(!fibonacci.signals.imports.contains("__main_module__"));
+}
+
+#[test]
+fn test_classification_of_bundled_modules() {
+    let squeezenet = analyze("image_classification_squeezenet_onnx");
+    assert_eq!(squeezenet.workload, Workload::Ml);
+    assert!(squeezenet
+        .workload_evidence
+        .iter()
+        .all(|evidence| matches!(evidence, Evidence::Signal { matched, .. } if matched.starts_with("wasi:nn/"))));
+
+    assert_eq!(analyze("matrix_transpose").workload, Workload::Matrix);
+    assert_eq!(analyze("matrix_multiplication_component").workload, Workload::Matrix);
+    assert_eq!(analyze("prime_number_checker").workload, Workload::Simple);
+    // As large as the matrix components, but it never walks float arrays in nested loops
+    let fibonacci = analyze("fibonacci");
+    assert_eq!(fibonacci.workload, Workload::Simple);
+    assert!(matches!(fibonacci.workload_evidence[..], [Evidence::Feature { .. }]));
+    assert_eq!(fibonacci.nested_loop_float_accesses, 0);
+
+    let report = build_memory_info("wasm-modules/matrix_transpose.cwasm", "wasm-modules/matrix_transpose.wasm").unwrap();
+    assert_eq!(report.classification.class, Workload::Matrix);
+    assert_eq!(report.classification.workload, "Matrix Operations");
+    assert_eq!(report.classification.evidence[0].to_string(), "nested_loop_float_accesses = 3 (>= 1)");
+}
+
+#[test]
+fn test_classification_of_every_shipped_module() {
+    // Class and the evidence that must be behind it; a module added to wasm-modules/ needs a line here
+    let expected: &[(&str, Workload, &str)] = &[
+        ("fibonacci", Workload::Simple, "function_count = 352 (<= 400)"),
+        ("fibonacci_optimized", Workload::Simple, "function_count = 371 (<= 400)"),
+        ("image_classification_resnet_onnx", Workload::Ml, "import wasi:nn/graph@0.2.0-rc-2024-10-28"),
+        ("image_classification_resnet_onnx_batch", Workload::Ml, "import wasi:nn/graph@0.2.0-rc-2024-10-28"),
+        ("image_classification_squeezenet_onnx", Workload::Ml, "import wasi:nn/graph@0.2.0-rc-2024-10-28"),
+        ("image_classification_squeezenet_onnx_batch", Workload::Ml, "import wasi:nn/graph@0.2.0-rc-2024-10-28"),
+        ("matrix_multiplication_component", Workload::Matrix, "nested_loop_float_accesses = 5 (>= 1)"),
+        ("matrix_transpose", Workload::Matrix, "nested_loop_float_accesses = 3 (>= 1)"),
+        ("prime_number_checker", Workload::Simple, "function_count = 146 (<= 400)"),
+    ];
+    let mut shipped: Vec<String> = std::fs::read_dir("wasm-modules")
+        .expect("wasm-modules")
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".wasm").map(str::to_string))
+        .collect();
+    shipped.sort();
+    assert_eq!(shipped, expected.iter().map(|(name, _, _)| name.to_string()).collect::<Vec<_>>());
+
+    for (name, workload, evidence) in expected {
+        let memory_info = analyze(name);
+        assert_eq!(memory_info.workload, *workload, "{}", name);
+        let found: Vec<String> = memory_info.workload_evidence.iter().map(|evidence| evidence.to_string()).collect();
+        assert!(found.iter().any(|found| found.starts_with(evidence)), "{}: {:?}", name, found);
+        // No class other than simple rests on a size threshold, and none on the module name
+        for evidence in memory_info.workload_evidence.iter() {
+            assert!(!matches!(evidence, Evidence::Signal { signal: Signal::Name(_), .. }), "{}", name);
+            if *workload != Workload::Simple {
+                assert!(!matches!(evidence, Evidence::Feature { feature: Feature::FunctionCount, .. }), "{}", name);
+            }
+        }
+    }
+}